@@ -7,3 +7,4 @@ pub use wgpu_back::WGPURunner;
 pub use proof_of_concept::ExecutionInformation;
 pub use proof_of_concept::Executor;
 pub use proof_of_concept::ShaderProgram as NodeExecutionOutput;
+pub use proof_of_concept::{WgslTemplate, WgslTemplatePiece};
@@ -1,11 +1,17 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use crate::{
     nodegraph::{
-        FallibleNodeTypeRc, NodeAnnotation, NodeAnnotationHas, NodeGraph, NodeRef,
-        NodeTypeAnnotation, ValueRef,
+        FallibleNodeTypeRc, NodeAnnotationHas, NodeGraph, NodeInputReference, NodeRef,
+        NodeTypeAnnotation,
+    },
+    typechecking::{
+        InputTypeNotes, NodeGraphFormalTypeAnalysis,
+        domains::OutOfDomainPolicy,
+        index_ops::IndexOp,
+        typetypes::{PrimitiveType, TypeError, TypeErrorKind, U32Boundedness},
+        vectors::node_conversion,
     },
-    typechecking::{NodeGraphFormalTypeAnalysis, typetypes::TypeError},
 };
 
 #[derive(Clone)]
@@ -47,14 +53,229 @@ pub enum ExecutionInformation {
     Add,
     Exp,
     Constant(f32),
+    // Constant whose value lives in the node's extra data (`Constant: 0.8()`).
+    ConstantFromData,
     Attr(String),
     Out,
     ERR,
     Vector3,
+    Wgsl(WgslTemplate),
+    Index(IndexOp),
+    // `comp: [N] -> T` to `vecN<T>`.
+    Pack,
+    // `vecN<T>` to `comp: [N] -> T`.
+    Unpack,
+}
+
+#[derive(Debug, Clone)]
+pub enum WgslTemplatePiece {
+    Text(String),
+    Input(usize),
+}
+
+// WGSL body with `{input_name}` holes, each filled with a call to that input's function (cast to
+// the input's type if need be).
+// A body without a `return` is treated as a single expression.
+#[derive(Debug, Clone)]
+pub struct WgslTemplate {
+    pub pieces: Vec<WgslTemplatePiece>,
+}
+
+impl WgslTemplate {
+    // Fails with the name of the first placeholder that isn't one of `input_names`.
+    pub fn parse(text: &str, input_names: &[&str]) -> Result<WgslTemplate, String> {
+        let mut pieces = Vec::new();
+        let mut literal = String::new();
+        let mut chars = text.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let name: String = chars.by_ref().take_while(|c| *c != '}').collect();
+                    let name = name.trim();
+                    let ind = input_names
+                        .iter()
+                        .position(|n| *n == name)
+                        .ok_or_else(|| name.to_string())?;
+                    if !literal.is_empty() {
+                        pieces.push(WgslTemplatePiece::Text(std::mem::take(&mut literal)));
+                    }
+                    pieces.push(WgslTemplatePiece::Input(ind));
+                }
+                _ => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            pieces.push(WgslTemplatePiece::Text(literal));
+        }
+        Ok(WgslTemplate { pieces })
+    }
+
+    pub fn expand(&self, input_calls: &[String]) -> String {
+        let body: String = self
+            .pieces
+            .iter()
+            .map(|p| match p {
+                WgslTemplatePiece::Text(t) => t.clone(),
+                WgslTemplatePiece::Input(i) => input_calls[*i].clone(),
+            })
+            .collect();
+        if body.contains("return") {
+            body
+        } else {
+            format!("return {};", body.trim())
+        }
+    }
 }
 
 impl NodeTypeAnnotation for ExecutionInformation {}
 
+fn wgsl_type(prim: PrimitiveType) -> String {
+    match prim {
+        PrimitiveType::F32 => "f32".to_string(),
+        PrimitiveType::I32 => "i32".to_string(),
+        PrimitiveType::U32(_) | PrimitiveType::U8 => "u32".to_string(),
+        PrimitiveType::Bool => "bool".to_string(),
+        PrimitiveType::Vector(n, scalar) => format!("vec{}<{}>", n, wgsl_type(scalar.primitive())),
+        PrimitiveType::Matrix(cols, rows) => format!("mat{}x{}<f32>", cols, rows),
+        // Nodes are emitted as their instances, which don't have type variables left.
+        PrimitiveType::Var(_) => "f32".to_string(),
+    }
+}
+
+// The value an out-of-domain read gives.
+fn zero(prim: PrimitiveType) -> String {
+    match prim {
+        PrimitiveType::Matrix(..) => format!("{}()", wgsl_type(prim)),
+        _ => format!("{}(0)", wgsl_type(prim)),
+    }
+}
+
+// What a node's function returns: its (first) output's primitive. Nodes without outputs get f32.
+fn return_type(typ: &FallibleNodeTypeRc) -> PrimitiveType {
+    typ.as_ref()
+        .ok()
+        .and_then(|t| t.outputs.first())
+        .and_then(|o| o.value_type.as_ref().ok())
+        .map(|v| v.output)
+        .unwrap_or(PrimitiveType::F32)
+}
+
+fn literal(val: f32, prim: PrimitiveType) -> String {
+    match prim {
+        PrimitiveType::F32 => format!("{}f", val),
+        PrimitiveType::I32 => format!("{}i", val as i32),
+        PrimitiveType::U32(_) => format!("{}u", val as u32),
+        PrimitiveType::U8 => format!("{}u", val as u8),
+        PrimitiveType::Bool => (val != 0.0).to_string(),
+        // Every entry gets the value.
+        PrimitiveType::Vector(_, scalar) => {
+            format!("{}({})", wgsl_type(prim), literal(val, scalar.primitive()))
+        }
+        PrimitiveType::Matrix(cols, rows) => {
+            let entries = vec![literal(val, PrimitiveType::F32); (cols * rows) as usize];
+            format!("{}({})", wgsl_type(prim), entries.join(", "))
+        }
+        PrimitiveType::Var(_) => format!("{}f", val),
+    }
+}
+
+// The arguments every function takes, with the WGSL parameters they're passed as.
+const PARAMS: [(&str, &str, &str); 3] = [
+    ("x", "x", "f32"),
+    ("y", "y", "f32"),
+    ("comp", "component", "u32"),
+];
+
+// Type files call the component argument `comp`, but the editor's nodes spell it out like the WGSL
+// parameter, so either name works.
+fn param_index(arg: &str) -> Result<usize, TypeError> {
+    PARAMS
+        .iter()
+        .position(|p| p.0 == arg || p.1 == arg)
+        .ok_or(TypeError::new(
+            TypeErrorKind::Other,
+            format!("No WGSL parameter for argument {}", arg),
+        ))
+}
+
+fn default_args() -> Vec<String> {
+    PARAMS.iter().map(|p| p.1.to_string()).collect()
+}
+
+// A call to the function `name` from an input. Arguments outside the function's domain are
+// clamped into it, or the value zeroed there, and the result is converted if the cast changes the
+// WGSL type. Bound changes don't.
+fn call(name: &str, notes: Option<&InputTypeNotes>) -> Result<String, TypeError> {
+    call_with(name, notes, default_args())
+}
+
+// Same, but with the arguments passed as `args` (WGSL expressions, in PARAMS order) rather than
+// the caller's own parameters.
+fn call_with(
+    name: &str,
+    notes: Option<&InputTypeNotes>,
+    mut args: Vec<String>,
+) -> Result<String, TypeError> {
+    let mut in_domain = Vec::new();
+    for guard in notes.map(|n| n.domain_guards()).unwrap_or_default() {
+        let ind = param_index(&guard.arg)?;
+        let (_, _, scalar) = PARAMS[ind];
+        let arg = args[ind].clone();
+        args[ind] = format!(
+            "min({}, {}({}))",
+            arg,
+            scalar,
+            guard.bound.saturating_sub(1)
+        );
+        if guard.policy == OutOfDomainPolicy::Zero {
+            in_domain.push(format!("{} < {}({})", arg, scalar, guard.bound));
+        }
+    }
+
+    let mut call = format!("{}({})", name, args.join(","));
+    let cast = notes.and_then(|n| n.cast());
+    if !in_domain.is_empty() {
+        let source = cast
+            .map(|c| c.from)
+            .or(notes.map(|n| n.formal_type.output))
+            .unwrap_or(PrimitiveType::F32);
+        call = format!(
+            "select({}, {}, {})",
+            zero(source),
+            call,
+            in_domain.join(" && ")
+        );
+    }
+    Ok(match cast {
+        Some(cast) if wgsl_type(cast.from) != wgsl_type(cast.to) => {
+            format!("{}({})", wgsl_type(cast.to), call)
+        }
+        _ => call,
+    })
+}
+
+fn input_notes(
+    types: &NodeGraphFormalTypeAnalysis,
+    source_node: NodeRef,
+    input_ind: usize,
+) -> Option<&InputTypeNotes> {
+    types
+        .input_type_notes
+        .get(&NodeInputReference {
+            source_node,
+            input_ind,
+        })
+        .and_then(|notes| notes.as_ref().ok())
+}
+
 impl Default for Executor {
     fn default() -> Self {
         Self {
@@ -64,38 +285,44 @@ impl Default for Executor {
 }
 
 impl Executor {
+    // Just the node's own function. Its inputs' functions are emitted separately, under `names`.
     fn make_prog<T: NodeAnnotationHas<FallibleNodeTypeRc>>(
         &mut self,
-        cached: &mut HashMap<NodeRef, Result<ShaderProgram, TypeError>>,
-        port: ValueRef,
+        node_ref: NodeRef,
         graph: &NodeGraph<T>,
         types: &NodeGraphFormalTypeAnalysis,
+        names: &HashMap<NodeRef, String>,
     ) -> Result<ShaderProgram, TypeError> {
-        let n = graph.get_node(port.node).ok_or(TypeError {
-            message: "Node not found".to_string(),
-        })?;
-        let exec = n.annotation.get_t().clone().map(|f| f.annotation.clone())?;
+        let n = graph
+            .get_node(node_ref)
+            .ok_or(TypeError::new(TypeErrorKind::MissingNode, "Node not found"))?;
+        // Polymorphic nodes run as what the typechecker made of them.
+        let typ = types
+            .instances
+            .get(&node_ref)
+            .unwrap_or(n.annotation.get_t());
+        let exec = typ.clone().map(|f| f.annotation.clone())?;
+        let ret = wgsl_type(return_type(typ));
 
-        let inps: Option<Result<Vec<ShaderProgram>, TypeError>> = n
+        // Sources always come first in the order, so they're named by now.
+        let inps: Option<Vec<String>> = n
             .inputs
             .iter()
-            .map(|f| f.map(|g| self.make_prog(cached, g, graph, types)))
-            .collect();
+            .enumerate()
+            .map(|(ind, f)| f.map(|g| call(&names[&g.node], input_notes(types, node_ref, ind))))
+            .collect::<Option<Vec<_>>>()
+            .map(|calls| calls.into_iter().collect::<Result<Vec<_>, _>>())
+            .transpose()?;
 
-        let res = match exec {
+        match exec {
             ExecutionInformation::Add => {
-                let inps = inps.ok_or(TypeError {
-                    message: "No inputs".to_string(),
-                })??;
-
-                let (inp_texts, inp_names): (Vec<String>, Vec<String>) =
-                    inps.into_iter().map(|a| (a.text, a.name)).collect();
+                let inp_names = inps.ok_or(TypeError::new(TypeErrorKind::Other, "No inputs"))?;
 
                 let name = self.namer.generate_name();
 
                 let result_text = format!(
-                    "{}\n{}\nfn {}(x: f32, y: f32, component: u32) -> f32 {{ return {}(x,y,component) + {}(x,y,component); }}",
-                    inp_texts[0], inp_texts[1], name, inp_names[0], inp_names[1]
+                    "fn {}(x: f32, y: f32, component: u32) -> {} {{ return {} + {}; }}",
+                    name, ret, inp_names[0], inp_names[1]
                 );
 
                 Ok(ShaderProgram {
@@ -104,24 +331,13 @@ impl Executor {
                 })
             }
             ExecutionInformation::Vector3 => {
-                let inps = inps.ok_or(TypeError {
-                    message: "No inputs".to_string(),
-                })??;
-
-                let (inp_texts, inp_names): (Vec<String>, Vec<String>) =
-                    inps.into_iter().map(|a| (a.text, a.name)).collect();
+                let inp_names = inps.ok_or(TypeError::new(TypeErrorKind::Other, "No inputs"))?;
 
                 let name = self.namer.generate_name();
 
                 let result_text = format!(
-                    "{}\n{}\n{}\nfn {}(x: f32, y: f32, component: u32) -> f32 {{ if component == 0 {{ return {}(x,y,component); }} if component == 1 {{ return {}(x,y,component); }} return {}(x,y,component); }}",
-                    inp_texts[0],
-                    inp_texts[1],
-                    inp_texts[2],
-                    name,
-                    inp_names[0],
-                    inp_names[1],
-                    inp_names[2]
+                    "fn {}(x: f32, y: f32, component: u32) -> {} {{ if component == 0 {{ return {}; }} if component == 1 {{ return {}; }} return {}; }}",
+                    name, ret, inp_names[0], inp_names[1], inp_names[2]
                 );
 
                 Ok(ShaderProgram {
@@ -134,8 +350,10 @@ impl Executor {
                 let name = self.namer.generate_name();
                 Ok(ShaderProgram {
                     text: format!(
-                        "fn {}(x: f32, y: f32, component: u32) -> f32 {{ return {}f; }}",
-                        name, val
+                        "fn {}(x: f32, y: f32, component: u32) -> {} {{ return {}; }}",
+                        name,
+                        ret,
+                        literal(val, return_type(typ))
                     ),
                     name,
                 })
@@ -144,20 +362,120 @@ impl Executor {
                 let name = self.namer.generate_name();
                 Ok(ShaderProgram {
                     text: format!(
-                        "fn {}(x: f32, y: f32, component: u32) -> f32 {{ return {}; }}",
-                        name, attr_name
+                        "fn {}(x: f32, y: f32, component: u32) -> {} {{ return {}; }}",
+                        name, ret, attr_name
                     ),
                     name,
                 })
             }
-            ExecutionInformation::Out => todo!(),
-            ExecutionInformation::ERR => Err(TypeError {
-                message: "No execution information".to_string(),
-            }),
-        };
+            ExecutionInformation::ConstantFromData => {
+                let val: f32 = n
+                    .extra_data
+                    .as_ref()
+                    .and_then(|d| d.trim().parse().ok())
+                    .ok_or(TypeError::new(
+                        TypeErrorKind::Other,
+                        "Constant needs a numeric value",
+                    ))?;
+                let name = self.namer.generate_name();
+                Ok(ShaderProgram {
+                    text: format!(
+                        "fn {}(x: f32, y: f32, component: u32) -> {} {{ return {}; }}",
+                        name,
+                        ret,
+                        literal(val, return_type(typ))
+                    ),
+                    name,
+                })
+            }
+            ExecutionInformation::Wgsl(template) => {
+                let inp_names = inps.ok_or(TypeError::new(TypeErrorKind::Other, "No inputs"))?;
+
+                let name = self.namer.generate_name();
+
+                let result_text = format!(
+                    "fn {}(x: f32, y: f32, component: u32) -> {} {{ {} }}",
+                    name,
+                    ret,
+                    template.expand(&inp_names)
+                );
+
+                Ok(ShaderProgram {
+                    text: result_text,
+                    name,
+                })
+            }
+            ExecutionInformation::Index(op) => {
+                let inp_names = inps.ok_or(TypeError::new(TypeErrorKind::Other, "No inputs"))?;
+
+                let name = self.namer.generate_name();
+
+                let expr = match op {
+                    IndexOp::Add => format!("{} + {}", inp_names[0], inp_names[1]),
+                    IndexOp::Mul => format!("{} * {}", inp_names[0], inp_names[1]),
+                    IndexOp::Mod => format!("{} % {}", inp_names[0], inp_names[1]),
+                    IndexOp::Clamp => match return_type(typ) {
+                        PrimitiveType::U32(U32Boundedness::Bounded(k)) => {
+                            format!("min({}, {}u)", inp_names[0], k.saturating_sub(1))
+                        }
+                        _ => inp_names[0].clone(),
+                    },
+                };
+
+                Ok(ShaderProgram {
+                    text: format!(
+                        "fn {}(x: f32, y: f32, component: u32) -> {} {{ return {}; }}",
+                        name, ret, expr
+                    ),
+                    name,
+                })
+            }
+            ExecutionInformation::Pack => {
+                let conversion = node_conversion(typ.as_ref().map_err(Clone::clone)?)?;
+                let source =
+                    n.inputs[0].ok_or(TypeError::new(TypeErrorKind::Other, "No inputs"))?;
+                let ind = param_index(&conversion.arg)?;
+                // The source read once per entry, at that entry.
+                let entries = (0..conversion.size)
+                    .map(|i| {
+                        let mut args = default_args();
+                        args[ind] = format!("{}u", i);
+                        call_with(&names[&source.node], input_notes(types, node_ref, 0), args)
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                let name = self.namer.generate_name();
+                Ok(ShaderProgram {
+                    text: format!(
+                        "fn {}(x: f32, y: f32, component: u32) -> {} {{ return {}({}); }}",
+                        name,
+                        ret,
+                        ret,
+                        entries.join(", ")
+                    ),
+                    name,
+                })
+            }
+            ExecutionInformation::Unpack => {
+                let conversion = node_conversion(typ.as_ref().map_err(Clone::clone)?)?;
+                let inp_names = inps.ok_or(TypeError::new(TypeErrorKind::Other, "No inputs"))?;
+                let (_, param, _) = PARAMS[param_index(&conversion.arg)?];
 
-        cached.insert(port.node, res.clone());
-        res
+                let name = self.namer.generate_name();
+                Ok(ShaderProgram {
+                    text: format!(
+                        "fn {}(x: f32, y: f32, component: u32) -> {} {{ return {}[{}]; }}",
+                        name, ret, inp_names[0], param
+                    ),
+                    name,
+                })
+            }
+            ExecutionInformation::Out => todo!(),
+            ExecutionInformation::ERR => Err(TypeError::new(
+                TypeErrorKind::IncompleteNodeType,
+                "No execution information",
+            )),
+        }
     }
 
     pub fn reset_names(&mut self) {
@@ -169,18 +487,74 @@ impl Executor {
         graph: &NodeGraph<T>,
         types: &NodeGraphFormalTypeAnalysis,
     ) -> Result<ShaderProgram, TypeError> {
-        let mut results = HashMap::new();
-        for n in graph.iter_nodes() {
-            if let Ok(typ) = n.1.annotation.get_t() {
-                if let ExecutionInformation::Out = typ.annotation {
-                    if let Some(inp) = n.1.inputs[0] {
-                        return self.make_prog(&mut results, inp, graph, types);
-                    }
+        if let Some(problem) = types.validation.problems.first() {
+            return Err(problem.to_type_error());
+        }
+        let order = graph
+            .topological_order()
+            .map_err(|cycle| TypeError::new(TypeErrorKind::MalformedGraph, cycle.to_string()))?;
+
+        let (out_node, out) = order
+            .iter()
+            .filter_map(|node_ref| graph.get_node(*node_ref).map(|n| (*node_ref, n)))
+            .find_map(|(node_ref, n)| match n.annotation.get_t() {
+                Ok(typ) if matches!(typ.annotation, ExecutionInformation::Out) => {
+                    n.inputs[0].map(|val| (node_ref, val))
+                }
+                _ => None,
+            })
+            .ok_or(TypeError::new(TypeErrorKind::Other, "No output found"))?;
+
+        // Only what the output reads from gets emitted, each node once however many read it.
+        let mut needed = HashSet::from([out.node]);
+        let mut to_visit = vec![out.node];
+        while let Some(node_ref) = to_visit.pop() {
+            let n = graph
+                .get_node(node_ref)
+                .ok_or(TypeError::new(TypeErrorKind::MissingNode, "Node not found"))?;
+            for src in n.inputs.iter().flatten() {
+                if needed.insert(src.node) {
+                    to_visit.push(src.node);
                 }
             }
         }
-        Err(TypeError {
-            message: "No output found".to_string(),
+
+        // Inputs the typechecker refused, like reads outside a value's domain under
+        // OutOfDomainPolicy::Error, would only make for a broken shader.
+        if let Some((_, e)) = types
+            .input_type_notes
+            .iter()
+            .filter(|(inp, _)| needed.contains(&inp.source_node) || inp.source_node == out_node)
+            .filter_map(|(inp, notes)| notes.as_ref().err().map(|e| (inp, e)))
+            .min_by_key(|(inp, _)| *inp)
+        {
+            return Err(e.clone());
+        }
+
+        let mut names = HashMap::new();
+        let mut text = Vec::new();
+        for node_ref in order.into_iter().filter(|n| needed.contains(n)) {
+            let prog = self.make_prog(node_ref, graph, types, &names)?;
+            text.push(prog.text);
+            names.insert(node_ref, prog.name);
+        }
+
+        // The shader wants an f32 over the whole screen, so anything else gets converted or guarded
+        // on the way out.
+        let mut name = names.remove(&out.node).unwrap();
+        let out_call = call(&name, input_notes(types, out_node, 0))?;
+        if out_call != call(&name, None)? {
+            let wrapper = self.namer.generate_name();
+            text.push(format!(
+                "fn {}(x: f32, y: f32, component: u32) -> f32 {{ return {}; }}",
+                wrapper, out_call
+            ));
+            name = wrapper;
+        }
+
+        Ok(ShaderProgram {
+            text: text.join("\n"),
+            name,
         })
     }
 }
@@ -1,15 +1,39 @@
+use std::path::PathBuf;
+
 use shadex_backend::{
-    parsing::{construct_node_graph, parse_whole_input, type_parsing::parse_type_world},
+    parsing::{
+        imports::{FsLoader, load_type_world},
+        load_node_graph,
+    },
     typechecking,
 };
 
+// Usage: shadex-backend [graph.shadex] [types.shadextypes]
 fn main() {
-    let types_text = include_str!("../../examples/typeland.shadextypes");
-    let universe = parse_type_world(types_text).unwrap();
+    let examples = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../examples");
+    let mut args = std::env::args().skip(1);
+    let graph_path = args
+        .next()
+        .map_or_else(|| examples.join("test_fv.shadex"), PathBuf::from);
+    let types_path = args
+        .next()
+        .map_or_else(|| examples.join("typeland.shadextypes"), PathBuf::from);
+
+    let universe = match load_type_world(&FsLoader, &types_path) {
+        Ok(universe) => universe,
+        Err(err) => {
+            eprintln!("{}", err.render());
+            return;
+        }
+    };
 
-    let input_text = include_str!("../../examples/test_fv.shadex");
-    let full_output = parse_whole_input(input_text.as_bytes()).unwrap().1;
-    let constructed = construct_node_graph(&universe, full_output).unwrap();
+    let constructed = match load_node_graph(&FsLoader, &universe, &graph_path) {
+        Ok(graph) => graph,
+        Err(err) => {
+            eprintln!("{}", err.render());
+            return;
+        }
+    };
 
     let typechecker = typechecking::NodeGraphFormalTypeAnalysis::analyze(&constructed);
     println!("{:#?}", constructed);
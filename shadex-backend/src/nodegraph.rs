@@ -1,5 +1,5 @@
 use std::{
-    collections::HashMap,
+    collections::{BTreeSet, HashMap},
     fmt::{Debug, Display},
     rc::Rc,
 };
@@ -9,22 +9,32 @@ use crate::{
     typechecking::typetypes::{MaybeValueType, TypeError, ValueType},
 };
 
+pub mod ordering;
+pub mod validation;
+
 pub trait NodeAnnotation: Clone + std::fmt::Debug {}
 pub trait NodeAnnotationHas<T>: NodeAnnotation {
     fn get_t(&self) -> &T;
 }
 
-#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub struct NodeRef {
     id: usize,
 }
 
-#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub struct ValueRef {
     pub node: NodeRef,
     pub output_index: usize,
 }
 
+// One input slot of a node.
+#[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct NodeInputReference {
+    pub source_node: NodeRef,
+    pub input_ind: usize,
+}
+
 #[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub enum NodeTypeRef {
     Custom(usize),
@@ -82,30 +92,177 @@ pub struct Node<T: NodeAnnotation> {
     pub extra_data: Option<String>,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GraphEditError {
+    MissingNode(NodeRef),
+    MissingInput(NodeInputReference),
+}
+
+impl Display for GraphEditError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GraphEditError::MissingNode(node) => write!(f, "node {:?} is not in the graph", node),
+            GraphEditError::MissingInput(inp) => write!(
+                f,
+                "node {:?} has no input {}",
+                inp.source_node, inp.input_ind
+            ),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct NodeGraph<T: NodeAnnotation> {
     nodes: HashMap<usize, Node<T>>,
     next_id: usize,
+    // Reverse edges: the inputs reading from each node, kept up to date by every edit.
+    consumers: HashMap<NodeRef, BTreeSet<NodeInputReference>>,
 }
 
 impl<T: NodeAnnotation> NodeGraph<T> {
     pub fn add_node(&mut self, node: Node<T>) -> NodeRef {
-        let node_id = self.next_id;
-        self.nodes.insert(node_id, node);
+        let node_ref = NodeRef { id: self.next_id };
+        for (input_ind, src) in node.inputs.iter().enumerate() {
+            if let Some(src) = src {
+                self.consumers
+                    .entry(src.node)
+                    .or_default()
+                    .insert(NodeInputReference {
+                        source_node: node_ref,
+                        input_ind,
+                    });
+            }
+        }
+        self.nodes.insert(node_ref.id, node);
         self.next_id += 1;
-        NodeRef { id: node_id }
+        node_ref
     }
 
     pub fn get_node(&self, node_ref: NodeRef) -> Option<&Node<T>> {
         self.nodes.get(&node_ref.id)
     }
 
-    pub fn get_node_mut(&mut self, node_ref: NodeRef) -> Option<&mut Node<T>> {
-        self.nodes.get_mut(&node_ref.id)
+    // Removes the node, and disconnects every input that was reading from it.
+    pub fn remove_node(&mut self, node_ref: NodeRef) -> Option<Node<T>> {
+        let node = self.nodes.remove(&node_ref.id)?;
+        for (input_ind, src) in node.inputs.iter().enumerate() {
+            if let Some(src) = src {
+                self.forget_consumer(
+                    src.node,
+                    &NodeInputReference {
+                        source_node: node_ref,
+                        input_ind,
+                    },
+                );
+            }
+        }
+        for consumer in self.consumers.remove(&node_ref).unwrap_or_default() {
+            // Inputs of the node itself are gone already, if it was reading from itself.
+            if let Some(dest) = self.nodes.get_mut(&consumer.source_node.id) {
+                dest.inputs[consumer.input_ind] = None;
+            }
+        }
+        Some(node)
+    }
+
+    fn forget_consumer(&mut self, src: NodeRef, consumer: &NodeInputReference) {
+        if let Some(consumers) = self.consumers.get_mut(&src) {
+            consumers.remove(consumer);
+            if consumers.is_empty() {
+                self.consumers.remove(&src);
+            }
+        }
+    }
+
+    fn input_slot(
+        &mut self,
+        input: &NodeInputReference,
+    ) -> Result<&mut Option<ValueRef>, GraphEditError> {
+        self.nodes
+            .get_mut(&input.source_node.id)
+            .ok_or(GraphEditError::MissingNode(input.source_node))?
+            .inputs
+            .get_mut(input.input_ind)
+            .ok_or_else(|| GraphEditError::MissingInput(input.clone()))
+    }
+
+    // Feeds `input` from `source`, and returns what it was connected to before.
+    pub fn connect(
+        &mut self,
+        input: NodeInputReference,
+        source: ValueRef,
+    ) -> Result<Option<ValueRef>, GraphEditError> {
+        if !self.nodes.contains_key(&source.node.id) {
+            return Err(GraphEditError::MissingNode(source.node));
+        }
+        let previous = self.input_slot(&input)?.replace(source);
+        if let Some(previous) = previous {
+            self.forget_consumer(previous.node, &input);
+        }
+        self.consumers.entry(source.node).or_default().insert(input);
+        Ok(previous)
+    }
+
+    // Leaves `input` free, and returns what it was connected to.
+    pub fn disconnect(
+        &mut self,
+        input: NodeInputReference,
+    ) -> Result<Option<ValueRef>, GraphEditError> {
+        let previous = self.input_slot(&input)?.take();
+        if let Some(previous) = previous {
+            self.forget_consumer(previous.node, &input);
+        }
+        Ok(previous)
+    }
+
+    // The inputs that read any output of `node_ref`, in a stable order.
+    pub fn consumers(&self, node_ref: NodeRef) -> impl Iterator<Item = &NodeInputReference> {
+        self.consumers.get(&node_ref).into_iter().flatten()
+    }
+
+    // Replaces a node's annotation, returning the old one.
+    pub fn set_annotation(&mut self, node_ref: NodeRef, annotation: T) -> Option<T> {
+        let node = self.nodes.get_mut(&node_ref.id)?;
+        Some(std::mem::replace(&mut node.annotation, annotation))
+    }
+
+    // Replaces a node's extra data, returning the old one. Inputs only change through `connect`
+    // and `disconnect`, which keep the consumer index up to date.
+    pub fn set_extra_data(
+        &mut self,
+        node_ref: NodeRef,
+        extra_data: Option<String>,
+    ) -> Option<Option<String>> {
+        let node = self.nodes.get_mut(&node_ref.id)?;
+        Some(std::mem::replace(&mut node.extra_data, extra_data))
+    }
+
+    // The same graph, node for node and with the same ids, annotated differently.
+    pub fn map_annotations<U: NodeAnnotation>(
+        &self,
+        mut f: impl FnMut(NodeRef, &T) -> U,
+    ) -> NodeGraph<U> {
+        NodeGraph {
+            nodes: self
+                .nodes
+                .iter()
+                .map(|(id, node)| {
+                    let mapped = Node {
+                        annotation: f(NodeRef { id: *id }, &node.annotation),
+                        inputs: node.inputs.clone(),
+                        extra_data: node.extra_data.clone(),
+                    };
+                    (*id, mapped)
+                })
+                .collect(),
+            next_id: self.next_id,
+            consumers: self.consumers.clone(),
+        }
     }
 
     pub fn clear(&mut self) {
         self.nodes.clear();
+        self.consumers.clear();
         self.next_id = 0;
     }
 
@@ -113,6 +270,7 @@ impl<T: NodeAnnotation> NodeGraph<T> {
         NodeGraph {
             nodes: HashMap::new(),
             next_id: 0,
+            consumers: HashMap::new(),
         }
     }
 
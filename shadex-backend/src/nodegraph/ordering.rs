@@ -0,0 +1,119 @@
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    fmt::Display,
+};
+
+use crate::nodegraph::{NodeAnnotation, NodeGraph, NodeRef};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CycleError {
+    // Nodes on a cycle, plus any that both depend on one cycle and feed another.
+    // Nodes that only depend on a cycle are in neither list.
+    pub nodes: Vec<NodeRef>,
+    // Everything that doesn't depend on a cycle, still in order.
+    pub ordered: Vec<NodeRef>,
+}
+
+impl Display for CycleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "the graph has a cycle through nodes {:?}", self.nodes)
+    }
+}
+
+impl<T: NodeAnnotation> NodeGraph<T> {
+    // Every node after all the nodes it reads from, smallest id first when there's a choice.
+    // Inputs from nodes that aren't in the graph are ignored; `validate` reports those.
+    pub fn topological_order(&self) -> Result<Vec<NodeRef>, CycleError> {
+        let all = self.iter_nodes().map(|(node_ref, _)| node_ref).collect();
+        self.topological_order_of(&all)
+    }
+
+    // Same, for just `nodes`. Inputs from outside of them count as ordered already, and nodes that
+    // aren't in the graph are left out.
+    pub fn topological_order_of(
+        &self,
+        nodes: &HashSet<NodeRef>,
+    ) -> Result<Vec<NodeRef>, CycleError> {
+        // Edges are read off the inputs of `nodes`, keeping only those between two of them, so the
+        // walk never looks past the subset.
+        let mut waiting_on: HashMap<NodeRef, usize> = HashMap::new();
+        let mut dependents: HashMap<NodeRef, Vec<NodeRef>> = HashMap::new();
+        for node_ref in nodes {
+            let Some(node) = self.get_node(*node_ref) else {
+                continue;
+            };
+            let node_ref = *node_ref;
+            let sources: Vec<NodeRef> = node
+                .inputs
+                .iter()
+                .flatten()
+                .map(|v| v.node)
+                .filter(|src| nodes.contains(src) && self.get_node(*src).is_some())
+                .collect();
+            waiting_on.insert(node_ref, sources.len());
+            for src in sources {
+                dependents.entry(src).or_default().push(node_ref);
+            }
+        }
+
+        let mut ready: BTreeSet<NodeRef> = waiting_on
+            .iter()
+            .filter(|(_, count)| **count == 0)
+            .map(|(node_ref, _)| *node_ref)
+            .collect();
+        let mut order = Vec::with_capacity(waiting_on.len());
+        while let Some(node_ref) = ready.pop_first() {
+            order.push(node_ref);
+            for dep in dependents.get(&node_ref).into_iter().flatten() {
+                let count = waiting_on.get_mut(dep).unwrap();
+                *count -= 1;
+                if *count == 0 {
+                    ready.insert(*dep);
+                }
+            }
+        }
+
+        if order.len() == waiting_on.len() {
+            return Ok(order);
+        }
+
+        // What's left can't be ordered. Peel off the nodes that only hang below a cycle, by running
+        // the same thing backwards over the leftovers.
+        let mut feeding: HashMap<NodeRef, usize> = waiting_on
+            .iter()
+            .filter(|(_, count)| **count > 0)
+            .map(|(node_ref, _)| (*node_ref, 0))
+            .collect();
+        for node_ref in feeding.keys().copied().collect::<Vec<_>>() {
+            for dep in dependents.get(&node_ref).into_iter().flatten() {
+                if feeding.contains_key(dep) {
+                    *feeding.get_mut(&node_ref).unwrap() += 1;
+                }
+            }
+        }
+        let mut leaves: Vec<NodeRef> = feeding
+            .iter()
+            .filter(|(_, count)| **count == 0)
+            .map(|(node_ref, _)| *node_ref)
+            .collect();
+        while let Some(node_ref) = leaves.pop() {
+            feeding.remove(&node_ref);
+            let node = self.get_node(node_ref).unwrap();
+            for src in node.inputs.iter().flatten().map(|v| v.node) {
+                if let Some(count) = feeding.get_mut(&src) {
+                    *count -= 1;
+                    if *count == 0 {
+                        leaves.push(src);
+                    }
+                }
+            }
+        }
+
+        let mut nodes: Vec<NodeRef> = feeding.into_keys().collect();
+        nodes.sort();
+        Err(CycleError {
+            nodes,
+            ordered: order,
+        })
+    }
+}
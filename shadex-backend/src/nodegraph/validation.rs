@@ -0,0 +1,153 @@
+use std::fmt::Display;
+
+use crate::{
+    nodegraph::{NodeGraph, NodeInputReference, NodeRef, ValueRef},
+    typechecking::typetypes::{AccessibleFallibleType, ErrorLocation, TypeError, TypeErrorKind},
+};
+
+// Ways a graph can be put together wrong, independent of whether its types line up.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GraphProblem {
+    // The input reads from a node that isn't in the graph.
+    DanglingInput {
+        input: NodeInputReference,
+        source: ValueRef,
+    },
+    // The input reads an output its source doesn't have.
+    OutputOutOfRange {
+        input: NodeInputReference,
+        source: ValueRef,
+        outputs: usize,
+    },
+    // The node has a different number of input slots than its type has inputs.
+    InputCountMismatch {
+        node: NodeRef,
+        expected: usize,
+        found: usize,
+    },
+}
+
+impl Display for GraphProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GraphProblem::DanglingInput { input, source } => write!(
+                f,
+                "input {} of node {:?} reads from node {:?}, which is not in the graph",
+                input.input_ind, input.source_node, source.node
+            ),
+            GraphProblem::OutputOutOfRange {
+                input,
+                source,
+                outputs,
+            } => write!(
+                f,
+                "input {} of node {:?} reads output {} of node {:?}, which only has {}",
+                input.input_ind, input.source_node, source.output_index, source.node, outputs
+            ),
+            GraphProblem::InputCountMismatch {
+                node,
+                expected,
+                found,
+            } => write!(
+                f,
+                "node {:?} has {} input(s), but its type takes {}",
+                node, found, expected
+            ),
+        }
+    }
+}
+
+impl GraphProblem {
+    // The node that has the problem.
+    pub fn node(&self) -> NodeRef {
+        match self {
+            GraphProblem::DanglingInput { input, .. }
+            | GraphProblem::OutputOutOfRange { input, .. } => input.source_node,
+            GraphProblem::InputCountMismatch { node, .. } => *node,
+        }
+    }
+
+    pub fn to_type_error(&self) -> TypeError {
+        let (kind, location) = match self {
+            GraphProblem::DanglingInput { input, .. } => (
+                TypeErrorKind::MissingNode,
+                ErrorLocation::Input(input.clone()),
+            ),
+            GraphProblem::OutputOutOfRange { input, .. } => (
+                TypeErrorKind::MalformedGraph,
+                ErrorLocation::Input(input.clone()),
+            ),
+            GraphProblem::InputCountMismatch { node, .. } => {
+                (TypeErrorKind::MalformedGraph, ErrorLocation::Node(*node))
+            }
+        };
+        TypeError::new(kind, self.to_string()).at(location)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ValidationReport {
+    pub problems: Vec<GraphProblem>,
+}
+
+impl ValidationReport {
+    pub fn is_valid(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+impl<T: AccessibleFallibleType> NodeGraph<T> {
+    // Finds every reference that would send the typechecker or executor off the end of the graph.
+    // Nodes whose type failed can only be checked for dangling inputs.
+    pub fn validate(&self) -> ValidationReport {
+        self.validate_nodes(self.iter_nodes().map(|(node_ref, _)| node_ref))
+    }
+
+    // Same, for just the problems `nodes` have. Nodes that aren't in the graph have none.
+    pub fn validate_nodes(&self, nodes: impl IntoIterator<Item = NodeRef>) -> ValidationReport {
+        let mut nodes: Vec<_> = nodes
+            .into_iter()
+            .filter_map(|node_ref| Some((node_ref, self.get_node(node_ref)?)))
+            .collect();
+        nodes.sort_by_key(|(node_ref, _)| *node_ref);
+
+        let mut report = ValidationReport::default();
+        for (node_ref, node) in nodes {
+            if let Ok(typ) = node.annotation.fallible()
+                && typ.inputs.len() != node.inputs.len()
+            {
+                report.problems.push(GraphProblem::InputCountMismatch {
+                    node: node_ref,
+                    expected: typ.inputs.len(),
+                    found: node.inputs.len(),
+                });
+            }
+
+            for (input_ind, source) in node.inputs.iter().enumerate() {
+                let Some(source) = *source else {
+                    continue;
+                };
+                let input = NodeInputReference {
+                    source_node: node_ref,
+                    input_ind,
+                };
+                let Some(source_node) = self.get_node(source.node) else {
+                    report
+                        .problems
+                        .push(GraphProblem::DanglingInput { input, source });
+                    continue;
+                };
+                if let Ok(source_type) = source_node.annotation.fallible()
+                    && source.output_index >= source_type.outputs.len()
+                {
+                    report.problems.push(GraphProblem::OutputOutOfRange {
+                        input,
+                        source,
+                        outputs: source_type.outputs.len(),
+                    });
+                }
+            }
+        }
+        report
+    }
+}
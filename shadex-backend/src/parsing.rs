@@ -1,55 +1,232 @@
-use std::collections::HashMap;
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+mod definitions;
+pub mod diagnostics;
+pub mod imports;
+pub mod printing;
 pub mod type_parsing;
 
 use nom::{
-    IResult, Parser,
+    Parser,
     branch::alt,
     bytes::{complete::take_until, tag},
     character::{
-        complete::{alpha1, alphanumeric0},
+        complete::{
+            alpha1, alphanumeric0, alphanumeric1, digit1, line_ending, multispace1,
+            not_line_ending, one_of, space1,
+        },
         multispace0,
     },
-    combinator::{complete, eof, fail, map, opt, value},
+    combinator::{consumed, cut, eof, not, opt, peek, recognize},
     error::{Error, ParseError},
-    multi::{many0, separated_list0},
+    multi::{many0, many0_count, separated_list0, separated_list1},
     number::float,
-    sequence::{self, delimited, preceded, terminated},
+    sequence::{delimited, preceded, terminated},
 };
 
 use crate::{
+    execution::ExecutionInformation,
     nodegraph::{
         FallibleNodeTypeRc, InputInfo, Node, NodeAnnotation, NodeGraph, NodeRef, NodeTypeInfo,
-        NodeTypeRc, NodeTypeRef, OutputInfo, TypedNodeGraph, ValueRef,
+        OutputInfo, ValueRef,
+    },
+    parsing::{
+        definitions::{
+            Definition, DefinitionInstance, check_definition, expand_definition, parse_definition,
+        },
+        diagnostics::{Diagnostic, DiagnosticKind, SourceSpan},
+        imports::{ImportContext, LoadError, SourceLoader, resolve_import},
     },
-    typechecking::typetypes::TypeError,
+    typechecking::typetypes::{MaybeValueType, PrimitiveType, U32Boundedness, ValueType},
 };
 
+// Comes up with a type for a name that isn't declared, like a sized instance of a node family.
+pub type TypeResolver<T> = Rc<dyn Fn(&str) -> Option<T>>;
+
+#[derive(Clone)]
 pub struct SimpleTypeWorld<T: NodeAnnotation> {
     pub node_types: HashMap<String, T>,
+    resolver: Option<TypeResolver<T>>,
+    // What the resolver came up with. Clones of the world share it, so a name keeps meaning the
+    // same type (by identity) in the world a graph was built from.
+    resolved: Rc<RefCell<HashMap<String, T>>>,
+}
+
+impl<T: NodeAnnotation> std::fmt::Debug for SimpleTypeWorld<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SimpleTypeWorld")
+            .field("node_types", &self.node_types)
+            .field("resolved", &self.resolved.borrow())
+            .finish()
+    }
 }
 
 impl<T: NodeAnnotation> SimpleTypeWorld<T> {
     pub fn new() -> Self {
         Self {
             node_types: HashMap::new(),
+            resolver: None,
+            resolved: Rc::new(RefCell::new(HashMap::new())),
         }
     }
+
+    pub fn with_resolver(mut self, resolver: impl Fn(&str) -> Option<T> + 'static) -> Self {
+        self.resolver = Some(Rc::new(resolver));
+        self
+    }
+
+    // The declared type called `name`, or else whatever the resolver makes of it.
+    pub fn get(&self, name: &str) -> Option<T> {
+        if let Some(typ) = self.node_types.get(name) {
+            return Some(typ.clone());
+        }
+        if let Some(typ) = self.resolved.borrow().get(name) {
+            return Some(typ.clone());
+        }
+        let typ = (self.resolver.as_ref()?)(name)?;
+        self.resolved
+            .borrow_mut()
+            .insert(name.to_string(), typ.clone());
+        Some(typ)
+    }
+
+    // Adds every type of `other`, as `namespace::Name` if a namespace is given.
+    // Names that are already taken keep their old type and are returned.
+    pub fn merge(&mut self, other: SimpleTypeWorld<T>, namespace: Option<&str>) -> Vec<String> {
+        let mut duplicates = Vec::new();
+        for (name, typ) in other.node_types {
+            let name = match namespace {
+                Some(ns) => format!("{}::{}", ns, name),
+                None => name,
+            };
+            match self.node_types.entry(name) {
+                std::collections::hash_map::Entry::Occupied(occupied_entry) => {
+                    duplicates.push(occupied_entry.key().clone());
+                }
+                std::collections::hash_map::Entry::Vacant(vacant_entry) => {
+                    vacant_entry.insert(typ);
+                }
+            }
+        }
+        duplicates.sort();
+        duplicates
+    }
+}
+
+impl SimpleTypeWorld<FallibleNodeTypeRc> {
+    // Finds the name a node type was registered under, by identity rather than structure.
+    // If the same type is registered under several names, the smallest one wins so the answer
+    // doesn't depend on hash order.
+    pub fn type_name(&self, typ: &FallibleNodeTypeRc) -> Option<String> {
+        let typ = typ.as_ref().ok()?;
+        let resolved = self.resolved.borrow();
+        self.node_types
+            .iter()
+            .chain(resolved.iter())
+            .filter(|(_, t)| t.as_ref().is_ok_and(|t| Rc::ptr_eq(t, typ)))
+            .map(|(name, _)| name)
+            .min()
+            .cloned()
+    }
 }
 
 #[derive(Clone)]
 struct ParseState {
     named_vars: HashMap<String, Value>,
+    // Grows as the file imports type worlds.
+    types: SimpleTypeWorld<FallibleNodeTypeRc>,
+    defs: HashMap<String, Rc<Definition>>,
+}
+
+// The graph being built, along with where each of its nodes was written.
+struct GraphBuilder {
+    graph: NodeGraph<FallibleNodeTypeRc>,
+    // One constant type per primitive, shared by every literal of it.
+    literal_types: HashMap<PrimitiveType, Rc<NodeTypeInfo<MaybeValueType, ExecutionInformation>>>,
+    origins: HashMap<NodeRef, (Option<PathBuf>, SourceSpan)>,
+    // The file the expressions being built were written in.
+    source_file: Option<PathBuf>,
+}
+
+impl GraphBuilder {
+    fn new() -> Self {
+        Self {
+            graph: NodeGraph::new(),
+            literal_types: HashMap::new(),
+            origins: HashMap::new(),
+            source_file: None,
+        }
+    }
+
+    // Every construction is a node of its own; only names and destructuring share one.
+    fn add_node(&mut self, node: Node<FallibleNodeTypeRc>) -> NodeRef {
+        self.graph.add_node(node)
+    }
+
+    fn literal_type(
+        &mut self,
+        prim: PrimitiveType,
+    ) -> Rc<NodeTypeInfo<MaybeValueType, ExecutionInformation>> {
+        self.literal_types
+            .entry(prim)
+            .or_insert_with(|| {
+                Rc::new(NodeTypeInfo {
+                    inputs: vec![],
+                    outputs: vec![OutputInfo {
+                        name: Some("val".to_string()),
+                        value_type: Ok(ValueType::primitive(prim)),
+                    }],
+                    annotation: ExecutionInformation::ConstantFromData,
+                })
+            })
+            .clone()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spanned<T> {
+    pub item: T,
+    pub span: SourceSpan,
 }
 
-#[derive(Debug)]
+pub type SpannedExpression = Spanned<NodeExpression>;
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum NodeExpression {
     Identifier(String),
     FloatLiteral(f32),
     IntLiteral(i32),
-    Assignment(String, Box<NodeExpression>),
-    Construction(String, Option<String>, Vec<NodeExpression>),
+    Assignment(Spanned<String>, Box<SpannedExpression>),
+    // `(a, _, c) = Split(x)`, binding outputs by position. `_` is `None`.
+    Destructure(Vec<Spanned<Option<String>>>, Box<SpannedExpression>),
+    Construction(Spanned<String>, Option<String>, Vec<ConstructionArgument>),
     FreeVariable,
-    Output(Box<NodeExpression>, String),
+    Output(Box<SpannedExpression>, Spanned<String>),
+    // `import "lib.shadextypes" as ns`
+    Import(Spanned<String>, Option<Spanned<String>>),
+    // `use "subgraph.shadex" as Foo`
+    Use(Spanned<String>, Spanned<String>),
+    Definition(Box<DefinitionExpression>),
+}
+
+// `AddF(R, NULL)` or `AddF(a: R, b: NULL)`. Named arguments are matched against the input names.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConstructionArgument {
+    pub name: Option<Spanned<String>>,
+    pub value: SpannedExpression,
+}
+
+// `def Name(a @ f32; b @ f32) => out @ f32 { ... }`. The body binds each output by assigning to it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DefinitionExpression {
+    pub name: Spanned<String>,
+    pub inputs: Vec<(Spanned<String>, ValueType)>,
+    pub outputs: Vec<(Spanned<String>, ValueType)>,
+    pub body: Vec<SpannedExpression>,
 }
 
 // https://github.com/rust-bakery/nom/blob/main/examples/json2.rs
@@ -59,15 +236,158 @@ fn ws<'a, O, E: ParseError<&'a [u8]>, F: Parser<&'a [u8], Output = O, Error = E>
     delimited(multispace0(), f, multispace0())
 }
 
+fn identifier<'a>() -> impl Parser<&'a [u8], Output = String, Error = Error<&'a [u8]>> {
+    (alpha1, alphanumeric0)
+        .map(|b| String::from_utf8_lossy(b.0).to_string() + String::from_utf8_lossy(b.1).as_ref())
+}
+
+// `math::AddF`: names from imported type worlds and `use`d subgraphs.
+fn path_identifier<'a>() -> impl Parser<&'a [u8], Output = String, Error = Error<&'a [u8]>> {
+    (identifier(), many0(preceded(tag("::"), identifier()))).map(|(first, rest)| {
+        rest.into_iter()
+            .fold(first, |path, part| path + "::" + &part)
+    })
+}
+
+fn total_tag<'a>(s: &str) -> impl Parser<&'a [u8], Output = &'a [u8], Error = Error<&'a [u8]>> {
+    terminated(tag(s), not(recognize(alphanumeric1)))
+}
+
+fn quoted_string<'a>() -> impl Parser<&'a [u8], Output = String, Error = Error<&'a [u8]>> {
+    delimited(tag("\""), take_until("\""), tag("\""))
+        .map(|b| String::from_utf8_lossy(b).to_string())
+}
+
 fn parse_identifier<'a>() -> impl Parser<&'a [u8], Output = String, Error = Error<&'a [u8]>> {
-    ws((alpha1, alphanumeric0)
-        .map(|b| String::from_utf8_lossy(b.0).to_string() + String::from_utf8_lossy(b.1).as_ref()))
+    ws(identifier())
+}
+
+// Grammar of .shadex graph files:
+//
+//   file        := (separator* statement end)* separator* EOF
+//   statement   := import | use | def | expr
+//   import      := "import" string ("as" identifier)?
+//   use         := "use" string "as" identifier
+//   def         := "def" identifier "(" params ")" "=>" params "{" (separator* statement end)* separator* "}"
+//   params      := (identifier "@" type (";" identifier "@" type)*)?
+//   end         := inline-trivia (";" | newline | EOF | before "}")
+//   separator   := trivia | ";"
+//
+//   expr        := atom ("." selector)?
+//   atom        := destructure | construction | assignment | "NULL" | path | int | float
+//   destructure := "(" binding ("," binding)* ","? ")" "=" expr
+//   binding     := identifier | "_"
+//   construction:= path (":" extra-data)? "(" (argument ("," argument)* ","?)? ")"
+//   argument    := (identifier ":")? expr
+//   assignment  := identifier "=" expr
+//   extra-data  := '"' any-but-quote* '"' | any-but-paren*
+//   selector    := identifier | digits
+//   path        := identifier ("::" identifier)*
+//   string      := '"' any-but-quote* '"'
+//
+//   trivia      := whitespace | "//" to end of line | "/*" ... "*/"
+//   inline-trivia is the same without newlines (a block comment may still span lines).
+//
+// Every token may be preceded by trivia, so anything between parentheses, or after `=` or `.`,
+// can be spread over several lines. A statement ends at the first `;` or newline after
+// its last token, which is why two statements can't share a line without a `;`.
+//
+// `import` pulls in the node types of a .shadextypes file, under `ns::Name` if a namespace is given.
+// `use` builds another .shadex file into this graph and makes its variables available as
+// `Foo::Name`. Paths are relative to the file they're written in.
+//
+// `def` declares a node type made of other nodes. Its inputs are variables in the body, and each
+// output is whatever the body assigns to the variable of the same name. Every use of the def
+// builds a copy of the body into the graph. Types are written as in .shadextypes files.
+//
+// A node with several outputs can be bound once (`S = Split(X)`) and its outputs taken with
+// `S.a`, `S.b`, or all at once with `(a, b) = Split(X)`, where `_` skips an output. Only names and
+// destructuring reuse a node: writing the same construction twice builds it twice.
+
+fn line_comment<'a>() -> impl Parser<&'a [u8], Output = &'a [u8], Error = Error<&'a [u8]>> {
+    recognize((tag("//"), not_line_ending))
+}
+
+fn block_comment<'a>() -> impl Parser<&'a [u8], Output = &'a [u8], Error = Error<&'a [u8]>> {
+    recognize((tag("/*"), take_until("*/"), tag("*/")))
+}
+
+fn trivia_piece<'a>() -> impl Parser<&'a [u8], Output = &'a [u8], Error = Error<&'a [u8]>> {
+    alt((multispace1, line_comment(), block_comment()))
+}
+
+fn trivia<'a>() -> impl Parser<&'a [u8], Output = (), Error = Error<&'a [u8]>> {
+    many0_count(trivia_piece()).map(|_| ())
+}
+
+// Tokens of the graph grammar only skip trivia in front of them, so that whatever follows
+// a statement's last token is still there to be checked by the statement terminator.
+fn token<'a, O, F: Parser<&'a [u8], Output = O, Error = Error<&'a [u8]>>>(
+    f: F,
+) -> impl Parser<&'a [u8], Output = O, Error = Error<&'a [u8]>> {
+    preceded(trivia(), f)
+}
+
+fn statement_end<'a>() -> impl Parser<&'a [u8], Output = (), Error = Error<&'a [u8]>> {
+    preceded(
+        many0_count(alt((space1, line_comment(), block_comment()))),
+        alt((tag(";"), line_ending, eof, peek(tag("}")))),
+    )
+    .map(|_| ())
+}
+
+fn separators<'a>() -> impl Parser<&'a [u8], Output = (), Error = Error<&'a [u8]>> {
+    many0_count(alt((trivia_piece(), tag(";")))).map(|_| ())
+}
+
+// Quoted extra data may contain anything but a quote; the bare form runs up to the argument list.
+fn parse_extra_data<'a>() -> impl Parser<&'a [u8], Output = String, Error = Error<&'a [u8]>> {
+    alt((
+        token(delimited(tag("\""), take_until("\""), tag("\""))),
+        take_until("(").map(|b: &[u8]| b.trim_ascii()),
+    ))
+    .map(|b| String::from_utf8_lossy(b).to_string())
+}
+
+// Outputs are selected by name, or by position for unnamed outputs (`.0`).
+fn parse_output_selector<'a>() -> impl Parser<&'a [u8], Output = String, Error = Error<&'a [u8]>> {
+    token(alt((
+        identifier(),
+        digit1.map(|b| String::from_utf8_lossy(b).to_string()),
+    )))
+}
+
+// Offset of `input` into `base`. Only meaningful when `input` is a subslice of `base`,
+// which holds for everything nom hands back while parsing `base`.
+fn offset_in(base: &[u8], input: &[u8]) -> usize {
+    (input.as_ptr() as usize).saturating_sub(base.as_ptr() as usize)
+}
+
+// Runs `f` and records the byte range it consumed. Leading trivia is skipped before we start
+// counting, and trailing whitespace is trimmed (tokens wrapped in `ws` would otherwise leak into the span).
+fn spanned<'a, O, F: Parser<&'a [u8], Output = O, Error = Error<&'a [u8]>>>(
+    base: &'a [u8],
+    f: F,
+) -> impl Parser<&'a [u8], Output = Spanned<O>, Error = Error<&'a [u8]>> {
+    preceded(trivia(), consumed(f)).map(move |(text, item): (&[u8], O)| {
+        let start = offset_in(base, text);
+        let mut end = start + text.len();
+        while end > start && base[end - 1].is_ascii_whitespace() {
+            end -= 1;
+        }
+        Spanned {
+            item,
+            span: SourceSpan::new(start, end),
+        }
+    })
 }
 
-struct ExprParser;
+struct ExprParser<'a> {
+    base: &'a [u8],
+}
 
-impl<'a> Parser<&'a [u8]> for ExprParser {
-    type Output = NodeExpression;
+impl<'a> Parser<&'a [u8]> for ExprParser<'a> {
+    type Output = SpannedExpression;
 
     type Error = Error<&'a [u8]>;
 
@@ -75,38 +395,72 @@ impl<'a> Parser<&'a [u8]> for ExprParser {
         &mut self,
         input: &'a [u8],
     ) -> nom::PResult<OM, &'a [u8], Self::Output, Self::Error> {
+        let base = self.base;
         let mut parser = (
-            alt((
-                // Construction
-                (
-                    parse_identifier(),
-                    opt(preceded(ws(tag(":")), take_until("("))),
-                    ws(tag("(")),
-                    separated_list0(ws(tag(",")), parse_expr()),
-                    ws(tag(")")),
-                )
-                    .map(|(name, info, _, args, _)| {
-                        NodeExpression::Construction(
-                            name,
-                            info.map(|b| String::from_utf8_lossy(b).to_string()),
-                            args,
-                        )
-                    }),
-                // Assignment
-                (parse_identifier(), ws(tag("=")), ws(parse_expr()))
-                    .map(|(name, _, expr)| NodeExpression::Assignment(name, Box::new(expr))),
-                // Null
-                ws(tag("NULL")).map(|_| NodeExpression::FreeVariable),
-                // Identifier
-                parse_identifier().map(NodeExpression::Identifier),
-                // Float literal
-                ws(float()).map(NodeExpression::FloatLiteral),
-            )),
+            spanned(
+                base,
+                alt((
+                    // Destructuring
+                    (
+                        token(tag("(")),
+                        separated_list1(
+                            token(tag(",")),
+                            spanned(
+                                base,
+                                alt((identifier().map(Some), total_tag("_").map(|_| None))),
+                            ),
+                        ),
+                        opt(token(tag(","))),
+                        token(tag(")")),
+                        token(tag("=")),
+                        parse_expr(base),
+                    )
+                        .map(|(_, names, _, _, _, expr)| {
+                            NodeExpression::Destructure(names, Box::new(expr))
+                        }),
+                    // Construction
+                    (
+                        spanned(base, path_identifier()),
+                        opt(preceded(token(tag(":")), parse_extra_data())),
+                        token(tag("(")),
+                        terminated(
+                            separated_list0(token(tag(",")), parse_argument(base)),
+                            opt(token(tag(","))),
+                        ),
+                        token(tag(")")),
+                    )
+                        .map(|(name, info, _, args, _)| {
+                            NodeExpression::Construction(name, info, args)
+                        }),
+                    // Assignment
+                    (
+                        spanned(base, identifier()),
+                        token(tag("=")),
+                        parse_expr(base),
+                    )
+                        .map(|(name, _, expr)| NodeExpression::Assignment(name, Box::new(expr))),
+                    // Null
+                    token(tag("NULL")).map(|_| NodeExpression::FreeVariable),
+                    // Identifier
+                    token(path_identifier()).map(NodeExpression::Identifier),
+                    // Int literal, as long as it isn't the start of a float
+                    token(terminated(
+                        nom::character::complete::i32,
+                        not(one_of(".eE")),
+                    ))
+                    .map(NodeExpression::IntLiteral),
+                    // Float literal
+                    token(float()).map(NodeExpression::FloatLiteral),
+                )),
+            ),
             // Output
-            opt((ws(tag(".")), parse_identifier()).map(|(_, field)| field)),
+            opt((token(tag(".")), spanned(base, parse_output_selector())).map(|(_, field)| field)),
         )
             .map(|(expr, sub)| match sub {
-                Some(field) => NodeExpression::Output(Box::new(expr), field),
+                Some(field) => Spanned {
+                    span: SourceSpan::new(expr.span.start, field.span.end),
+                    item: NodeExpression::Output(Box::new(expr), field),
+                },
                 None => expr,
             });
 
@@ -114,110 +468,668 @@ impl<'a> Parser<&'a [u8]> for ExprParser {
     }
 }
 
-fn parse_expr() -> ExprParser {
-    ExprParser
+fn parse_expr(base: &[u8]) -> ExprParser<'_> {
+    ExprParser { base }
+}
+
+fn parse_import_statement(
+    base: &[u8],
+) -> impl Parser<&[u8], Output = SpannedExpression, Error = Error<&[u8]>> {
+    spanned(
+        base,
+        alt((
+            (
+                token(total_tag("import")),
+                spanned(base, token(quoted_string())),
+                opt(preceded(
+                    token(total_tag("as")),
+                    spanned(base, identifier()),
+                )),
+            )
+                .map(|(_, path, ns)| NodeExpression::Import(path, ns)),
+            (
+                token(total_tag("use")),
+                spanned(base, token(quoted_string())),
+                token(total_tag("as")),
+                spanned(base, identifier()),
+            )
+                .map(|(_, path, _, alias)| NodeExpression::Use(path, alias)),
+        )),
+    )
 }
 
-pub fn parse_whole_input(input: &[u8]) -> IResult<&[u8], Vec<NodeExpression>> {
-    terminated(many0(ws(parse_expr())), eof).parse_complete(input)
+// A named argument has to end at the `,` or `)`, otherwise `Constant: 0.5()` would be
+// read as an argument named `Constant`.
+fn parse_argument(
+    base: &[u8],
+) -> impl Parser<&[u8], Output = ConstructionArgument, Error = Error<&[u8]>> {
+    alt((
+        (
+            spanned(base, identifier()),
+            token(tag(":")),
+            parse_expr(base),
+            peek(token(alt((tag(","), tag(")"))))),
+        )
+            .map(|(name, _, value, _)| ConstructionArgument {
+                name: Some(name),
+                value,
+            }),
+        parse_expr(base).map(|value| ConstructionArgument { name: None, value }),
+    ))
 }
 
-#[derive(Clone, Copy)]
+struct StatementParser<'a> {
+    base: &'a [u8],
+}
+
+impl<'a> Parser<&'a [u8]> for StatementParser<'a> {
+    type Output = SpannedExpression;
+
+    type Error = Error<&'a [u8]>;
+
+    fn process<OM: nom::OutputMode>(
+        &mut self,
+        input: &'a [u8],
+    ) -> nom::PResult<OM, &'a [u8], Self::Output, Self::Error> {
+        let base = self.base;
+        let mut parser = alt((
+            parse_import_statement(base),
+            parse_definition(base),
+            parse_expr(base),
+        ));
+        parser.process::<OM>(input)
+    }
+}
+
+// Once a statement has parsed, a missing terminator is reported right where it should be,
+// instead of backtracking to the start of the statement.
+fn parse_statements(
+    base: &[u8],
+) -> impl Parser<&[u8], Output = Vec<SpannedExpression>, Error = Error<&[u8]>> {
+    terminated(
+        many0(preceded(
+            separators(),
+            terminated(StatementParser { base }, cut(statement_end())),
+        )),
+        separators(),
+    )
+}
+
+pub fn parse_whole_input(input: &[u8]) -> Result<Vec<SpannedExpression>, Diagnostic> {
+    match terminated(parse_statements(input), eof).parse_complete(input) {
+        Ok((_, exprs)) => Ok(exprs),
+        Err(nom::Err::Error(e) | nom::Err::Failure(e)) => {
+            let start = offset_in(input, e.input);
+            // Point at the rest of the offending line.
+            let end = start
+                + e.input
+                    .iter()
+                    .position(|b| *b == b'\n')
+                    .unwrap_or(e.input.len());
+            Err(Diagnostic::new(
+                DiagnosticKind::Syntax,
+                SourceSpan::new(start, end),
+            ))
+        }
+        Err(nom::Err::Incomplete(_)) => Err(Diagnostic::new(
+            DiagnosticKind::Syntax,
+            SourceSpan::new(input.len(), input.len()),
+        )),
+    }
+}
+
+#[derive(Clone)]
 enum Value {
     Float(f32),
     Int(i32),
     NodeRef(NodeRef),
     ValueRef(Option<ValueRef>),
+    // An expanded `def`, which has outputs like a node but no node of its own.
+    Instance(Rc<DefinitionInstance>),
+    // What `import` and `use` evaluate to.
+    Nothing,
+}
+
+// Literal arguments become constant nodes typed like the input they're passed to, holding their
+// value as extra data just like a hand-written `Constant: 0.5()`.
+fn add_literal_constant(
+    graph: &mut GraphBuilder,
+    value: Value,
+    prim: PrimitiveType,
+) -> Result<ValueRef, DiagnosticKind> {
+    // Passed to a type variable, a float is an f32 and an integer is as narrow as it can be, so it
+    // goes along with whatever the other inputs make of the variable.
+    let prim = match (&value, prim) {
+        (Value::Float(_), PrimitiveType::Var(_)) => PrimitiveType::F32,
+        (Value::Int(i), PrimitiveType::Var(_)) if *i >= 0 => {
+            PrimitiveType::U32(U32Boundedness::Bounded(*i as u32 + 1))
+        }
+        (Value::Int(_), PrimitiveType::Var(_)) => PrimitiveType::I32,
+        _ => prim,
+    };
+    let text = match (value, prim) {
+        (Value::Float(v), PrimitiveType::F32) => v.to_string(),
+        (Value::Int(i), PrimitiveType::F32) => (i as f32).to_string(),
+        (Value::Int(i), PrimitiveType::I32) => i.to_string(),
+        (Value::Int(i), PrimitiveType::U32(U32Boundedness::Unbounded)) if i >= 0 => i.to_string(),
+        (Value::Int(i), PrimitiveType::U32(U32Boundedness::Bounded(bd)))
+            if i >= 0 && (i as u32) < bd =>
+        {
+            i.to_string()
+        }
+        _ => return Err(DiagnosticKind::LiteralMismatch(prim.to_string())),
+    };
+
+    let typ = graph.literal_type(prim);
+    let node = graph.add_node(Node {
+        annotation: Ok(typ),
+        inputs: vec![],
+        extra_data: Some(text),
+    });
+
+    Ok(ValueRef {
+        node,
+        output_index: 0,
+    })
+}
+
+// Outputs are selected by name, or by position.
+fn output_index(outputs: &[OutputInfo<MaybeValueType>], selector: &str) -> Option<usize> {
+    outputs
+        .iter()
+        .position(|outp| outp.name.as_deref() == Some(selector))
+        .or_else(|| {
+            selector
+                .parse::<usize>()
+                .ok()
+                .filter(|ind| *ind < outputs.len())
+        })
+}
+
+// Matches the arguments of a construction against the inputs they're for. `input_infos` is
+// `None` when the type itself is broken, in which case positional arguments are taken as they are.
+// With `exact_literals`, integer literals going into plain u32 inputs are bounded by their value,
+// for index arithmetic to keep track of.
+fn resolve_arguments(
+    input_infos: Option<&[InputInfo<MaybeValueType>]>,
+    exact_literals: bool,
+    args: Vec<ConstructionArgument>,
+    span: SourceSpan,
+    state: &mut ParseState,
+    graph: &mut GraphBuilder,
+    imports: &mut ImportContext,
+) -> Result<Vec<Option<ValueRef>>, Diagnostic> {
+    // Arguments are evaluated in the order they're written, whatever slot they end up in.
+    let mut positional: Vec<Spanned<Value>> = Vec::new();
+    let mut named: Vec<(Spanned<String>, Spanned<Value>)> = Vec::new();
+    for arg in args {
+        let arg_span = arg.value.span;
+        let value = Spanned {
+            item: process_node_expr(arg.value, state, graph, imports)?,
+            span: arg_span,
+        };
+        match arg.name {
+            Some(name) => named.push((name, value)),
+            None if !named.is_empty() => {
+                return Err(Diagnostic::new(
+                    DiagnosticKind::PositionalAfterNamed,
+                    arg_span,
+                ));
+            }
+            None => positional.push(value),
+        }
+    }
+
+    let slot_count = input_infos.map_or(positional.len(), |infos| infos.len());
+    let input_infos = input_infos.unwrap_or(&[]);
+    let named_count = named.len();
+
+    if positional.len() > slot_count {
+        return Err(Diagnostic::new(
+            DiagnosticKind::ArityMismatch {
+                expected: slot_count,
+                found: positional.len() + named_count,
+            },
+            span,
+        ));
+    }
+
+    let mut slots: Vec<Option<Spanned<Value>>> = vec![None; slot_count];
+    let positional_count = positional.len();
+    for (slot, value) in slots.iter_mut().zip(positional) {
+        *slot = Some(value);
+    }
+    for (name, value) in named {
+        let ind = input_infos
+            .iter()
+            .position(|inp| inp.name == name.item)
+            .ok_or_else(|| {
+                Diagnostic::new(
+                    DiagnosticKind::UnknownArgumentName(name.item.clone()),
+                    name.span,
+                )
+            })?;
+        if slots[ind].is_some() {
+            return Err(Diagnostic::new(
+                DiagnosticKind::DuplicateArgument(name.item),
+                name.span,
+            ));
+        }
+        slots[ind] = Some(value);
+    }
+
+    if let Some(missing) = slots.iter().position(Option::is_none) {
+        let kind = if named_count == 0 {
+            DiagnosticKind::ArityMismatch {
+                expected: slot_count,
+                found: positional_count,
+            }
+        } else {
+            DiagnosticKind::MissingArgument(input_infos[missing].name.clone())
+        };
+        return Err(Diagnostic::new(kind, span));
+    }
+
+    let mut inputs = Vec::with_capacity(slot_count);
+    for (ind, slot) in slots.into_iter().enumerate() {
+        let Spanned {
+            item: value,
+            span: arg_span,
+        } = slot.unwrap();
+        // Untyped inputs get an f32, same as a hand-written `Constant`.
+        let prim = input_infos
+            .get(ind)
+            .and_then(|inp| inp.value_type.as_ref().ok())
+            .map_or(PrimitiveType::F32, |t| t.output);
+        let prim = match (&value, prim) {
+            (Value::Int(i), PrimitiveType::U32(U32Boundedness::Unbounded)) if exact_literals => {
+                u32::try_from(*i)
+                    .ok()
+                    .and_then(|i| i.checked_add(1))
+                    .map_or(prim, |bound| {
+                        PrimitiveType::U32(U32Boundedness::Bounded(bound))
+                    })
+            }
+            _ => prim,
+        };
+        inputs.push(
+            value_as_input(graph, value, prim).map_err(|kind| Diagnostic::new(kind, arg_span))?,
+        );
+    }
+    Ok(inputs)
+}
+
+// What a value turns into when it's plugged into an input of primitive type `prim`.
+fn value_as_input(
+    graph: &mut GraphBuilder,
+    value: Value,
+    prim: PrimitiveType,
+) -> Result<Option<ValueRef>, DiagnosticKind> {
+    match value {
+        Value::ValueRef(vr) => Ok(vr),
+        Value::Float(_) | Value::Int(_) => add_literal_constant(graph, value, prim).map(Some),
+        Value::NodeRef(_) | Value::Instance(_) | Value::Nothing => {
+            Err(DiagnosticKind::NonValueArgument)
+        }
+    }
 }
 
 fn process_node_expr(
-    expr: NodeExpression,
+    expr: SpannedExpression,
     state: &mut ParseState,
-    graph: &mut NodeGraph<FallibleNodeTypeRc>,
-    types: &SimpleTypeWorld<FallibleNodeTypeRc>,
-) -> Result<Value, ()> {
+    graph: &mut GraphBuilder,
+    imports: &mut ImportContext,
+) -> Result<Value, Diagnostic> {
+    let Spanned { item: expr, span } = expr;
     match expr {
-        NodeExpression::Identifier(name) => state.named_vars.get(&name).cloned().ok_or(()),
+        NodeExpression::Identifier(name) => match state.named_vars.get(&name) {
+            Some(v) => Ok(v.clone()),
+            None => Err(Diagnostic::new(DiagnosticKind::UnboundName(name), span)),
+        },
         NodeExpression::FloatLiteral(v) => Ok(Value::Float(v)),
         NodeExpression::IntLiteral(i) => Ok(Value::Int(i)),
         NodeExpression::Assignment(name, node_expression) => {
-            let rhs = process_node_expr(*node_expression, state, graph, types)?;
-            state.named_vars.insert(name, rhs);
+            let rhs = process_node_expr(*node_expression, state, graph, imports)?;
+            state.named_vars.insert(name.item, rhs.clone());
             Ok(rhs)
         }
-        NodeExpression::Construction(typename, data, node_expressions) => {
-            let args: Result<Vec<Option<ValueRef>>, ()> = node_expressions
-                .into_iter()
-                .map(|expr| {
-                    let _arg = process_node_expr(expr, state, graph, types)?;
-                    match _arg {
-                        Value::ValueRef(vr) => Ok(vr),
-                        _ => return Err(()),
-                    }
-                })
-                .collect();
+        NodeExpression::Destructure(names, node_expression) => {
+            let rhs_span = node_expression.span;
+            let rhs = process_node_expr(*node_expression, state, graph, imports)?;
+            let outputs = match &rhs {
+                Value::NodeRef(node_ref) => {
+                    let output_count = graph
+                        .graph
+                        .get_node(*node_ref)
+                        .and_then(|node| node.annotation.as_ref().ok())
+                        .map(|typ| typ.outputs.len())
+                        .ok_or_else(|| {
+                            Diagnostic::new(DiagnosticKind::NotDestructurable, rhs_span)
+                        })?;
+                    (0..output_count)
+                        .map(|output_index| {
+                            Some(ValueRef {
+                                node: *node_ref,
+                                output_index,
+                            })
+                        })
+                        .collect()
+                }
+                Value::Instance(instance) => instance.outputs.clone(),
+                _ => {
+                    return Err(Diagnostic::new(DiagnosticKind::NotDestructurable, rhs_span));
+                }
+            };
+            if outputs.len() != names.len() {
+                return Err(Diagnostic::new(
+                    DiagnosticKind::DestructureMismatch {
+                        outputs: outputs.len(),
+                        names: names.len(),
+                    },
+                    span,
+                ));
+            }
+            for (name, output) in names.into_iter().zip(outputs) {
+                if let Some(name) = name.item {
+                    state.named_vars.insert(name, Value::ValueRef(output));
+                }
+            }
+            Ok(rhs)
+        }
+        NodeExpression::Construction(typename, data, args) => {
+            if let Some(def) = state.defs.get(&typename.item).cloned() {
+                let inputs = resolve_arguments(
+                    Some(&def.signature.inputs),
+                    false,
+                    args,
+                    span,
+                    state,
+                    graph,
+                    imports,
+                )?;
+                let outputs = expand_definition(&def, inputs, graph, imports)?;
+                return Ok(Value::Instance(Rc::new(DefinitionInstance {
+                    signature: def.signature.clone(),
+                    outputs,
+                })));
+            }
 
-            let type_ref = types.node_types.get(&typename).ok_or(())?;
+            let type_ref = state.types.get(&typename.item);
+            let type_ref = type_ref.ok_or_else(|| {
+                Diagnostic::new(
+                    DiagnosticKind::UnknownType(typename.item.clone()),
+                    typename.span,
+                )
+            })?;
+
+            let input_infos = type_ref.as_ref().ok().map(|typ| typ.inputs.as_slice());
+            let index_op = type_ref
+                .as_ref()
+                .is_ok_and(|typ| matches!(typ.annotation, ExecutionInformation::Index(_)));
+            let inputs =
+                resolve_arguments(input_infos, index_op, args, span, state, graph, imports)?;
 
             let node = Node {
                 annotation: type_ref.clone(),
-                inputs: args?,
+                inputs,
                 extra_data: data,
             };
 
             let node_id = graph.add_node(node);
+            graph
+                .origins
+                .insert(node_id, (graph.source_file.clone(), span));
 
             Ok(Value::NodeRef(node_id))
         }
         NodeExpression::Output(node_expression, output_name) => {
-            let node_value = process_node_expr(*node_expression, state, graph, types)?;
+            let bad_output = || {
+                Diagnostic::new(
+                    DiagnosticKind::BadOutputName(output_name.item.clone()),
+                    output_name.span,
+                )
+            };
+
+            let node_value = process_node_expr(*node_expression, state, graph, imports)?;
             let node_ref = match node_value {
                 Value::NodeRef(nr) => nr,
-                _ => return Err(()),
+                Value::Instance(instance) => {
+                    let output_ind = output_index(&instance.signature.outputs, &output_name.item)
+                        .ok_or_else(bad_output)?;
+                    return Ok(Value::ValueRef(instance.outputs[output_ind]));
+                }
+                _ => return Err(bad_output()),
             };
 
-            let node_info = graph.get_node(node_ref).ok_or(())?;
-
-            let type_info = &node_info.annotation;
+            let node_info = graph.graph.get_node(node_ref).ok_or_else(bad_output)?;
 
-            let output_ind = type_info
-                .clone()
-                .map(|f| {
-                    f.outputs
-                        .iter()
-                        .position(|outp| match &outp.name {
-                            Some(txt) => *txt == output_name,
-                            _ => false,
-                        })
-                        .ok_or_else(|| TypeError {
-                            message: "No output of that name found".to_string(),
-                        })
-                })
-                .map_err(|_| ())?
-                .map_err(|_| ())?;
+            let output_ind = node_info
+                .annotation
+                .as_ref()
+                .ok()
+                .and_then(|f| output_index(&f.outputs, &output_name.item))
+                .ok_or_else(bad_output)?;
 
             Ok(Value::ValueRef(Some(ValueRef {
                 node: node_ref,
                 output_index: output_ind,
             })))
         }
+        NodeExpression::Definition(def) => {
+            let name = def.name.clone();
+            if state.defs.contains_key(&name.item)
+                || state.types.node_types.contains_key(&name.item)
+            {
+                return Err(Diagnostic::new(
+                    DiagnosticKind::DuplicateNodeType(name.item),
+                    name.span,
+                ));
+            }
+            let definition = check_definition(*def, state, graph.source_file.clone(), imports)?;
+            state.defs.insert(name.item, Rc::new(definition));
+            Ok(Value::Nothing)
+        }
         NodeExpression::FreeVariable => Ok(Value::ValueRef(None)),
+        NodeExpression::Import(path, namespace) => {
+            let world = imports.type_world(&path)?;
+            let namespace = namespace.as_ref().map(|ns| ns.item.as_str());
+            match state.types.merge(world, namespace).into_iter().next() {
+                Some(name) => Err(Diagnostic::new(
+                    DiagnosticKind::DuplicateNodeType(name),
+                    span,
+                )),
+                None => Ok(Value::Nothing),
+            }
+        }
+        NodeExpression::Use(path, alias) => {
+            let file = resolve_import(imports.current_file(), &path.item);
+            let source = imports.enter(&file, &path)?;
+            // The subgraph gets its own variables, but starts out with the types its user sees.
+            let mut sub_state = ParseState {
+                named_vars: HashMap::new(),
+                types: state.types.clone(),
+                defs: state.defs.clone(),
+            };
+            let outer_file = graph.source_file.replace(file.clone());
+            let result = parse_whole_input(source.as_bytes()).and_then(|exprs| {
+                exprs.into_iter().try_for_each(|expr| {
+                    process_node_expr(expr, &mut sub_state, graph, imports).map(|_| ())
+                })
+            });
+            graph.source_file = outer_file;
+            imports.leave();
+            result.map_err(|d| d.in_file(&file))?;
+
+            for (name, value) in sub_state.named_vars {
+                state
+                    .named_vars
+                    .insert(format!("{}::{}", alias.item, name), value);
+            }
+            // Only what the subgraph defines itself, not what it was handed.
+            for (name, def) in sub_state.defs {
+                if !state.defs.contains_key(&name) {
+                    state.defs.insert(format!("{}::{}", alias.item, name), def);
+                }
+            }
+            Ok(Value::Nothing)
+        }
     }
 }
 
-pub fn construct_node_graph(
+// What a variable ended up as, for tools that show it to the user.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Binding {
+    Value(Option<ValueRef>),
+    // A node or a def use, with its outputs by name (or position, if unnamed).
+    Outputs(Vec<(String, Option<ValueRef>)>),
+    // Not a value until it's passed to something.
+    Literal(String),
+}
+
+// Ties a built graph back to the text it came from.
+#[derive(Debug, Default)]
+pub struct GraphSourceMap {
+    // The construction each node was built from, and the file it's written in (`None` when
+    // the graph isn't loaded from a file). Every construction builds its own node, so each one
+    // has a single span; names and destructuring refer back to it.
+    pub nodes: HashMap<NodeRef, (Option<PathBuf>, SourceSpan)>,
+    // What each top-level assignment of the root file bound, by the span of the assigned name.
+    pub bindings: HashMap<SourceSpan, Binding>,
+}
+
+fn binding_of(value: &Value, graph: &GraphBuilder) -> Option<Binding> {
+    let named_outputs =
+        |outputs: &[OutputInfo<MaybeValueType>],
+         values: &mut dyn Iterator<Item = Option<ValueRef>>| {
+            outputs
+                .iter()
+                .enumerate()
+                .zip(values)
+                .map(|((ind, outp), value)| {
+                    (outp.name.clone().unwrap_or_else(|| ind.to_string()), value)
+                })
+                .collect()
+        };
+    Some(match value {
+        Value::Float(v) => Binding::Literal(v.to_string()),
+        Value::Int(i) => Binding::Literal(i.to_string()),
+        Value::ValueRef(vr) => Binding::Value(*vr),
+        Value::NodeRef(node) => {
+            let typ = graph.graph.get_node(*node)?.annotation.as_ref().ok()?;
+            Binding::Outputs(named_outputs(
+                &typ.outputs,
+                &mut (0..typ.outputs.len()).map(|output_index| {
+                    Some(ValueRef {
+                        node: *node,
+                        output_index,
+                    })
+                }),
+            ))
+        }
+        Value::Instance(instance) => Binding::Outputs(named_outputs(
+            &instance.signature.outputs,
+            &mut instance.outputs.iter().copied(),
+        )),
+        Value::Nothing => return None,
+    })
+}
+
+// Names a statement assigns to, including ones assigned inside its arguments.
+fn assigned_names(expr: &SpannedExpression, names: &mut Vec<Spanned<String>>) {
+    match &expr.item {
+        NodeExpression::Assignment(name, rhs) => {
+            assigned_names(rhs, names);
+            names.push(name.clone());
+        }
+        NodeExpression::Destructure(bindings, rhs) => {
+            assigned_names(rhs, names);
+            names.extend(
+                bindings
+                    .iter()
+                    .filter_map(|b| b.item.clone().map(|item| Spanned { item, span: b.span })),
+            );
+        }
+        NodeExpression::Construction(_, _, args) => {
+            for arg in args {
+                assigned_names(&arg.value, names);
+            }
+        }
+        NodeExpression::Output(inner, _) => assigned_names(inner, names),
+        _ => {}
+    }
+}
+
+fn construct_with_imports(
     types: &SimpleTypeWorld<FallibleNodeTypeRc>,
-    exprs: Vec<NodeExpression>,
-) -> Result<NodeGraph<FallibleNodeTypeRc>, ()> {
+    exprs: Vec<SpannedExpression>,
+    imports: &mut ImportContext,
+) -> Result<(NodeGraph<FallibleNodeTypeRc>, GraphSourceMap), Diagnostic> {
     let mut parse_state = ParseState {
         named_vars: HashMap::new(),
+        types: types.clone(),
+        defs: HashMap::new(),
     };
 
-    let mut graph = NodeGraph::<FallibleNodeTypeRc>::new();
+    let mut graph = GraphBuilder::new();
+    graph.source_file = imports.current_file().map(Path::to_path_buf);
+    let mut bindings = HashMap::new();
 
     for expr in exprs {
-        process_node_expr(expr, &mut parse_state, &mut graph, types)?;
+        let mut names = Vec::new();
+        assigned_names(&expr, &mut names);
+        process_node_expr(expr, &mut parse_state, &mut graph, imports)?;
+        for name in names {
+            let binding = parse_state
+                .named_vars
+                .get(&name.item)
+                .and_then(|value| binding_of(value, &graph));
+            bindings.extend(binding.map(|b| (name.span, b)));
+        }
     }
 
-    Ok(graph)
+    let source_map = GraphSourceMap {
+        nodes: graph.origins,
+        bindings,
+    };
+    Ok((graph.graph, source_map))
+}
+
+// For graphs that aren't backed by a file; `import` and `use` are errors here.
+pub fn construct_node_graph(
+    types: &SimpleTypeWorld<FallibleNodeTypeRc>,
+    exprs: Vec<SpannedExpression>,
+) -> Result<NodeGraph<FallibleNodeTypeRc>, Diagnostic> {
+    construct_with_imports(types, exprs, &mut ImportContext::new(None)).map(|(graph, _)| graph)
+}
+
+// Reads, parses and builds the graph at `path`, following its imports.
+pub fn load_node_graph(
+    loader: &dyn SourceLoader,
+    types: &SimpleTypeWorld<FallibleNodeTypeRc>,
+    path: &Path,
+) -> Result<NodeGraph<FallibleNodeTypeRc>, LoadError> {
+    load_node_graph_mapped(loader, types, path).map(|(graph, _)| graph)
+}
+
+// Same as `load_node_graph`, but also says where everything in the graph came from.
+pub fn load_node_graph_mapped(
+    loader: &dyn SourceLoader,
+    types: &SimpleTypeWorld<FallibleNodeTypeRc>,
+    path: &Path,
+) -> Result<(NodeGraph<FallibleNodeTypeRc>, GraphSourceMap), LoadError> {
+    let mut imports = ImportContext::new(Some(loader));
+    let path = resolve_import(None, &path.to_string_lossy());
+    let root = Spanned {
+        item: path.to_string_lossy().to_string(),
+        span: SourceSpan::default(),
+    };
+    let source = match imports.enter(&path, &root) {
+        Ok(source) => source,
+        Err(diag) => return Err(imports.into_error(Some(diag))),
+    };
+
+    let result = parse_whole_input(source.as_bytes())
+        .and_then(|exprs| construct_with_imports(types, exprs, &mut imports));
+    imports.leave();
+    result.map_err(|diag| imports.into_error(Some(diag.in_file(&path))))
 }
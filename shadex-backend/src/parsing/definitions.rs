@@ -0,0 +1,230 @@
+use std::{collections::HashMap, path::PathBuf, rc::Rc};
+
+use nom::{Parser, bytes::tag, error::Error, multi::separated_list0};
+
+use crate::{
+    execution::ExecutionInformation,
+    nodegraph::{FallibleNodeTypeRc, InputInfo, Node, NodeTypeInfo, OutputInfo, ValueRef},
+    parsing::{
+        DefinitionExpression, GraphBuilder, NodeExpression, ParseState, SimpleTypeWorld, Spanned,
+        SpannedExpression, Value,
+        diagnostics::{Diagnostic, DiagnosticKind, SourceSpan},
+        identifier,
+        imports::ImportContext,
+        parse_identifier, parse_statements, process_node_expr, spanned, token, total_tag,
+        type_parsing::parse_sugar_fn_type,
+        value_as_input, ws,
+    },
+    typechecking::{
+        NodeGraphFormalTypeAnalysis,
+        typetypes::{MaybeValueType, PrimitiveType, ValueType},
+    },
+};
+
+pub(super) struct Definition {
+    // Only the ports matter, it's never executed as a node.
+    pub(super) signature: Rc<NodeTypeInfo<MaybeValueType, ExecutionInformation>>,
+    output_spans: Vec<SourceSpan>,
+    body: Vec<SpannedExpression>,
+    // What the body sees is what was around where it was written, so a def can't call itself.
+    types: SimpleTypeWorld<FallibleNodeTypeRc>,
+    defs: HashMap<String, Rc<Definition>>,
+    file: Option<PathBuf>,
+}
+
+// One use of a def: the values its outputs ended up as.
+pub(super) struct DefinitionInstance {
+    pub(super) signature: Rc<NodeTypeInfo<MaybeValueType, ExecutionInformation>>,
+    pub(super) outputs: Vec<Option<ValueRef>>,
+}
+
+fn parse_params(
+    base: &[u8],
+) -> impl Parser<&[u8], Output = Vec<(Spanned<String>, ValueType)>, Error = Error<&[u8]>> {
+    separated_list0(
+        ws(tag(";")),
+        (
+            spanned(base, parse_identifier()),
+            ws(tag("@")),
+            parse_sugar_fn_type(),
+        )
+            .map(|(name, _, typ)| (name, typ)),
+    )
+}
+
+pub(super) fn parse_definition(
+    base: &[u8],
+) -> impl Parser<&[u8], Output = SpannedExpression, Error = Error<&[u8]>> {
+    spanned(
+        base,
+        (
+            token(total_tag("def")),
+            spanned(base, identifier()),
+            token(tag("(")),
+            parse_params(base),
+            token(tag(")")),
+            token(tag("=>")),
+            parse_params(base),
+            token(tag("{")),
+            parse_statements(base),
+            token(tag("}")),
+        )
+            .map(|(_, name, _, inputs, _, _, outputs, _, body, _)| {
+                NodeExpression::Definition(Box::new(DefinitionExpression {
+                    name,
+                    inputs,
+                    outputs,
+                    body,
+                }))
+            }),
+    )
+}
+
+// Builds a copy of the body into `graph`, with the def's inputs bound to `inputs`.
+pub(super) fn expand_definition(
+    def: &Definition,
+    inputs: Vec<Option<ValueRef>>,
+    graph: &mut GraphBuilder,
+    imports: &mut ImportContext,
+) -> Result<Vec<Option<ValueRef>>, Diagnostic> {
+    let mut body_state = ParseState {
+        named_vars: def
+            .signature
+            .inputs
+            .iter()
+            .zip(inputs)
+            .map(|(inp, value)| (inp.name.clone(), Value::ValueRef(value)))
+            .collect(),
+        types: def.types.clone(),
+        defs: def.defs.clone(),
+    };
+
+    // The body's nodes are written where the def is, not where it's used.
+    let outer_file = std::mem::replace(&mut graph.source_file, def.file.clone());
+    let result =
+        def.body.iter().cloned().try_for_each(|expr| {
+            process_node_expr(expr, &mut body_state, graph, imports).map(|_| ())
+        });
+    graph.source_file = outer_file;
+    result?;
+
+    def.signature
+        .outputs
+        .iter()
+        .zip(&def.output_spans)
+        .map(|(out, span)| {
+            let name = out.name.clone().unwrap_or_default();
+            let value = body_state.named_vars.get(&name).cloned().ok_or_else(|| {
+                Diagnostic::new(DiagnosticKind::MissingDefinitionOutput(name), *span)
+            })?;
+            let prim = out
+                .value_type
+                .as_ref()
+                .map_or(PrimitiveType::F32, |t| t.output);
+            value_as_input(graph, value, prim).map_err(|kind| Diagnostic::new(kind, *span))
+        })
+        .collect()
+}
+
+// The body may depend on fewer arguments than declared (it's constant with respect to the rest),
+// but not on arguments the signature doesn't mention.
+fn fits_signature(found: &ValueType, declared: &ValueType) -> bool {
+    found.output == declared.output
+        && found
+            .inputs
+            .iter()
+            .all(|(name, typ)| declared.inputs.get(name) == Some(typ))
+}
+
+// Checks the body against the declared outputs once, by expanding it on stand-in nodes that
+// have exactly the declared input types.
+pub(super) fn check_definition(
+    def: DefinitionExpression,
+    state: &ParseState,
+    file: Option<PathBuf>,
+    imports: &mut ImportContext,
+) -> Result<Definition, Diagnostic> {
+    let signature = Rc::new(NodeTypeInfo {
+        inputs: def
+            .inputs
+            .iter()
+            .map(|(name, typ)| InputInfo {
+                name: name.item.clone(),
+                value_type: Ok(typ.clone()),
+            })
+            .collect(),
+        outputs: def
+            .outputs
+            .iter()
+            .map(|(name, typ)| OutputInfo {
+                name: Some(name.item.clone()),
+                value_type: Ok(typ.clone()),
+            })
+            .collect(),
+        annotation: ExecutionInformation::ERR,
+    });
+    let definition = Definition {
+        signature,
+        output_spans: def.outputs.iter().map(|(name, _)| name.span).collect(),
+        body: def.body,
+        types: state.types.clone(),
+        defs: state.defs.clone(),
+        file,
+    };
+
+    let mut check_graph = GraphBuilder::new();
+    let stand_ins = definition
+        .signature
+        .inputs
+        .iter()
+        .map(|inp| {
+            let stand_in = Rc::new(NodeTypeInfo {
+                inputs: vec![],
+                outputs: vec![OutputInfo {
+                    name: Some(inp.name.clone()),
+                    value_type: inp.value_type.clone(),
+                }],
+                annotation: ExecutionInformation::ERR,
+            });
+            let node = check_graph.add_node(Node {
+                annotation: Ok(stand_in),
+                inputs: vec![],
+                extra_data: None,
+            });
+            Some(ValueRef {
+                node,
+                output_index: 0,
+            })
+        })
+        .collect();
+
+    let outputs = expand_definition(&definition, stand_ins, &mut check_graph, imports)?;
+    let analysis = NodeGraphFormalTypeAnalysis::analyze(&check_graph.graph);
+
+    for (((name, declared), out), span) in def
+        .outputs
+        .iter()
+        .zip(outputs)
+        .zip(&definition.output_spans)
+    {
+        // A NULL output is left to whatever it ends up connected to.
+        let Some(out) = out else { continue };
+        let found = match analysis.output_type_notes.get(&out) {
+            Some(Ok(notes)) if fits_signature(&notes.formal_type, declared) => continue,
+            Some(Ok(notes)) => notes.formal_type.to_string(),
+            Some(Err(e)) => e.to_string(),
+            // Comes from a node whose type is broken, which is reported where it's used.
+            None => continue,
+        };
+        return Err(Diagnostic::new(
+            DiagnosticKind::SignatureMismatch {
+                output: name.item.clone(),
+                declared: declared.to_string(),
+                found,
+            },
+            *span,
+        ));
+    }
+
+    Ok(definition)
+}
@@ -0,0 +1,240 @@
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+// Byte offsets into the source text. Line/column info is recovered on demand,
+// since graph construction doesn't hold on to the source.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct SourceSpan {
+    pub start: usize,
+    pub end: usize,
+}
+
+// 1-based, columns counted in characters.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SourceLocation {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl SourceSpan {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    pub fn len(&self) -> usize {
+        self.end.saturating_sub(self.start)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn locate(&self, source: &str) -> SourceLocation {
+        locate_offset(source, self.start)
+    }
+
+    pub fn locate_end(&self, source: &str) -> SourceLocation {
+        locate_offset(source, self.end)
+    }
+}
+
+fn locate_offset(source: &str, offset: usize) -> SourceLocation {
+    let offset = offset.min(source.len());
+    let before = &source.as_bytes()[..offset];
+    let line_start = before
+        .iter()
+        .rposition(|b| *b == b'\n')
+        .map_or(0, |p| p + 1);
+    let line = before.iter().filter(|b| **b == b'\n').count() + 1;
+    let column = String::from_utf8_lossy(&before[line_start..])
+        .chars()
+        .count()
+        + 1;
+    SourceLocation { line, column }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DiagnosticKind {
+    Syntax,
+    UnknownType(String),
+    UnboundName(String),
+    BadOutputName(String),
+    ArityMismatch {
+        expected: usize,
+        found: usize,
+    },
+    NonValueArgument,
+    DuplicateNodeType(String),
+    UnknownBuiltin(String),
+    UnknownTemplateInput(String),
+    UnknownArgumentName(String),
+    DuplicateArgument(String),
+    MissingArgument(String),
+    PositionalAfterNamed,
+    // A literal argument that can't be a value of the input's type (given as its name).
+    LiteralMismatch(String),
+    ImportFailed {
+        path: String,
+        reason: String,
+    },
+    ImportCycle(String),
+    // Graphs and type worlds given as plain text have nowhere to import from.
+    ImportsUnavailable,
+    MissingDefinitionOutput(String),
+    SignatureMismatch {
+        output: String,
+        declared: String,
+        found: String,
+    },
+    // `(a, b) = ...` on something that isn't a node with known outputs.
+    NotDestructurable,
+    DestructureMismatch {
+        outputs: usize,
+        names: usize,
+    },
+}
+
+impl Display for DiagnosticKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DiagnosticKind::Syntax => write!(f, "syntax error"),
+            DiagnosticKind::UnknownType(name) => write!(f, "unknown node type `{}`", name),
+            DiagnosticKind::UnboundName(name) => write!(f, "`{}` is not defined", name),
+            DiagnosticKind::BadOutputName(name) => write!(f, "no output named `{}`", name),
+            DiagnosticKind::ArityMismatch { expected, found } => {
+                write!(f, "expected {} argument(s), found {}", expected, found)
+            }
+            DiagnosticKind::NonValueArgument => {
+                write!(f, "argument is not a value (select an output with `.name`)")
+            }
+            DiagnosticKind::DuplicateNodeType(name) => {
+                write!(f, "node type `{}` is declared more than once", name)
+            }
+            DiagnosticKind::UnknownBuiltin(name) => write!(f, "no builtin named `{}`", name),
+            DiagnosticKind::UnknownTemplateInput(name) => {
+                write!(f, "template refers to `{}`, which is not an input", name)
+            }
+            DiagnosticKind::UnknownArgumentName(name) => write!(f, "no input named `{}`", name),
+            DiagnosticKind::DuplicateArgument(name) => {
+                write!(f, "input `{}` is given more than once", name)
+            }
+            DiagnosticKind::MissingArgument(name) => write!(f, "missing argument for `{}`", name),
+            DiagnosticKind::PositionalAfterNamed => {
+                write!(f, "positional arguments must come before named ones")
+            }
+            DiagnosticKind::LiteralMismatch(typ) => {
+                write!(f, "literal is not a valid `{}`", typ)
+            }
+            DiagnosticKind::ImportFailed { path, reason } => {
+                write!(f, "could not import `{}`: {}", path, reason)
+            }
+            DiagnosticKind::ImportCycle(path) => write!(f, "`{}` ends up importing itself", path),
+            DiagnosticKind::ImportsUnavailable => {
+                write!(f, "imports can only be used in files loaded from a path")
+            }
+            DiagnosticKind::MissingDefinitionOutput(name) => {
+                write!(f, "output `{}` is never assigned in the body", name)
+            }
+            DiagnosticKind::SignatureMismatch {
+                output,
+                declared,
+                found,
+            } => write!(
+                f,
+                "output `{}` is declared as `{}`, but the body gives `{}`",
+                output, declared, found
+            ),
+            DiagnosticKind::NotDestructurable => {
+                write!(f, "only a node's outputs can be destructured")
+            }
+            DiagnosticKind::DestructureMismatch { outputs, names } => write!(
+                f,
+                "node has {} output(s), but {} name(s) are given",
+                outputs, names
+            ),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub kind: DiagnosticKind,
+    pub span: SourceSpan,
+    // Only set once imports are involved; `None` means the file the caller handed in.
+    pub file: Option<PathBuf>,
+}
+
+impl Diagnostic {
+    pub fn new(kind: DiagnosticKind, span: SourceSpan) -> Self {
+        Self {
+            kind,
+            span,
+            file: None,
+        }
+    }
+
+    // Attributes the diagnostic to `path`, unless it already came from some other file.
+    pub fn in_file(mut self, path: &Path) -> Self {
+        if self.file.is_none() {
+            self.file = Some(path.to_path_buf());
+        }
+        self
+    }
+
+    pub fn message(&self) -> String {
+        self.kind.to_string()
+    }
+
+    // Rustc-style rendering: message, location and the offending line with a caret underneath.
+    pub fn render(&self, source: &str) -> String {
+        let loc = self.span.locate(source);
+        let line_text = source.lines().nth(loc.line - 1).unwrap_or("");
+        let line_len = line_text.chars().count();
+
+        // Underline to the end of the span, but never past the end of the first line.
+        let end = self.span.locate_end(source);
+        let caret_end = if end.line == loc.line {
+            end.column
+        } else {
+            line_len + 1
+        };
+        let carets = caret_end.saturating_sub(loc.column).max(1);
+
+        let gutter = " ".repeat(loc.line.to_string().len());
+        let file = self
+            .file
+            .as_ref()
+            .map_or(String::new(), |f| format!("{}:", f.display()));
+        format!(
+            "error: {}\n{} --> {}{}:{}\n{} |\n{} | {}\n{} | {}{}",
+            self.kind,
+            gutter,
+            file,
+            loc.line,
+            loc.column,
+            gutter,
+            loc.line,
+            line_text,
+            gutter,
+            " ".repeat(loc.column - 1),
+            "^".repeat(carets)
+        )
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.file {
+            Some(file) => write!(
+                f,
+                "{} (in {} at byte {})",
+                self.kind,
+                file.display(),
+                self.span.start
+            ),
+            None => write!(f, "{} (at byte {})", self.kind, self.span.start),
+        }
+    }
+}
@@ -0,0 +1,224 @@
+use std::{
+    collections::HashMap,
+    path::{Component, Path, PathBuf},
+};
+
+use crate::{
+    nodegraph::FallibleNodeTypeRc,
+    parsing::{
+        SimpleTypeWorld, Spanned,
+        diagnostics::{Diagnostic, DiagnosticKind},
+        type_parsing::parse_type_world_with,
+    },
+};
+
+// Where imported files come from. Paths handed to `load` are already resolved.
+pub trait SourceLoader {
+    fn load(&self, path: &Path) -> Result<String, String>;
+}
+
+pub struct FsLoader;
+
+impl SourceLoader for FsLoader {
+    fn load(&self, path: &Path) -> Result<String, String> {
+        std::fs::read_to_string(path).map_err(|e| e.to_string())
+    }
+}
+
+// For graphs that don't live on disk (and for tests).
+#[derive(Default)]
+pub struct MemoryLoader {
+    pub files: HashMap<PathBuf, String>,
+}
+
+impl MemoryLoader {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_file(mut self, path: impl Into<PathBuf>, content: impl Into<String>) -> Self {
+        self.files.insert(path.into(), content.into());
+        self
+    }
+}
+
+impl SourceLoader for MemoryLoader {
+    fn load(&self, path: &Path) -> Result<String, String> {
+        self.files
+            .get(path)
+            .cloned()
+            .ok_or_else(|| "no such file".to_string())
+    }
+}
+
+// Relative to the importing file. `.` and `..` are resolved lexically so the same file
+// always ends up under the same path, which is what cycle detection and caching go by.
+pub fn resolve_import(from: Option<&Path>, target: &str) -> PathBuf {
+    let joined = match from.and_then(Path::parent) {
+        Some(dir) => dir.join(target),
+        None => PathBuf::from(target),
+    };
+    let mut resolved = PathBuf::new();
+    for component in joined.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir if resolved.file_name().is_some() => {
+                resolved.pop();
+            }
+            other => resolved.push(other.as_os_str()),
+        }
+    }
+    resolved
+}
+
+#[derive(Debug)]
+pub struct LoadError {
+    pub diagnostics: Vec<Diagnostic>,
+    // Text of every file that was read, so diagnostics from imported files can be shown too.
+    pub sources: HashMap<PathBuf, String>,
+}
+
+impl LoadError {
+    pub fn render(&self) -> String {
+        self.diagnostics
+            .iter()
+            .map(
+                |d| match d.file.as_ref().and_then(|f| self.sources.get(f)) {
+                    Some(source) => d.render(source),
+                    // Nothing to point at, e.g. when the root file itself couldn't be read.
+                    None => format!("error: {}", d.kind),
+                },
+            )
+            .collect::<Vec<String>>()
+            .join("\n\n")
+    }
+}
+
+pub(crate) struct ImportContext<'l> {
+    loader: Option<&'l dyn SourceLoader>,
+    // Files currently being loaded, outermost first.
+    stack: Vec<PathBuf>,
+    type_worlds: HashMap<PathBuf, SimpleTypeWorld<FallibleNodeTypeRc>>,
+    sources: HashMap<PathBuf, String>,
+    // Errors inside imported files. The failed import itself is reported where it's written.
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'l> ImportContext<'l> {
+    pub(crate) fn new(loader: Option<&'l dyn SourceLoader>) -> Self {
+        Self {
+            loader,
+            stack: Vec::new(),
+            type_worlds: HashMap::new(),
+            sources: HashMap::new(),
+            diagnostics: Vec::new(),
+        }
+    }
+
+    pub(crate) fn into_error(mut self, diagnostic: Option<Diagnostic>) -> LoadError {
+        self.diagnostics.extend(diagnostic);
+        LoadError {
+            diagnostics: self.diagnostics,
+            sources: self.sources,
+        }
+    }
+
+    // Reads `path` and marks it as being loaded. Every successful `enter` needs a `leave`.
+    pub(crate) fn enter(
+        &mut self,
+        path: &Path,
+        span_of: &Spanned<String>,
+    ) -> Result<String, Diagnostic> {
+        let loader = self
+            .loader
+            .ok_or_else(|| Diagnostic::new(DiagnosticKind::ImportsUnavailable, span_of.span))?;
+        if self.stack.iter().any(|p| p == path) {
+            return Err(Diagnostic::new(
+                DiagnosticKind::ImportCycle(path.display().to_string()),
+                span_of.span,
+            ));
+        }
+        let source = match self.sources.get(path) {
+            Some(source) => source.clone(),
+            None => {
+                let source = loader.load(path).map_err(|reason| {
+                    Diagnostic::new(
+                        DiagnosticKind::ImportFailed {
+                            path: path.display().to_string(),
+                            reason,
+                        },
+                        span_of.span,
+                    )
+                })?;
+                self.sources.insert(path.to_path_buf(), source.clone());
+                source
+            }
+        };
+        self.stack.push(path.to_path_buf());
+        Ok(source)
+    }
+
+    pub(crate) fn leave(&mut self) {
+        self.stack.pop();
+    }
+
+    pub(crate) fn current_file(&self) -> Option<&Path> {
+        self.stack.last().map(PathBuf::as_path)
+    }
+
+    // A failed import, reported at the `import`/`use` that caused it.
+    fn failed_import(path: &Path, target: &Spanned<String>) -> Diagnostic {
+        Diagnostic::new(
+            DiagnosticKind::ImportFailed {
+                path: path.display().to_string(),
+                reason: "it contains errors".to_string(),
+            },
+            target.span,
+        )
+    }
+
+    pub(crate) fn type_world(
+        &mut self,
+        target: &Spanned<String>,
+    ) -> Result<SimpleTypeWorld<FallibleNodeTypeRc>, Diagnostic> {
+        let path = resolve_import(self.current_file(), &target.item);
+        // Diamond imports are fine, only an import of something still being loaded is a cycle.
+        if let Some(world) = self.type_worlds.get(&path) {
+            return Ok(world.clone());
+        }
+
+        let source = self.enter(&path, target)?;
+        let result = parse_type_world_with(&source, &mut |import| self.type_world(import));
+        self.leave();
+
+        match result {
+            Ok(world) => {
+                self.type_worlds.insert(path, world.clone());
+                Ok(world)
+            }
+            Err(err) => {
+                self.diagnostics
+                    .extend(err.diagnostics.into_iter().map(|d| d.in_file(&path)));
+                Err(Self::failed_import(&path, target))
+            }
+        }
+    }
+}
+
+// Loads a type world from a file, following its imports.
+pub fn load_type_world(
+    loader: &dyn SourceLoader,
+    path: &Path,
+) -> Result<SimpleTypeWorld<FallibleNodeTypeRc>, LoadError> {
+    let mut context = ImportContext::new(Some(loader));
+    let root = Spanned {
+        item: path.to_string_lossy().to_string(),
+        span: Default::default(),
+    };
+    match context.type_world(&root) {
+        Ok(world) => Ok(world),
+        // The root's own failure only says "it contains errors", the real ones are already collected.
+        Err(diag) if context.diagnostics.is_empty() => Err(context.into_error(Some(diag))),
+        Err(_) => Err(context.into_error(None)),
+    }
+}
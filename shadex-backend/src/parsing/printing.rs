@@ -0,0 +1,180 @@
+use std::{
+    collections::HashMap,
+    fmt::{Display, Write},
+};
+
+use crate::{
+    execution::ExecutionInformation,
+    nodegraph::{FallibleNodeTypeRc, Node, NodeGraph, NodeRef},
+    parsing::SimpleTypeWorld,
+    typechecking::typetypes::{AccessibleFallibleType, PrimitiveType},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PrintError {
+    // The node's type has no name we can write down.
+    UnnamedType(NodeRef),
+    // Extra data containing a quote can't be written in either extra data form.
+    UnprintableExtraData(NodeRef),
+    // An input points at a node that isn't in the graph.
+    DanglingInput(NodeRef),
+    Cycle,
+}
+
+impl Display for PrintError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PrintError::UnnamedType(node) => write!(f, "node {:?} has no type name", node),
+            PrintError::UnprintableExtraData(node) => {
+                write!(f, "extra data of node {:?} contains a quote", node)
+            }
+            PrintError::DanglingInput(node) => {
+                write!(f, "node {:?} has an input from a missing node", node)
+            }
+            PrintError::Cycle => write!(f, "graph has a cycle"),
+        }
+    }
+}
+
+fn format_extra_data(node: NodeRef, data: &str) -> Result<String, PrintError> {
+    if data.contains('"') {
+        return Err(PrintError::UnprintableExtraData(node));
+    }
+    // Plain numbers stay bare, like hand-written files; everything else gets quoted.
+    if data.trim() == data && data.parse::<f32>().is_ok() {
+        Ok(data.to_string())
+    } else {
+        Ok(format!("\"{}\"", data))
+    }
+}
+
+// Literal arguments are built as constants of a type no world names, so they're written back as
+// literals wherever they're read. Floats keep their point, or they'd come back as integers.
+pub fn literal_text<T: AccessibleFallibleType>(node: &Node<T>) -> Option<String> {
+    let typ = node.annotation.fallible().as_ref().ok()?;
+    if !matches!(typ.annotation, ExecutionInformation::ConstantFromData)
+        || !typ.inputs.is_empty()
+        || typ.outputs.len() != 1
+    {
+        return None;
+    }
+    let data = node.extra_data.as_deref()?;
+    match typ.outputs[0].value_type.as_ref().ok()?.output {
+        PrimitiveType::F32 => {
+            data.parse::<f32>().ok().filter(|v| v.is_finite())?;
+            if data.contains(['.', 'e', 'E']) {
+                Some(data.to_string())
+            } else {
+                Some(format!("{}.0", data))
+            }
+        }
+        PrimitiveType::I32 | PrimitiveType::U32(_) => {
+            data.parse::<i32>().ok()?;
+            Some(data.to_string())
+        }
+        _ => None,
+    }
+}
+
+// Every node comes after the nodes it reads from, smallest id first when there's a choice, so
+// printing the same graph twice gives the same text.
+fn emission_order<T: AccessibleFallibleType>(
+    graph: &NodeGraph<T>,
+) -> Result<Vec<NodeRef>, PrintError> {
+    for (node_ref, node) in graph.iter_nodes() {
+        if node
+            .inputs
+            .iter()
+            .flatten()
+            .any(|src| graph.get_node(src.node).is_none())
+        {
+            return Err(PrintError::DanglingInput(node_ref));
+        }
+    }
+    graph.topological_order().map_err(|_| PrintError::Cycle)
+}
+
+// Writes the graph as canonical .shadex source: one statement per node, variables named `n0`, `n1`, ...
+// in emission order. `type_name` decides how each node's type is spelled.
+pub fn print_node_graph<T: AccessibleFallibleType>(
+    graph: &NodeGraph<T>,
+    mut type_name: impl FnMut(NodeRef, &T) -> Option<String>,
+) -> Result<String, PrintError> {
+    let mut type_names = Vec::new();
+    let mut literals: HashMap<NodeRef, String> = HashMap::new();
+    for node_ref in emission_order(graph)? {
+        let node = graph.get_node(node_ref).unwrap();
+        match type_name(node_ref, &node.annotation) {
+            Some(name) => type_names.push((node_ref, name)),
+            None => {
+                // A literal nothing reads would get lost.
+                let literal = literal_text(node)
+                    .filter(|_| graph.consumers(node_ref).next().is_some())
+                    .ok_or(PrintError::UnnamedType(node_ref))?;
+                literals.insert(node_ref, literal);
+            }
+        }
+    }
+    let var_names: HashMap<NodeRef, String> = type_names
+        .iter()
+        .enumerate()
+        .map(|(i, (node, _))| (*node, format!("n{}", i)))
+        .collect();
+
+    let mut text = String::new();
+    for (node_ref, name) in type_names {
+        let node = graph.get_node(node_ref).unwrap();
+
+        let args: Vec<String> = node
+            .inputs
+            .iter()
+            .map(|inp| match inp {
+                None => "NULL".to_string(),
+                Some(src) if literals.contains_key(&src.node) => literals[&src.node].clone(),
+                Some(src) => {
+                    let src_node = graph.get_node(src.node).unwrap();
+                    let selector = src_node
+                        .annotation
+                        .fallible()
+                        .as_ref()
+                        .ok()
+                        .and_then(|t| t.outputs.get(src.output_index))
+                        .and_then(|o| o.name.clone())
+                        .unwrap_or_else(|| src.output_index.to_string());
+                    format!("{}.{}", var_names[&src.node], selector)
+                }
+            })
+            .collect();
+
+        let construction = match &node.extra_data {
+            Some(data) => format!(
+                "{}: {}({})",
+                name,
+                format_extra_data(node_ref, data)?,
+                args.join(", ")
+            ),
+            None => format!("{}({})", name, args.join(", ")),
+        };
+
+        let has_outputs = node
+            .annotation
+            .fallible()
+            .as_ref()
+            .map_or(true, |t| !t.outputs.is_empty());
+        if has_outputs {
+            let _ = writeln!(text, "{} = {}", var_names[&node_ref], construction);
+        } else {
+            let _ = writeln!(text, "{}", construction);
+        }
+    }
+
+    Ok(text)
+}
+
+// Names types by looking their node type up (by identity) in the type world they were built from.
+pub fn print_with_type_world(
+    types: &SimpleTypeWorld<FallibleNodeTypeRc>,
+    graph: &NodeGraph<FallibleNodeTypeRc>,
+) -> Result<String, PrintError> {
+    print_node_graph(graph, |_, annotation| types.type_name(annotation))
+}
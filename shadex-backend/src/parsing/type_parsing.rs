@@ -1,25 +1,33 @@
-use std::{collections::HashMap, process::Output, rc::Rc};
+use std::{collections::HashMap, rc::Rc};
 
 use nom::{
     Parser,
     branch::alt,
-    bytes::tag,
-    character::complete::alphanumeric1,
-    combinator::{eof, not, opt, recognize},
+    bytes::{complete::take_until, tag},
+    character::complete::{alphanumeric1, one_of, satisfy},
+    combinator::{eof, not, opt, peek, recognize},
     error::Error,
-    multi::{many0, separated_list0, separated_list1},
-    sequence::{self, delimited, preceded, separated_pair, terminated},
+    multi::{separated_list0, separated_list1},
+    sequence::{delimited, preceded, separated_pair, terminated},
 };
 
 use crate::{
-    nodegraph::{FallibleNodeTypeRc, InputInfo, NodeTypeInfo, NodeTypeRc, OutputInfo},
-    parsing::SimpleTypeWorld,
-    typechecking::typetypes::{
-        MaybeValueType, PrimitiveType, TypeError, U32Boundedness, ValueType,
+    execution::{ExecutionInformation, WgslTemplate},
+    nodegraph::{FallibleNodeTypeRc, InputInfo, NodeTypeInfo, OutputInfo},
+    parsing::{
+        SimpleTypeWorld, Spanned,
+        diagnostics::{Diagnostic, DiagnosticKind, SourceSpan},
+    },
+    typechecking::{
+        index_ops::IndexOp,
+        typetypes::{
+            MaybeValueType, PrimitiveType, ScalarType, TypeError, TypeErrorKind, U32Boundedness,
+            ValueType,
+        },
     },
 };
 
-use super::{parse_identifier, ws};
+use super::{offset_in, parse_identifier, quoted_string, spanned, total_tag, ws};
 
 pub struct FnTypeParser;
 
@@ -55,10 +63,6 @@ fn parse_named_arg_type<'a>()
     separated_pair(parse_identifier(), ws(tag(":")), parse_arg_type())
 }
 
-fn total_tag<'a>(s: &str) -> impl Parser<&'a [u8], Output = &'a [u8], Error = Error<&'a [u8]>> {
-    terminated(tag(s), not(recognize(alphanumeric1)))
-}
-
 fn parse_u32_bound<'a>() -> impl Parser<&'a [u8], Output = u32, Error = Error<&'a [u8]>> {
     delimited(ws(tag("[")), nom::character::complete::u32, ws(tag("]")))
 }
@@ -68,13 +72,60 @@ fn parse_primitive_type<'a>()
     ws(alt((
         total_tag("i32").map(|_| PrimitiveType::I32),
         total_tag("f32").map(|_| PrimitiveType::F32),
+        total_tag("bool").map(|_| PrimitiveType::Bool),
+        total_tag("u8").map(|_| PrimitiveType::U8),
+        preceded(
+            tag("vec"),
+            (parse_dimension(), parse_element(parse_scalar_type())),
+        )
+        .map(|(n, scalar)| PrimitiveType::Vector(n, scalar)),
+        preceded(
+            tag("mat"),
+            (
+                parse_dimension(),
+                preceded(tag("x"), parse_dimension()),
+                parse_element(total_tag("f32")),
+            ),
+        )
+        .map(|(cols, rows, _)| PrimitiveType::Matrix(cols, rows)),
         (total_tag("u32"), opt(parse_u32_bound())).map(|(_, bd)| {
             PrimitiveType::U32(bd.map_or(U32Boundedness::Unbounded, U32Boundedness::Bounded))
         }),
         parse_u32_bound().map(|num| PrimitiveType::U32(U32Boundedness::Bounded(num))),
+        parse_type_variable().map(PrimitiveType::Var),
     )))
 }
 
+// The N of `vecN` and `matNxM`.
+fn parse_dimension<'a>() -> impl Parser<&'a [u8], Output = u8, Error = Error<&'a [u8]>> {
+    one_of("234").map(|c: char| c as u8 - b'0')
+}
+
+// The `<f32>` of `vec3<f32>`.
+fn parse_element<'a, O>(
+    element: impl Parser<&'a [u8], Output = O, Error = Error<&'a [u8]>>,
+) -> impl Parser<&'a [u8], Output = O, Error = Error<&'a [u8]>> {
+    delimited(tag("<"), element, tag(">"))
+}
+
+fn parse_scalar_type<'a>() -> impl Parser<&'a [u8], Output = ScalarType, Error = Error<&'a [u8]>> {
+    alt((
+        total_tag("f32").map(|_| ScalarType::F32),
+        total_tag("i32").map(|_| ScalarType::I32),
+        total_tag("u32").map(|_| ScalarType::U32),
+        total_tag("bool").map(|_| ScalarType::Bool),
+        parse_type_variable().map(ScalarType::Var),
+    ))
+}
+
+// A single capital letter, like `T`.
+fn parse_type_variable<'a>() -> impl Parser<&'a [u8], Output = char, Error = Error<&'a [u8]>> {
+    terminated(
+        satisfy(|c| c.is_ascii_uppercase()),
+        not(recognize(alphanumeric1)),
+    )
+}
+
 fn parse_fn_type() -> FnTypeParser {
     FnTypeParser
 }
@@ -98,9 +149,7 @@ pub fn parse_complete_value_type(content: &str) -> MaybeValueType {
     let mut parser = terminated(parse_sugar_fn_type(), eof);
     match parser.parse_complete(content.as_bytes()) {
         Ok(typ) => Ok(typ.1),
-        Err(_) => Err(TypeError {
-            message: "Parsing failed".to_string(),
-        }),
+        Err(_) => Err(TypeError::new(TypeErrorKind::SpecParse, "Parsing failed")),
     }
 }
 
@@ -111,8 +160,84 @@ fn parse_named_value_type<'a>()
     separated_pair(ws(parse_identifier()), ws(tag("@")), parse_sugar_fn_type())
 }
 
-fn parse_node_type_declaration<'a>()
--> impl Parser<&'a [u8], Output = (String, FallibleNodeTypeRc), Error = Error<&'a [u8]>> {
+// How a declaration gets executed: `with builtin Add` or `with wgsl "{a} * {b}"`.
+#[derive(Debug, Clone)]
+enum ImplementationClause {
+    Builtin(String),
+    Wgsl(String),
+}
+
+fn parse_implementation_clause(
+    base: &[u8],
+) -> impl Parser<&[u8], Output = Spanned<ImplementationClause>, Error = Error<&[u8]>> {
+    preceded(
+        ws(total_tag("with")),
+        spanned(
+            base,
+            alt((
+                preceded(ws(total_tag("builtin")), parse_identifier())
+                    .map(ImplementationClause::Builtin),
+                preceded(
+                    ws(total_tag("wgsl")),
+                    ws(delimited(tag("\""), take_until("\""), tag("\""))),
+                )
+                .map(|b| ImplementationClause::Wgsl(String::from_utf8_lossy(b).to_string())),
+            )),
+        ),
+    )
+}
+
+fn resolve_implementation(
+    inputs: &[InputInfo<MaybeValueType>],
+    clause: &ImplementationClause,
+) -> Result<ExecutionInformation, DiagnosticKind> {
+    match clause {
+        ImplementationClause::Builtin(builtin) => {
+            let (expected_inputs, exec) = match builtin.as_str() {
+                "Add" => (2, ExecutionInformation::Add),
+                "Vector3" => (3, ExecutionInformation::Vector3),
+                "Out" => (1, ExecutionInformation::Out),
+                "Constant" => (0, ExecutionInformation::ConstantFromData),
+                // Between a function of `comp: [N]` and a `vecN<T>`, both ways.
+                "Pack" => (1, ExecutionInformation::Pack),
+                "Unpack" => (1, ExecutionInformation::Unpack),
+                "IndexAdd" => (2, ExecutionInformation::Index(IndexOp::Add)),
+                "IndexMul" => (2, ExecutionInformation::Index(IndexOp::Mul)),
+                "IndexMod" => (2, ExecutionInformation::Index(IndexOp::Mod)),
+                // Clamps into the bound of the declared output, like `val @ u32[3]`.
+                "ClampIndex" => (1, ExecutionInformation::Index(IndexOp::Clamp)),
+                // The attribute is named after the node's (only) input, like the GUI's Attr node.
+                "Attr" => (
+                    1,
+                    ExecutionInformation::Attr(
+                        inputs.first().map(|i| i.name.clone()).unwrap_or_default(),
+                    ),
+                ),
+                _ => return Err(DiagnosticKind::UnknownBuiltin(builtin.clone())),
+            };
+            if inputs.len() != expected_inputs {
+                return Err(DiagnosticKind::ArityMismatch {
+                    expected: expected_inputs,
+                    found: inputs.len(),
+                });
+            }
+            Ok(exec)
+        }
+        ImplementationClause::Wgsl(body) => {
+            let names: Vec<&str> = inputs.iter().map(|i| i.name.as_str()).collect();
+            WgslTemplate::parse(body, &names)
+                .map(ExecutionInformation::Wgsl)
+                .map_err(DiagnosticKind::UnknownTemplateInput)
+        }
+    }
+}
+
+// Yields the declaration plus a diagnostic if its implementation clause doesn't make sense.
+// The declaration is still usable for typechecking in that case, it just can't be executed.
+fn parse_node_type_declaration(
+    base: &[u8],
+) -> impl Parser<&[u8], Output = (String, FallibleNodeTypeRc, Option<Diagnostic>), Error = Error<&[u8]>>
+{
     let inputs_parser = separated_list0(
         ws(tag(";")),
         parse_named_value_type().map(|(n, content)| InputInfo {
@@ -130,38 +255,216 @@ fn parse_node_type_declaration<'a>()
 
     let fn_name = ws(parse_identifier());
 
-    let fn_details = separated_pair(inputs_parser, ws(tag("=>")), outputs_parser);
+    let fn_details = (
+        separated_pair(inputs_parser, ws(tag("=>")), outputs_parser),
+        opt(parse_implementation_clause(base)),
+    );
 
     let assignment = separated_pair(fn_name, ws(tag("=")), fn_details);
 
-    assignment.map(|(name, (inputs, outputs))| {
+    assignment.map(|(name, ((inputs, outputs), implementation))| {
+        let (annotation, diagnostic) = match implementation {
+            None => (ExecutionInformation::ERR, None),
+            Some(clause) => match resolve_implementation(&inputs, &clause.item) {
+                Ok(exec) => (exec, None),
+                Err(kind) => (
+                    ExecutionInformation::ERR,
+                    Some(Diagnostic::new(kind, clause.span)),
+                ),
+            },
+        };
         (
             name,
             Ok(Rc::new(NodeTypeInfo {
                 inputs,
                 outputs,
-                annotation: crate::execution::ExecutionInformation::ERR,
+                annotation,
             })),
+            diagnostic,
         )
     })
 }
 
-pub fn parse_node_type_declarations<'a>()
--> impl Parser<&'a [u8], Output = Vec<(String, FallibleNodeTypeRc)>, Error = Error<&'a [u8]>> {
-    many0(ws(parse_node_type_declaration()))
+#[derive(Debug)]
+pub struct TypeWorldError {
+    // Everything that did parse, so one bad declaration doesn't take the whole library down.
+    pub world: SimpleTypeWorld<FallibleNodeTypeRc>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl TypeWorldError {
+    pub fn render(&self, source: &str) -> String {
+        self.diagnostics
+            .iter()
+            .map(|d| d.render(source))
+            .collect::<Vec<String>>()
+            .join("\n\n")
+    }
+}
+
+// `Name =` (but not `Name =>`) at the start of a declaration, or the start of an import.
+fn parse_item_start<'a>() -> impl Parser<&'a [u8], Output = (), Error = Error<&'a [u8]>> {
+    alt((
+        (parse_identifier(), tag("="), not(tag(">"))).map(|_| ()),
+        ws(total_tag("import")).map(|_| ()),
+    ))
+}
+
+// Skip whole lines until one of them looks like the start of a declaration.
+fn skip_to_next_declaration(input: &[u8]) -> &[u8] {
+    let mut rest = input;
+    loop {
+        rest = match rest.iter().position(|b| *b == b'\n') {
+            Some(p) => &rest[p + 1..],
+            None => return &rest[rest.len()..],
+        };
+        if parse_item_start().parse_complete(rest).is_ok() {
+            return rest;
+        }
+    }
+}
+
+enum TypeWorldItem {
+    Declaration(String, FallibleNodeTypeRc, Option<Diagnostic>),
+    // `import "other.shadextypes" as ns`
+    Import(Spanned<String>, Option<String>),
+}
+
+fn parse_import(
+    base: &[u8],
+) -> impl Parser<&[u8], Output = (Spanned<String>, Option<String>), Error = Error<&[u8]>> {
+    preceded(
+        ws(total_tag("import")),
+        (
+            spanned(base, ws(quoted_string())),
+            opt(preceded(ws(total_tag("as")), parse_identifier())),
+        ),
+    )
 }
 
-pub fn parse_type_world(content: &str) -> Result<SimpleTypeWorld<FallibleNodeTypeRc>, ()> {
-    let mut parser = terminated(parse_node_type_declarations(), eof);
-    let res = match parser.parse_complete(content.as_bytes()) {
-        Ok((_, ok)) => ok,
-        Err(a) => panic!("{a}"),
-    };
+pub fn parse_type_world(
+    content: &str,
+) -> Result<SimpleTypeWorld<FallibleNodeTypeRc>, TypeWorldError> {
+    parse_type_world_with(content, &mut |import| {
+        Err(Diagnostic::new(
+            DiagnosticKind::ImportsUnavailable,
+            import.span,
+        ))
+    })
+}
+
+// Produces the type world behind an imported path, or a diagnostic to report at the import.
+pub type ImportResolver<'r> =
+    dyn FnMut(&Spanned<String>) -> Result<SimpleTypeWorld<FallibleNodeTypeRc>, Diagnostic> + 'r;
+
+pub fn parse_type_world_with(
+    content: &str,
+    resolve_import: &mut ImportResolver,
+) -> Result<SimpleTypeWorld<FallibleNodeTypeRc>, TypeWorldError> {
+    let base = content.as_bytes();
+
+    // An item only counts if it's followed by another item (or the end of the file).
+    // Otherwise a typo in the last output would silently truncate the output list.
+    let mut parser = terminated(
+        spanned(
+            base,
+            alt((
+                parse_import(base).map(|(path, ns)| TypeWorldItem::Import(path, ns)),
+                parse_node_type_declaration(base).map(|(name, typ, diagnostic)| {
+                    TypeWorldItem::Declaration(name, typ, diagnostic)
+                }),
+            )),
+        ),
+        peek(alt((ws(eof).map(|_| ()), parse_item_start()))),
+    );
 
     let mut uni = SimpleTypeWorld::<FallibleNodeTypeRc>::new();
-    for i in res {
-        uni.node_types.insert(i.0, i.1);
+    let mut diagnostics = Vec::new();
+
+    let mut rest = base;
+    loop {
+        rest = rest.trim_ascii_start();
+        if rest.is_empty() {
+            break;
+        }
+
+        match parser.parse_complete(rest) {
+            Ok((next, item)) => {
+                let Spanned { item, span } = item;
+                match item {
+                    TypeWorldItem::Declaration(name, typ, implementation_diagnostic) => {
+                        diagnostics.extend(implementation_diagnostic);
+                        match uni.node_types.entry(name) {
+                            std::collections::hash_map::Entry::Occupied(occupied_entry) => {
+                                diagnostics.push(Diagnostic::new(
+                                    DiagnosticKind::DuplicateNodeType(occupied_entry.key().clone()),
+                                    span,
+                                ));
+                            }
+                            std::collections::hash_map::Entry::Vacant(vacant_entry) => {
+                                vacant_entry.insert(typ);
+                            }
+                        }
+                    }
+                    TypeWorldItem::Import(path, ns) => match resolve_import(&path) {
+                        Ok(imported) => {
+                            for name in uni.merge(imported, ns.as_deref()) {
+                                diagnostics.push(Diagnostic::new(
+                                    DiagnosticKind::DuplicateNodeType(name),
+                                    span,
+                                ));
+                            }
+                        }
+                        Err(diag) => diagnostics.push(diag),
+                    },
+                }
+                rest = next;
+            }
+            Err(e) => {
+                let err_at = match e {
+                    nom::Err::Error(e) | nom::Err::Failure(e) => e.input,
+                    nom::Err::Incomplete(_) => &rest[rest.len()..],
+                };
+                let start = offset_in(base, err_at);
+                let end = start
+                    + err_at
+                        .iter()
+                        .position(|b| *b == b'\n')
+                        .unwrap_or(err_at.len());
+                diagnostics.push(Diagnostic::new(
+                    DiagnosticKind::Syntax,
+                    SourceSpan::new(start, end),
+                ));
+                rest = skip_to_next_declaration(rest);
+            }
+        }
+    }
+
+    if diagnostics.is_empty() {
+        Ok(uni)
+    } else {
+        Err(TypeWorldError {
+            world: uni,
+            diagnostics,
+        })
     }
+}
 
-    Ok(uni)
+// Where each declaration of a file is, by name, for tools that jump to it. Like error recovery,
+// this goes by what lines start with, so it still works on files that don't parse.
+pub fn declaration_spans(content: &str) -> Vec<Spanned<String>> {
+    let base = content.as_bytes();
+    let mut declarations = Vec::new();
+    let mut line = base;
+    loop {
+        let mut declaration_start =
+            terminated(spanned(base, parse_identifier()), (tag("="), not(tag(">"))));
+        if let Ok((_, name)) = declaration_start.parse_complete(line) {
+            declarations.push(name);
+        }
+        line = match line.iter().position(|b| *b == b'\n') {
+            Some(p) => &line[p + 1..],
+            None => return declarations,
+        };
+    }
 }
@@ -1,14 +1,35 @@
-use core::panic;
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
+
+// Used to live here, and is still commonly imported from here.
+pub use crate::nodegraph::NodeInputReference;
 
 use crate::{
-    nodegraph::{Node, NodeAnnotation, NodeGraph, NodeRef, ValueRef},
+    execution::ExecutionInformation,
+    nodegraph::{
+        FallibleNodeTypeRc, NodeGraph, NodeRef, NodeTypeInfo, ValueRef,
+        validation::{GraphProblem, ValidationReport},
+    },
+    typechecking::casts::{Cast, implicit_cast, narrowest},
+    typechecking::domains::{DomainGuard, OutOfDomainPolicy, fit_argument},
+    typechecking::polymorphism::{TypeBindings, is_polymorphic},
     typechecking::typetypes::{
-        AccessibleFallibleType, MaybeValueType, PrimitiveType, TypeError, U32Boundedness, ValueType,
+        AccessibleFallibleType, ErrorLocation, MaybeValueType, PrimitiveType, TypeError,
+        TypeErrorKind, U32Boundedness, ValueType,
     },
+    typechecking::vectors::node_conversion,
 };
 
+pub mod casts;
+pub mod domains;
+pub mod explain;
+pub mod incremental;
+pub mod index_ops;
+pub mod polymorphism;
 pub mod typetypes;
+pub mod vectors;
 
 #[derive(Default)]
 pub struct ValueTypeProperties {
@@ -20,6 +41,13 @@ fn assess_known_constant_type(typ: PrimitiveType) -> ValueTypeProperties {
     match typ {
         PrimitiveType::F32 => Default::default(),
         PrimitiveType::I32 => Default::default(),
+        PrimitiveType::Var(_) => Default::default(),
+        PrimitiveType::Bool => Default::default(),
+        PrimitiveType::Vector(..) | PrimitiveType::Matrix(..) => Default::default(),
+        PrimitiveType::U8 => ValueTypeProperties {
+            can_index_texture_axis: true,
+            can_index_vector: false,
+        },
         PrimitiveType::U32(u32_boundedness) => match u32_boundedness {
             U32Boundedness::Unbounded => Default::default(),
             U32Boundedness::Bounded(n) => ValueTypeProperties {
@@ -38,13 +66,7 @@ pub fn assess_value_type(typ: &ValueType) -> ValueTypeProperties {
     ValueTypeProperties::default()
 }
 
-#[derive(Clone, Debug, Hash, PartialEq, Eq)]
-pub struct NodeInputReference {
-    pub source_node: NodeRef,
-    pub input_ind: usize,
-}
-
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct OutputTypeNotes {
     // Formal type has arguments. Each comes from at least one of two places.
     pub formal_type: ValueType,
@@ -53,11 +75,25 @@ pub struct OutputTypeNotes {
     pub step_computation_requires: HashMap<String, ValueType>,
     // What would we need to know just to evaluate the inputs?
     pub inputs_parameterized_by: HashMap<String, ValueType>,
+    // Inputs that take an excess argument more broadly than the node ended up parameterized by it,
+    // in input order.
+    pub widened_inputs: Vec<ArgumentWidening>,
+}
+
+// When inputs disagree on an excess argument, the node is parameterized by the narrowest of them
+// and the rest take it widened.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ArgumentWidening {
+    pub input_ind: usize,
+    pub arg: String,
+    // What the node is parameterized by, and what the input takes.
+    pub from: ValueType,
+    pub to: ValueType,
 }
 
 pub type MaybeOutputTypeNotes = Result<OutputTypeNotes, TypeError>;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct InputTypeNotes {
     // Formal type EITHER comes freeformed from free variable, or has arguments that each come from one of two places.
     pub formal_type: ValueType,
@@ -67,13 +103,30 @@ pub struct InputTypeNotes {
 
 pub type MaybeInputTypeNotes = Result<InputTypeNotes, TypeError>;
 
-#[derive(Clone, Debug)]
+impl InputTypeNotes {
+    // The cast the value goes through on its way into the input, if any. Free variables never need one.
+    pub fn cast(&self) -> Option<&Cast> {
+        match &self.type_source {
+            InputValueTypeSource::FromOutput(promotion) => promotion.cast.as_ref(),
+            InputValueTypeSource::FreeVariable(_) => None,
+        }
+    }
+
+    pub fn domain_guards(&self) -> &[DomainGuard] {
+        match &self.type_source {
+            InputValueTypeSource::FromOutput(promotion) => &promotion.domain_guards,
+            InputValueTypeSource::FreeVariable(_) => &[],
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub enum InputValueTypeSource {
     FreeVariable(FreeVariableTypeSource),
     FromOutput(OutputPromotion),
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct FreeVariableTypeSource {
     // Types in the actual argument
     pub types_from_fv: HashMap<String, ValueType>,
@@ -81,7 +134,7 @@ pub struct FreeVariableTypeSource {
     pub itself: (String, ValueType),
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct OutputPromotion {
     // Types that come from the value source.
     pub types_from_output: HashMap<String, ValueType>,
@@ -93,14 +146,27 @@ pub struct OutputPromotion {
     // it's just some bookkeeping.
     pub underspecified_args: HashSet<String>,
 
-    // Cast necessary? Leaving off for now.
-    pub cast: (),
+    // Set when the value's primitive isn't the one the input asks for.
+    pub cast: Option<Cast>,
+    // Arguments the input asks for the value at, where the value isn't defined. Sorted by argument.
+    pub domain_guards: Vec<DomainGuard>,
 }
 
 #[derive(Debug)]
 pub struct NodeGraphFormalTypeAnalysis {
     pub output_type_notes: HashMap<ValueRef, MaybeOutputTypeNotes>,
     pub input_type_notes: HashMap<NodeInputReference, MaybeInputTypeNotes>,
+    pub validation: ValidationReport,
+    // How inputs that read a value outside its domain are handled.
+    pub out_of_domain: OutOfDomainPolicy,
+    // The types of polymorphic nodes with their type variables filled in, which is what they're
+    // typechecked and executed as. Other nodes aren't in here.
+    pub instances: HashMap<NodeRef, FallibleNodeTypeRc>,
+
+    // The edges the notes were worked out over, so an edit can find what it affects even after
+    // the graph itself has forgotten them.
+    read_from: HashMap<NodeRef, Vec<NodeRef>>,
+    dependents: HashMap<NodeRef, HashSet<NodeRef>>,
 }
 
 type TypedNodeGraph = crate::nodegraph::TypedNodeGraph;
@@ -116,25 +182,36 @@ impl NodeGraphFormalTypeAnalysis {
             return res.clone();
         }
 
+        let here = ErrorLocation::Input(inp_ref.clone());
         let node = graph.get_node(inp_ref.source_node).unwrap();
         let node_type = match node.annotation.fallible().as_ref() {
             Ok(a) => a,
             Err(_) => {
-                return Err(TypeError {
-                    message: "Incomplete type".to_string(),
-                });
+                return Err(
+                    TypeError::new(TypeErrorKind::IncompleteNodeType, "Incomplete type").at(here),
+                );
+            }
+        };
+        let node_type = match self.instance(graph, inp_ref.source_node, node_type) {
+            Ok(a) => a,
+            Err(e) => {
+                let err = e.at(here);
+                self.input_type_notes.insert(inp_ref, Err(err.clone()));
+                return Err(err);
             }
         };
 
         let specd_input_type = &node_type.inputs[inp_ref.input_ind].value_type;
 
-        let provided_output_type =
-            node.inputs[inp_ref.input_ind].map(|f| self.analyze_single_output(graph, f));
+        let source = node.inputs[inp_ref.input_ind];
+        let provided_output_type = source.map(|f| self.source_notes(graph, f));
 
         let inp_notes = match (specd_input_type.as_ref(), provided_output_type) {
-            (Err(e), Some(_)) => Err(e.clone()),
-            (_, Some(Err(e))) => Err(e),
+            (Err(e), Some(_)) => Err(e.clone().or_at(here)),
+            (_, Some(Err(e))) => Err(e.propagate(here)),
             (Ok(specd_input_type), Some(Ok(real_output))) => 'block: {
+                // Only reached with a source.
+                let source = ErrorLocation::Output(source.unwrap());
                 let mut op = OutputPromotion {
                     types_from_output: real_output
                         .formal_type
@@ -151,7 +228,8 @@ impl NodeGraphFormalTypeAnalysis {
                         .filter(|name| !specd_input_type.inputs.contains_key(*name))
                         .map(String::clone)
                         .collect(),
-                    cast: (),
+                    cast: None,
+                    domain_guards: Vec::new(),
                 };
                 // The actual input type must have at least all the arguments of the source value.
                 let mut result_args: HashMap<String, Box<ValueType>> =
@@ -164,27 +242,34 @@ impl NodeGraphFormalTypeAnalysis {
                             .insert(arg.0.clone(), (**arg.1).clone());
                         result_args.insert(arg.0.clone(), arg.1.clone());
                     }
-                    // Otherwise, it is present in both the source output and the input. Should be checked for equality.
+                    // Otherwise, it is present in both the source output and the input. The input
+                    // decides what it's evaluated over, as long as the source can be read there.
                     else {
-                        // I know this can be done more efficiently with some entry stuff that I did earlier, but I hate reading it.
-                        // So I'm skipping it this time.
-
-                        if **real_output.formal_type.inputs.get(arg.0).unwrap() != **arg.1 {
-                            break 'block Err(TypeError {
-                                message: "Input argument type is wrong.".to_string(),
-                            });
-                            //panic!("Input argument type is wrong")
+                        let source_arg = real_output.formal_type.inputs.get(arg.0).unwrap();
+                        match fit_argument(arg.0, source_arg, arg.1, self.out_of_domain) {
+                            Ok(guard) => op.domain_guards.extend(guard),
+                            Err(e) => {
+                                break 'block Err(e.at(here).with_related(
+                                    source,
+                                    format!("{} is {} here", arg.0, source_arg),
+                                ));
+                            }
                         }
+                        result_args.insert(arg.0.clone(), arg.1.clone());
                     }
                 }
-
-                if specd_input_type.output != real_output.formal_type.output {
-                    // Should insert a cast. For now, scream.
-                    break 'block Err(TypeError {
-                        message: "Wrong primitive! Tell Robin to add casting.".to_string(),
-                    });
-                    //panic!("Wrong primitive! Tell Robin to add casting.");
-                }
+                op.domain_guards.sort_by(|a, b| a.arg.cmp(&b.arg));
+
+                op.cast =
+                    match implicit_cast(real_output.formal_type.output, specd_input_type.output) {
+                        Ok(cast) => cast,
+                        Err(e) => {
+                            break 'block Err(e.at(here).with_related(
+                                source,
+                                format!("the value is {} here", real_output.formal_type.output),
+                            ));
+                        }
+                    };
 
                 let src = InputValueTypeSource::FromOutput(op);
 
@@ -196,7 +281,7 @@ impl NodeGraphFormalTypeAnalysis {
                     type_source: src,
                 })
             }
-            (Err(e), None) => Err(e.clone()),
+            (Err(e), None) => Err(e.clone().or_at(here)),
             (Ok(specd_input_type), None) => {
                 let mut formal_type = (specd_input_type).clone();
                 let itself = (
@@ -224,6 +309,76 @@ impl NodeGraphFormalTypeAnalysis {
         inp_notes
     }
 
+    // What an input sees of the value it reads. Sources come earlier in the order, so they're
+    // analyzed by now, unless their node has no type to analyze.
+    fn source_notes<T: AccessibleFallibleType>(
+        &mut self,
+        graph: &NodeGraph<T>,
+        val_ref: ValueRef,
+    ) -> MaybeOutputTypeNotes {
+        if let Some(notes) = self.output_type_notes.get(&val_ref) {
+            return notes.clone();
+        }
+        match graph
+            .get_node(val_ref.node)
+            .map(|n| n.annotation.fallible())
+        {
+            Some(Err(e)) => {
+                let err = e.clone().or_at(ErrorLocation::Output(val_ref));
+                self.output_type_notes.insert(val_ref, Err(err.clone()));
+                Err(err)
+            }
+            _ => Err(TypeError::new(
+                TypeErrorKind::MissingNode,
+                "Source was not analyzed before its consumer",
+            )
+            .at(ErrorLocation::Output(val_ref))),
+        }
+    }
+
+    // The type a node is checked as: its declared one, or for a polymorphic node, that with its
+    // type variables inferred from what's connected to it. Sources have to be analyzed already.
+    fn instance<T: AccessibleFallibleType>(
+        &mut self,
+        graph: &NodeGraph<T>,
+        node_ref: NodeRef,
+        declared: &Rc<NodeTypeInfo<MaybeValueType, ExecutionInformation>>,
+    ) -> FallibleNodeTypeRc {
+        if !is_polymorphic(declared) {
+            return Ok(declared.clone());
+        }
+        if let Some(instance) = self.instances.get(&node_ref) {
+            return instance.clone();
+        }
+        let node = graph.get_node(node_ref).unwrap();
+        let mut bindings = TypeBindings::default();
+        let mut instance = Ok(());
+        for (input_ind, input) in declared.inputs.iter().enumerate() {
+            // Inputs with broken types or sources get their own errors.
+            let (Ok(declared_input), Some(source)) = (&input.value_type, node.inputs[input_ind])
+            else {
+                continue;
+            };
+            let Ok(actual) = self.source_notes(graph, source) else {
+                continue;
+            };
+            let input = NodeInputReference {
+                source_node: node_ref,
+                input_ind,
+            };
+            instance = bindings.unify(declared_input, &actual.formal_type, &input);
+            if instance.is_err() {
+                break;
+            }
+        }
+        let instance = instance
+            .and_then(|_| bindings.instantiate(declared))
+            .map_err(|e| e.at(ErrorLocation::Node(node_ref)));
+        self.instances.insert(node_ref, instance.clone());
+        instance
+    }
+
+    // Only looks at the node's own inputs, which it analyzes first if they aren't yet.
     fn analyze_single_output<T: AccessibleFallibleType>(
         &mut self,
         graph: &NodeGraph<T>,
@@ -240,15 +395,23 @@ impl NodeGraphFormalTypeAnalysis {
             output_index,
         } = val_ref;
 
+        let here = ErrorLocation::Output(val_ref);
+
         // Original node reference
         let node = graph.get_node(node_ref).unwrap();
 
-        // Original node type
+        // Original node type, instantiated if it's polymorphic
         let node_type = match node.annotation.fallible().as_ref() {
+            Ok(a) => self
+                .instance(graph, node_ref, a)
+                .map_err(|e| e.at(here.clone())),
+            Err(e) => Err(e.clone().or_at(here.clone())),
+        };
+        let node_type = match node_type {
             Ok(a) => a,
-            Err(e) => {
-                self.output_type_notes.insert(val_ref, Err(e.clone()));
-                return Err(e.clone());
+            Err(err) => {
+                self.output_type_notes.insert(val_ref, Err(err.clone()));
+                return Err(err);
             }
         };
 
@@ -258,7 +421,7 @@ impl NodeGraphFormalTypeAnalysis {
         let output_type_notes = 'block: {
             let output_type = match &output_type.value_type {
                 Ok(a) => a,
-                Err(e) => break 'block Err(e.clone()),
+                Err(e) => break 'block Err(e.clone().or_at(here)),
             };
             // Steps:
             // 1. Record types of inputs, real and missing.
@@ -271,32 +434,44 @@ impl NodeGraphFormalTypeAnalysis {
             //      - Arguments: union of excess arguments of inputs, unioned with spec'd arguments of output
             //      -
 
-            // Question: How to handle two excess input arguments with same name but different arguments?
-            // For now: Just fail.
-            // Possible solution: Intersect them / pick the 'narrowest' form / defer to constant functions basically.
-            // Potential pain point: Inserting "constant wrt"s to allow the narrower inputs to extend to the broader uses.
+            // Two excess input arguments with the same name but different types get narrowed to the
+            // narrowest of them, and the inputs that wanted them broader are noted as widened.
+            // Types that don't narrow into each other (u32 vs f32, say) are still an error.
 
             //let mut input_types: Vec<ValueType> = vec![];
             let mut excess_input_args: HashMap<String, ValueType> = HashMap::new();
+            let mut arg_uses: Vec<(usize, String, ValueType)> = Vec::new();
+            // Primitives the inputs' values had before any cast, for ops that work out their
+            // output from them.
+            let mut operand_types: Vec<PrimitiveType> = Vec::new();
 
             for i in 0..node_type.inputs.len() {
+                let inp_ref = NodeInputReference {
+                    source_node: node_ref,
+                    input_ind: i,
+                };
                 let specd_input_type = match &node_type.inputs[i].value_type {
                     Ok(a) => a,
-                    Err(e) => break 'block Err(e.clone()),
+                    Err(e) => {
+                        break 'block Err(e
+                            .clone()
+                            .or_at(ErrorLocation::Input(inp_ref))
+                            .propagate(here));
+                    }
                 };
                 // This function call will insert "Constant WRT"s and casts as-needed, so that provided_input_type is always a superset of specd. ??? Maybe???
-                let provided_input_type = self.analyze_single_input(
-                    graph,
-                    NodeInputReference {
-                        source_node: node_ref,
-                        input_ind: i,
-                    },
-                );
+                let provided_input_type = self.analyze_single_input(graph, inp_ref.clone());
 
                 let provided_input_type = match provided_input_type {
                     Ok(a) => a,
-                    Err(e) => break 'block Err(e),
+                    Err(e) => break 'block Err(e.propagate(here)),
                 };
+                operand_types.push(
+                    provided_input_type
+                        .cast()
+                        .map(|cast| cast.from)
+                        .unwrap_or(provided_input_type.formal_type.output),
+                );
 
                 for (name, typ) in &provided_input_type.formal_type.inputs {
                     if specd_input_type.inputs.contains_key(name) {
@@ -305,22 +480,54 @@ impl NodeGraphFormalTypeAnalysis {
 
                     let slot = excess_input_args.entry(name.clone());
                     match slot {
-                        std::collections::hash_map::Entry::Occupied(occupied_entry) => {
+                        std::collections::hash_map::Entry::Occupied(mut occupied_entry) => {
                             let curr = occupied_entry.get();
-                            if *curr != **typ {
-                                break 'block Err(TypeError {
-                            message: "Two arguments with same name and different inputs. Might change behavior later to be narrowing.".to_string()
-                        });
-                                //panic!("Two arguments with same name and different inputs. Might change behavior later to be narrowing.")
+                            match narrowest(curr, typ) {
+                                Some(narrow) => *occupied_entry.get_mut() = narrow,
+                                None => {
+                                    let first = arg_uses
+                                        .iter()
+                                        .find(|(_, n, _)| n == name)
+                                        .map_or(i, |(ind, _, _)| *ind);
+                                    let input_at = |input_ind| {
+                                        ErrorLocation::Input(NodeInputReference {
+                                            source_node: node_ref,
+                                            input_ind,
+                                        })
+                                    };
+                                    break 'block Err(TypeError::new(
+                                        TypeErrorKind::ArgumentConflict,
+                                        format!(
+                                            "Inputs disagree on argument {}: {} and {} don't narrow to a common type",
+                                            name, curr, typ
+                                        ),
+                                    )
+                                    .at(here)
+                                    .with_related(input_at(first), format!("{} is {} here", name, curr))
+                                    .with_related(input_at(i), format!("and {} here", typ)));
+                                }
                             }
                         }
                         std::collections::hash_map::Entry::Vacant(vacant_entry) => {
                             vacant_entry.insert(*typ.clone());
                         }
                     };
+                    arg_uses.push((i, name.clone(), *typ.clone()));
                 }
             }
 
+            arg_uses.sort_by(|a, b| (a.0, &a.1).cmp(&(b.0, &b.1)));
+            let widened_inputs = arg_uses
+                .into_iter()
+                .filter(|(_, name, typ)| excess_input_args[name] != *typ)
+                .map(|(input_ind, arg, to)| ArgumentWidening {
+                    input_ind,
+                    from: excess_input_args[&arg].clone(),
+                    arg,
+                    to,
+                })
+                .collect();
+
             let inputs_parameterized_by = excess_input_args.clone();
 
             let mut output_formal_args = excess_input_args;
@@ -339,7 +546,7 @@ impl NodeGraphFormalTypeAnalysis {
                         // This SHOULD be okay, because it's actually two evaluation steps (first to deparameterize the output and second to evaluate it.)
                         // I think?
                         if *curr != **output_inp.1 {
-                            break 'block Err(TypeError { message: "Input and output arguments with same name and different inputs. WILL change behavior later.".to_string() });
+                            break 'block Err(TypeError::new(TypeErrorKind::ArgumentConflict, "Input and output arguments with same name and different inputs. WILL change behavior later.").at(here));
                         }
                     }
                     std::collections::hash_map::Entry::Vacant(vacant_entry) => {
@@ -348,18 +555,36 @@ impl NodeGraphFormalTypeAnalysis {
                 };
             }
 
+            // Index arithmetic keeps the bounds of its operands, as far as it can.
+            let output_primitive = match &node_type.annotation {
+                ExecutionInformation::Index(op) => {
+                    match op.result_type(&operand_types, output_type.output) {
+                        Ok(prim) => prim,
+                        Err(e) => break 'block Err(e.or_at(here)),
+                    }
+                }
+                ExecutionInformation::Pack | ExecutionInformation::Unpack => {
+                    match node_conversion(&node_type) {
+                        Ok(_) => output_type.output,
+                        Err(e) => break 'block Err(e.at(here)),
+                    }
+                }
+                _ => output_type.output,
+            };
+
             let actual_output_type = ValueType {
                 inputs: output_formal_args
                     .into_iter()
                     .map(|f| (f.0, Box::new(f.1)))
                     .collect(),
-                output: output_type.output,
+                output: output_primitive,
             };
 
             Ok(OutputTypeNotes {
                 formal_type: actual_output_type,
                 step_computation_requires: specd_output_args,
                 inputs_parameterized_by,
+                widened_inputs,
             })
         };
 
@@ -369,36 +594,173 @@ impl NodeGraphFormalTypeAnalysis {
         output_type_notes
     }
 
+    fn analyze_node<T: AccessibleFallibleType>(&mut self, graph: &NodeGraph<T>, node_ref: NodeRef) {
+        let Some(Ok(typ)) = graph.get_node(node_ref).map(|n| n.annotation.fallible()) else {
+            return;
+        };
+        for inp in 0..typ.inputs.len() {
+            let _ = self.analyze_single_input(
+                graph,
+                NodeInputReference {
+                    source_node: node_ref,
+                    input_ind: inp,
+                },
+            );
+        }
+
+        for outp in 0..typ.outputs.len() {
+            let _ = self.analyze_single_output(
+                graph,
+                ValueRef {
+                    node: node_ref,
+                    output_index: outp,
+                },
+            );
+        }
+    }
+
+    fn seed_problem<T: AccessibleFallibleType>(
+        &mut self,
+        graph: &NodeGraph<T>,
+        problem: &GraphProblem,
+    ) {
+        let err = problem.to_type_error();
+        match problem {
+            GraphProblem::DanglingInput { input, .. }
+            | GraphProblem::OutputOutOfRange { input, .. } => {
+                self.input_type_notes.insert(input.clone(), Err(err));
+            }
+            GraphProblem::InputCountMismatch { node, .. } => self.seed_node(graph, *node, err),
+        }
+    }
+
+    // Gives every input and output of the node the same error.
+    fn seed_node<T: AccessibleFallibleType>(
+        &mut self,
+        graph: &NodeGraph<T>,
+        node_ref: NodeRef,
+        err: TypeError,
+    ) {
+        let Some(node) = graph.get_node(node_ref) else {
+            return;
+        };
+        let (inputs, outputs) = match node.annotation.fallible() {
+            Ok(typ) => (typ.inputs.len().max(node.inputs.len()), typ.outputs.len()),
+            Err(_) => (node.inputs.len(), 0),
+        };
+        for input_ind in 0..inputs {
+            let inp_ref = NodeInputReference {
+                source_node: node_ref,
+                input_ind,
+            };
+            let here = ErrorLocation::Input(inp_ref.clone());
+            self.input_type_notes
+                .insert(inp_ref, Err(err.clone().at(here)));
+        }
+        for output_index in 0..outputs {
+            let val_ref = ValueRef {
+                node: node_ref,
+                output_index,
+            };
+            self.output_type_notes
+                .insert(val_ref, Err(err.clone().at(ErrorLocation::Output(val_ref))));
+        }
+    }
+
     pub fn analyze<T: AccessibleFallibleType>(graph: &NodeGraph<T>) -> NodeGraphFormalTypeAnalysis {
+        Self::analyze_with_policy(graph, OutOfDomainPolicy::default())
+    }
+
+    pub fn analyze_with_policy<T: AccessibleFallibleType>(
+        graph: &NodeGraph<T>,
+        out_of_domain: OutOfDomainPolicy,
+    ) -> NodeGraphFormalTypeAnalysis {
         let mut analysis = NodeGraphFormalTypeAnalysis {
             output_type_notes: HashMap::new(),
             input_type_notes: HashMap::new(),
+            validation: ValidationReport::default(),
+            out_of_domain,
+            instances: HashMap::new(),
+            read_from: HashMap::new(),
+            dependents: HashMap::new(),
         };
-        let nodes: Vec<(NodeRef, &Node<T>)> = graph.iter_nodes().collect();
-        for i in nodes {
-            let typ = i.1.annotation.fallible();
-            if let Ok(typ) = typ {
-                for inp in 0..typ.inputs.len() {
-                    let _ = analysis.analyze_single_input(
-                        graph,
-                        NodeInputReference {
-                            source_node: i.0,
-                            input_ind: inp,
-                        },
-                    );
+        let all = graph.iter_nodes().map(|n| n.0).collect();
+        analysis.reanalyze(graph, &all);
+        analysis
+    }
+
+    // (Re)computes the notes of `dirty`, which must include everything downstream of it.
+    // The notes of everything else are taken as they are.
+    fn reanalyze<T: AccessibleFallibleType>(
+        &mut self,
+        graph: &NodeGraph<T>,
+        dirty: &HashSet<NodeRef>,
+    ) {
+        for node_ref in dirty {
+            for src in self.read_from.remove(node_ref).unwrap_or_default() {
+                if let Some(deps) = self.dependents.get_mut(&src) {
+                    deps.remove(node_ref);
                 }
+            }
+            if graph.get_node(*node_ref).is_none() {
+                self.dependents.remove(node_ref);
+            }
+        }
 
-                for outp in 0..typ.outputs.len() {
-                    let _ = analysis.analyze_single_output(
-                        graph,
-                        ValueRef {
-                            node: i.0,
-                            output_index: outp,
-                        },
-                    );
+        // Malformed parts of the graph get their errors up front. Everything below only looks at
+        // notes that aren't there yet, so it never follows a bad reference. Problems of the clean
+        // nodes can't have changed, since whatever they read from is clean too.
+        let found = graph.validate_nodes(dirty.iter().copied());
+        for problem in &found.problems {
+            self.seed_problem(graph, problem);
+        }
+        self.validation
+            .problems
+            .retain(|problem| !dirty.contains(&problem.node()));
+        self.validation.problems.extend(found.problems);
+        self.validation.problems.sort_by_key(GraphProblem::node);
+
+        // A worklist in dependency order: by the time a node comes up, everything it reads from has
+        // its notes, so nothing recurses however deep the graph is. Clean nodes already have theirs,
+        // and anything on a cycle with a dirty node is downstream of it, so dirty as well. A dirty
+        // node reading from a clean one on a cycle gets the cycle's error through that input.
+        let order = match graph.topological_order_of(dirty) {
+            Ok(order) => order,
+            Err(cycle) => {
+                let on_cycle: HashSet<NodeRef> = cycle.nodes.iter().copied().collect();
+                let ordered: HashSet<NodeRef> = cycle.ordered.iter().copied().collect();
+                let unordered: Vec<NodeRef> = dirty
+                    .iter()
+                    .copied()
+                    .filter(|n| !ordered.contains(n) && graph.get_node(*n).is_some())
+                    .collect();
+                for node_ref in unordered {
+                    let err = if on_cycle.contains(&node_ref) {
+                        TypeError::new(TypeErrorKind::MalformedGraph, cycle.to_string())
+                    } else {
+                        let mut err =
+                            TypeError::new(TypeErrorKind::MalformedGraph, "Depends on a cycle");
+                        if let Some(first) = cycle.nodes.first() {
+                            err = err.at(ErrorLocation::Node(*first));
+                        }
+                        err.propagate(ErrorLocation::Node(node_ref))
+                    };
+                    self.seed_node(graph, node_ref, err);
                 }
+                cycle.ordered
+            }
+        };
+
+        for node_ref in order {
+            let sources: Vec<NodeRef> = graph
+                .get_node(node_ref)
+                .map(|n| n.inputs.iter().flatten().map(|v| v.node).collect())
+                .unwrap_or_default();
+            for src in &sources {
+                self.dependents.entry(*src).or_default().insert(node_ref);
             }
+            self.read_from.insert(node_ref, sources);
+            self.analyze_node(graph, node_ref);
         }
-        analysis
     }
 }
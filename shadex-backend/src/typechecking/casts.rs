@@ -0,0 +1,115 @@
+use crate::typechecking::typetypes::{
+    PrimitiveType, TypeError, TypeErrorKind, U32Boundedness, ValueType,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CastKind {
+    // Every value of the source type comes out exactly.
+    Lossless,
+    // Some values get rounded, truncated or wrapped on the way.
+    Lossy,
+    // A bounded index used where a looser bound, or any u32, is expected. The value isn't touched.
+    BoundedWidening,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cast {
+    pub from: PrimitiveType,
+    pub to: PrimitiveType,
+    pub kind: CastKind,
+}
+
+// Bounds whose every index fits exactly in an f32 / an i32.
+const F32_EXACT_BOUND: u32 = 1 << 24;
+const I32_EXACT_BOUND: u32 = 1 << 31;
+// Every u8 is below this.
+const U8_BOUND: u32 = 1 << 8;
+
+// How a value of `from` gets used where `to` is expected: as it is (None), through a cast, or not at all.
+// Going from bounded to unbounded u32 and on to i32 or f32 only ever loses information on the way up;
+// nothing is implicitly narrowed to a bounded u32.
+// A u8 goes to any u32 that holds 256 values, and a u32 bounded by 256 goes to a u8, both exactly.
+// Neither is a widening, so a u8 never stands in for a bounded index or the other way around.
+// Vectors cast entry by entry, but only to vectors of the same size, and bools and matrices don't
+// cast at all.
+pub fn implicit_cast(from: PrimitiveType, to: PrimitiveType) -> Result<Option<Cast>, TypeError> {
+    if from == to {
+        return Ok(None);
+    }
+    let no_cast = || {
+        TypeError::new(
+            TypeErrorKind::PrimitiveMismatch,
+            format!("No implicit cast from {} to {}", from, to),
+        )
+    };
+    let kind = match (from, to) {
+        (
+            PrimitiveType::U32(U32Boundedness::Bounded(_)),
+            PrimitiveType::U32(U32Boundedness::Unbounded),
+        ) => CastKind::BoundedWidening,
+        (
+            PrimitiveType::U32(U32Boundedness::Bounded(n)),
+            PrimitiveType::U32(U32Boundedness::Bounded(m)),
+        ) if n <= m => CastKind::BoundedWidening,
+        (PrimitiveType::U32(U32Boundedness::Bounded(n)), PrimitiveType::F32)
+            if n <= F32_EXACT_BOUND =>
+        {
+            CastKind::Lossless
+        }
+        (PrimitiveType::U32(U32Boundedness::Bounded(n)), PrimitiveType::I32)
+            if n <= I32_EXACT_BOUND =>
+        {
+            CastKind::Lossless
+        }
+        (PrimitiveType::U8, PrimitiveType::U32(U32Boundedness::Unbounded)) => CastKind::Lossless,
+        (PrimitiveType::U8, PrimitiveType::U32(U32Boundedness::Bounded(m))) if m >= U8_BOUND => {
+            CastKind::Lossless
+        }
+        (PrimitiveType::U32(U32Boundedness::Bounded(n)), PrimitiveType::U8) if n <= U8_BOUND => {
+            CastKind::Lossless
+        }
+        (PrimitiveType::U8, PrimitiveType::F32 | PrimitiveType::I32) => CastKind::Lossless,
+        (PrimitiveType::Vector(n, a), PrimitiveType::Vector(m, b)) if n == m => {
+            match implicit_cast(a.primitive(), b.primitive()) {
+                Ok(Some(cast)) => cast.kind,
+                _ => return Err(no_cast()),
+            }
+        }
+        (PrimitiveType::U32(_), PrimitiveType::F32 | PrimitiveType::I32)
+        | (PrimitiveType::I32, PrimitiveType::F32)
+        | (PrimitiveType::F32, PrimitiveType::I32)
+        | (
+            PrimitiveType::I32 | PrimitiveType::F32,
+            PrimitiveType::U32(U32Boundedness::Unbounded),
+        ) => CastKind::Lossy,
+        _ => return Err(no_cast()),
+    };
+    Ok(Some(Cast { from, to, kind }))
+}
+
+// The narrowest type both `a` and `b` can be used as, when one only differs from the other by a
+// tighter bound. `[3]` and `[4]` narrow to `[3]`, `[3]` and `u32` to `[3]`; `u32` and `f32` don't.
+pub fn narrowest(a: &ValueType, b: &ValueType) -> Option<ValueType> {
+    if a == b {
+        return Some(a.clone());
+    }
+    if !a.inputs.is_empty() || !b.inputs.is_empty() {
+        return None;
+    }
+    let widens = |from, to| {
+        matches!(
+            implicit_cast(from, to),
+            Ok(Some(Cast {
+                kind: CastKind::BoundedWidening,
+                ..
+            }))
+        )
+    };
+    if widens(a.output, b.output) {
+        Some(a.clone())
+    } else if widens(b.output, a.output) {
+        Some(b.clone())
+    } else {
+        None
+    }
+}
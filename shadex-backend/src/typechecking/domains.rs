@@ -0,0 +1,75 @@
+use crate::typechecking::typetypes::{
+    PrimitiveType, TypeError, TypeErrorKind, U32Boundedness, ValueType,
+};
+
+// What happens when a value is asked for at an argument outside the domain it's defined over,
+// like the fourth component of an RGB colour.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutOfDomainPolicy {
+    // Use the value at the nearest index that's in the domain.
+    #[default]
+    Clamp,
+    // Use zero.
+    Zero,
+    // Don't typecheck.
+    Error,
+}
+
+// An argument that an input ranges over further than its source is defined.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DomainGuard {
+    pub arg: String,
+    // The source is defined for 0..bound.
+    pub bound: u32,
+    pub policy: OutOfDomainPolicy,
+}
+
+// Whether a source defined over `source` for `arg` can be read by an input that ranges over `wanted`.
+// Arguments go the other way around from values: a source over u32 can be read at any [n], and one
+// over [4] at any [3]. Reading a source over [3] at a [4] or u32 needs a guard for the indices it
+// doesn't have.
+pub fn fit_argument(
+    arg: &str,
+    source: &ValueType,
+    wanted: &ValueType,
+    policy: OutOfDomainPolicy,
+) -> Result<Option<DomainGuard>, TypeError> {
+    if source == wanted {
+        return Ok(None);
+    }
+    let mismatch = || {
+        TypeError::new(
+            TypeErrorKind::ArgumentConflict,
+            format!(
+                "Argument {} is {} in the source but {} in the input",
+                arg, source, wanted
+            ),
+        )
+    };
+    if !source.inputs.is_empty() || !wanted.inputs.is_empty() {
+        return Err(mismatch());
+    }
+    let bound = match (source.output, wanted.output) {
+        (PrimitiveType::U32(U32Boundedness::Unbounded), PrimitiveType::U32(_)) => return Ok(None),
+        (
+            PrimitiveType::U32(U32Boundedness::Bounded(n)),
+            PrimitiveType::U32(U32Boundedness::Bounded(m)),
+        ) if m <= n => return Ok(None),
+        (PrimitiveType::U32(U32Boundedness::Bounded(n)), PrimitiveType::U32(_)) => n,
+        _ => return Err(mismatch()),
+    };
+    match policy {
+        OutOfDomainPolicy::Error => Err(TypeError::new(
+            TypeErrorKind::ArgumentConflict,
+            format!(
+                "Argument {} ranges over {} here, but the source is only defined over {}",
+                arg, wanted, source
+            ),
+        )),
+        policy => Ok(Some(DomainGuard {
+            arg: arg.to_string(),
+            bound,
+            policy,
+        })),
+    }
+}
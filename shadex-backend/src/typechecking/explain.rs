@@ -0,0 +1,326 @@
+use std::{
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
+
+use crate::{
+    execution::ExecutionInformation,
+    nodegraph::{NodeGraph, NodeInputReference, NodeRef, ValueRef},
+    typechecking::{
+        InputTypeNotes, InputValueTypeSource, NodeGraphFormalTypeAnalysis, OutputTypeNotes,
+        typetypes::{AccessibleFallibleType, TypeError, TypeErrorKind, ValueType},
+    },
+};
+
+// Where an argument of some port's formal type enters the graph.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ArgumentOrigin {
+    // An Attr node's output, which is a function of the attribute.
+    Attr(ValueRef),
+    // An output whose declared type takes the argument, like Vec3's `comp`.
+    OutputSpec(ValueRef),
+    // An unconnected input, which is a function of its own name and of whatever its spec takes.
+    FreeVariable(NodeInputReference),
+    // An input that takes an argument its value doesn't depend on, so the value is constant in it.
+    ConstantWrt(NodeInputReference),
+}
+
+impl ArgumentOrigin {
+    pub fn node(&self) -> NodeRef {
+        match self {
+            ArgumentOrigin::Attr(val) | ArgumentOrigin::OutputSpec(val) => val.node,
+            ArgumentOrigin::FreeVariable(inp) | ArgumentOrigin::ConstantWrt(inp) => inp.source_node,
+        }
+    }
+}
+
+// How an argument gets to a port. Outputs reached several ways share their derivation.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Derivation {
+    Origin(ArgumentOrigin),
+    // Passed on by an output from every input of its node that has the argument left over.
+    Through(ValueRef, Vec<Rc<Derivation>>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArgumentExplanation {
+    pub arg: String,
+    // The argument's type as the port has it.
+    pub typ: ValueType,
+    pub derivation: Rc<Derivation>,
+}
+
+impl ArgumentExplanation {
+    // Each origin of the argument, with the outputs it goes through from there to the port. An
+    // output reached several ways is only followed the first time, so there's one path per origin.
+    pub fn paths(&self) -> Vec<(ArgumentOrigin, Vec<ValueRef>)> {
+        let mut paths: Vec<(ArgumentOrigin, Vec<ValueRef>)> = Vec::new();
+        let mut via = Vec::new();
+        let mut seen = HashSet::new();
+        // A walk with its own stack, since derivations are as deep as the graph. `None` marks
+        // where the walk leaves an output again.
+        let mut stack = vec![Some(&*self.derivation)];
+        while let Some(step) = stack.pop() {
+            match step {
+                None => {
+                    via.pop();
+                }
+                Some(Derivation::Origin(origin)) => {
+                    if !paths.iter().any(|(o, _)| o == origin) {
+                        paths.push((origin.clone(), via.iter().rev().copied().collect()));
+                    }
+                }
+                Some(Derivation::Through(val, from)) => {
+                    if !seen.insert(*val) {
+                        continue;
+                    }
+                    via.push(*val);
+                    stack.push(None);
+                    stack.extend(from.iter().rev().map(|d| Some(&**d)));
+                }
+            }
+        }
+        paths
+    }
+
+    // One line per origin, like "x comes from Attr#3 via AddF#7".
+    pub fn describe(&self, node_name: impl Fn(NodeRef) -> String) -> Vec<String> {
+        self.paths()
+            .into_iter()
+            .map(|(origin, via)| {
+                let from = match &origin {
+                    ArgumentOrigin::Attr(val) => node_name(val.node),
+                    ArgumentOrigin::OutputSpec(val) => {
+                        format!("the declared output of {}", node_name(val.node))
+                    }
+                    ArgumentOrigin::FreeVariable(inp) => format!(
+                        "unconnected input {} of {}",
+                        inp.input_ind,
+                        node_name(inp.source_node)
+                    ),
+                    ArgumentOrigin::ConstantWrt(inp) => format!(
+                        "input {} of {}, which is constant in it",
+                        inp.input_ind,
+                        node_name(inp.source_node)
+                    ),
+                };
+                let mut line = format!("{} comes from {}", self.arg, from);
+                // An unconnected input's own node goes without saying.
+                let via: Vec<String> = via
+                    .iter()
+                    .filter(|val| val.node != origin.node())
+                    .map(|val| node_name(val.node))
+                    .collect();
+                if !via.is_empty() {
+                    line.push_str(&format!(" via {}", via.join(", ")));
+                }
+                line
+            })
+            .collect()
+    }
+}
+
+// Dropping a deep derivation one level per call would overflow the stack, so the subtrees nothing
+// else holds are taken apart here instead.
+impl Drop for Derivation {
+    fn drop(&mut self) {
+        let Derivation::Through(_, from) = self else {
+            return;
+        };
+        let mut pending = std::mem::take(from);
+        while let Some(derivation) = pending.pop() {
+            if let Ok(mut derivation) = Rc::try_unwrap(derivation)
+                && let Derivation::Through(_, from) = &mut derivation
+            {
+                pending.append(from);
+            }
+        }
+    }
+}
+
+// How an output gets an argument, with the outputs it's passed on from still to be derived.
+enum DerivationStep {
+    Origin(ArgumentOrigin),
+    Through(Vec<StepSource>),
+}
+
+enum StepSource {
+    Origin(ArgumentOrigin),
+    Output(ValueRef),
+}
+
+type DerivationMemo = HashMap<(ValueRef, String), Rc<Derivation>>;
+
+fn not_analyzed() -> TypeError {
+    TypeError::new(TypeErrorKind::MissingNode, "Port was not analyzed")
+}
+
+impl NodeGraphFormalTypeAnalysis {
+    // Where each argument of the output's formal type comes from, in argument order.
+    pub fn explain<T: AccessibleFallibleType>(
+        &self,
+        graph: &NodeGraph<T>,
+        val_ref: ValueRef,
+    ) -> Result<Vec<ArgumentExplanation>, TypeError> {
+        let notes = self.output_notes(val_ref)?;
+        let mut memo = DerivationMemo::new();
+        self.explain_args(&notes.formal_type, |arg| {
+            self.derive_output(graph, val_ref, arg, &mut memo)
+        })
+    }
+
+    // Same for an input's formal type.
+    pub fn explain_input<T: AccessibleFallibleType>(
+        &self,
+        graph: &NodeGraph<T>,
+        inp_ref: &NodeInputReference,
+    ) -> Result<Vec<ArgumentExplanation>, TypeError> {
+        let notes = self.input_notes(inp_ref)?;
+        let mut memo = DerivationMemo::new();
+        self.explain_args(&notes.formal_type, |arg| match &notes.type_source {
+            InputValueTypeSource::FreeVariable(_) => Ok(Rc::new(Derivation::Origin(
+                ArgumentOrigin::FreeVariable(inp_ref.clone()),
+            ))),
+            InputValueTypeSource::FromOutput(promotion)
+                if promotion.added_constant_wrt.contains_key(arg) =>
+            {
+                Ok(Rc::new(Derivation::Origin(ArgumentOrigin::ConstantWrt(
+                    inp_ref.clone(),
+                ))))
+            }
+            InputValueTypeSource::FromOutput(_) => {
+                let source = graph
+                    .get_node(inp_ref.source_node)
+                    .and_then(|n| n.inputs.get(inp_ref.input_ind).copied().flatten())
+                    .ok_or_else(not_analyzed)?;
+                self.derive_output(graph, source, arg, &mut memo)
+            }
+        })
+    }
+
+    fn explain_args(
+        &self,
+        formal_type: &ValueType,
+        mut derive: impl FnMut(&str) -> Result<Rc<Derivation>, TypeError>,
+    ) -> Result<Vec<ArgumentExplanation>, TypeError> {
+        let mut args = formal_type
+            .inputs
+            .iter()
+            .map(|(arg, typ)| {
+                Ok(ArgumentExplanation {
+                    arg: arg.clone(),
+                    typ: (**typ).clone(),
+                    derivation: derive(arg)?,
+                })
+            })
+            .collect::<Result<Vec<_>, TypeError>>()?;
+        args.sort_by(|a, b| a.arg.cmp(&b.arg));
+        Ok(args)
+    }
+
+    fn output_notes(&self, val_ref: ValueRef) -> Result<&OutputTypeNotes, TypeError> {
+        match self.output_type_notes.get(&val_ref) {
+            Some(Ok(notes)) => Ok(notes),
+            Some(Err(e)) => Err(e.clone()),
+            None => Err(not_analyzed()),
+        }
+    }
+
+    fn input_notes(&self, inp_ref: &NodeInputReference) -> Result<&InputTypeNotes, TypeError> {
+        match self.input_type_notes.get(inp_ref) {
+            Some(Ok(notes)) => Ok(notes),
+            Some(Err(e)) => Err(e.clone()),
+            None => Err(not_analyzed()),
+        }
+    }
+
+    // Works through the outputs on its own stack, deriving each one once its sources are memoised,
+    // so a long chain doesn't recurse once per node.
+    fn derive_output<T: AccessibleFallibleType>(
+        &self,
+        graph: &NodeGraph<T>,
+        val_ref: ValueRef,
+        arg: &str,
+        memo: &mut DerivationMemo,
+    ) -> Result<Rc<Derivation>, TypeError> {
+        let key = |val: ValueRef| (val, arg.to_string());
+        let mut stack = vec![val_ref];
+        while let Some(&top) = stack.last() {
+            if memo.contains_key(&key(top)) {
+                stack.pop();
+                continue;
+            }
+            let derivation = match self.derivation_step(graph, top, arg)? {
+                DerivationStep::Origin(origin) => Derivation::Origin(origin),
+                DerivationStep::Through(from) => {
+                    let pending: Vec<ValueRef> = from
+                        .iter()
+                        .filter_map(|source| match source {
+                            StepSource::Output(source) if !memo.contains_key(&key(*source)) => {
+                                Some(*source)
+                            }
+                            _ => None,
+                        })
+                        .collect();
+                    if !pending.is_empty() {
+                        stack.extend(pending);
+                        continue;
+                    }
+                    let from = from
+                        .into_iter()
+                        .map(|source| match source {
+                            StepSource::Origin(origin) => Rc::new(Derivation::Origin(origin)),
+                            StepSource::Output(source) => memo[&key(source)].clone(),
+                        })
+                        .collect();
+                    Derivation::Through(top, from)
+                }
+            };
+            memo.insert(key(top), Rc::new(derivation));
+            stack.pop();
+        }
+        Ok(memo[&key(val_ref)].clone())
+    }
+
+    fn derivation_step<T: AccessibleFallibleType>(
+        &self,
+        graph: &NodeGraph<T>,
+        val_ref: ValueRef,
+        arg: &str,
+    ) -> Result<DerivationStep, TypeError> {
+        let notes = self.output_notes(val_ref)?;
+        let node = graph.get_node(val_ref.node).ok_or_else(not_analyzed)?;
+        let is_attr = node
+            .annotation
+            .fallible()
+            .as_ref()
+            .is_ok_and(|t| matches!(t.annotation, ExecutionInformation::Attr(_)));
+
+        if is_attr {
+            return Ok(DerivationStep::Origin(ArgumentOrigin::Attr(val_ref)));
+        }
+        if notes.step_computation_requires.contains_key(arg) {
+            return Ok(DerivationStep::Origin(ArgumentOrigin::OutputSpec(val_ref)));
+        }
+        // Otherwise it's left over from some of the inputs, which don't take it themselves.
+        let mut from = Vec::new();
+        for (input_ind, source) in node.inputs.iter().enumerate() {
+            let inp_ref = NodeInputReference {
+                source_node: val_ref.node,
+                input_ind,
+            };
+            match (&self.input_notes(&inp_ref)?.type_source, source) {
+                (InputValueTypeSource::FreeVariable(fv), _) if fv.itself.0 == arg => {
+                    from.push(StepSource::Origin(ArgumentOrigin::FreeVariable(inp_ref)));
+                }
+                (InputValueTypeSource::FromOutput(promotion), Some(source))
+                    if promotion.underspecified_args.contains(arg) =>
+                {
+                    from.push(StepSource::Output(*source));
+                }
+                _ => {}
+            }
+        }
+        Ok(DerivationStep::Through(from))
+    }
+}
@@ -0,0 +1,80 @@
+use std::{collections::HashSet, rc::Rc};
+
+use crate::{
+    nodegraph::{FallibleNodeTypeRc, NodeGraph, NodeInputReference, NodeRef},
+    typechecking::{NodeGraphFormalTypeAnalysis, typetypes::AccessibleFallibleType},
+};
+
+// Something that was done to a graph since it was last analyzed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GraphEdit {
+    NodeAdded(NodeRef),
+    // Inputs that read from the node were disconnected along with it, and don't need edits of their own.
+    NodeRemoved(NodeRef),
+    InputRewired(NodeInputReference),
+    // The node's ports changed type. A new annotation with the same signature, like a constant with
+    // a new value, doesn't change any notes and doesn't need an edit.
+    TypeChanged(NodeRef),
+}
+
+// Whether two node types have the same ports, so one can stand in for the other without retyping.
+pub fn same_signature(a: &FallibleNodeTypeRc, b: &FallibleNodeTypeRc) -> bool {
+    match (a, b) {
+        (Ok(a), Ok(b)) => {
+            Rc::ptr_eq(a, b)
+                || (a.inputs.len() == b.inputs.len()
+                    && a.outputs.len() == b.outputs.len()
+                    && a.inputs
+                        .iter()
+                        .zip(&b.inputs)
+                        .all(|(x, y)| x.name == y.name && x.value_type == y.value_type)
+                    && a.outputs
+                        .iter()
+                        .zip(&b.outputs)
+                        .all(|(x, y)| x.name == y.name && x.value_type == y.value_type))
+        }
+        (Err(a), Err(b)) => a == b,
+        _ => false,
+    }
+}
+
+impl NodeGraphFormalTypeAnalysis {
+    // Brings the analysis up to date with `graph`, which `edits` have already been applied to.
+    // Only the edited nodes and what's downstream of them are looked at again.
+    pub fn update<T: AccessibleFallibleType>(&mut self, graph: &NodeGraph<T>, edits: &[GraphEdit]) {
+        let mut dirty: HashSet<NodeRef> = edits
+            .iter()
+            .map(|edit| match edit {
+                GraphEdit::NodeAdded(node_ref)
+                | GraphEdit::NodeRemoved(node_ref)
+                | GraphEdit::TypeChanged(node_ref) => *node_ref,
+                GraphEdit::InputRewired(input) => input.source_node,
+            })
+            .collect();
+
+        // Downstream along the edges as they were, which is the only record of what a removed
+        // node fed, and as they are now.
+        let mut to_visit: Vec<NodeRef> = dirty.iter().copied().collect();
+        while let Some(node_ref) = to_visit.pop() {
+            let before = self
+                .dependents
+                .get(&node_ref)
+                .into_iter()
+                .flatten()
+                .copied();
+            let now = graph.consumers(node_ref).map(|inp| inp.source_node);
+            for dep in before.chain(now).collect::<Vec<_>>() {
+                if dirty.insert(dep) {
+                    to_visit.push(dep);
+                }
+            }
+        }
+
+        self.input_type_notes
+            .retain(|inp, _| !dirty.contains(&inp.source_node));
+        self.output_type_notes
+            .retain(|val, _| !dirty.contains(&val.node));
+        self.instances.retain(|node, _| !dirty.contains(node));
+        self.reanalyze(graph, &dirty);
+    }
+}
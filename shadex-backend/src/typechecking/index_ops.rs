@@ -0,0 +1,90 @@
+use crate::typechecking::typetypes::{PrimitiveType, TypeError, TypeErrorKind, U32Boundedness};
+
+// Arithmetic on u32 indices that keeps track of their bounds, so that e.g. an offset texture
+// coordinate or an index into a flattened table can still index things downstream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndexOp {
+    Add,
+    Mul,
+    Mod,
+    // Clamps the operand into the bound of the node's declared output.
+    Clamp,
+}
+
+impl IndexOp {
+    pub fn arity(&self) -> usize {
+        match self {
+            IndexOp::Clamp => 1,
+            IndexOp::Add | IndexOp::Mul | IndexOp::Mod => 2,
+        }
+    }
+
+    // The result's type, from the operands as they come in (before being widened to the inputs'
+    // types) and the declared output type.
+    pub fn result_type(
+        &self,
+        operands: &[PrimitiveType],
+        declared: PrimitiveType,
+    ) -> Result<PrimitiveType, TypeError> {
+        let bounds = operands
+            .iter()
+            .map(|op| match op {
+                PrimitiveType::U32(bound) => Ok(*bound),
+                _ => Err(TypeError::new(
+                    TypeErrorKind::PrimitiveMismatch,
+                    format!("Index arithmetic needs u32 operands, not {}", op),
+                )),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if bounds.len() != self.arity() {
+            return Err(TypeError::new(
+                TypeErrorKind::IncompleteNodeType,
+                format!(
+                    "Index arithmetic expected {} operand(s), found {}",
+                    self.arity(),
+                    bounds.len()
+                ),
+            ));
+        }
+
+        // The largest possible result is one less than the bound, so a bound is (max + 1).
+        let bound = match (self, bounds[0], bounds.get(1)) {
+            (IndexOp::Add, U32Boundedness::Bounded(n), Some(U32Boundedness::Bounded(m))) => {
+                n.checked_add(*m).map(|sum| sum.saturating_sub(1))
+            }
+            (IndexOp::Mul, U32Boundedness::Bounded(n), Some(U32Boundedness::Bounded(m))) => n
+                .saturating_sub(1)
+                .checked_mul(m.saturating_sub(1))
+                .and_then(|max| max.checked_add(1)),
+            // The remainder is below the divisor, and never more than the dividend.
+            (IndexOp::Mod, a, Some(b)) => {
+                let below_divisor = match b {
+                    U32Boundedness::Bounded(m) => Some(m.saturating_sub(1).max(1)),
+                    U32Boundedness::Unbounded => None,
+                };
+                let dividend = match a {
+                    U32Boundedness::Bounded(n) => Some(n),
+                    U32Boundedness::Unbounded => None,
+                };
+                below_divisor.into_iter().chain(dividend).min()
+            }
+            (IndexOp::Clamp, a, _) => {
+                let PrimitiveType::U32(U32Boundedness::Bounded(k)) = declared else {
+                    return Err(TypeError::new(
+                        TypeErrorKind::IncompleteNodeType,
+                        format!("Can only clamp to a bounded index, not {}", declared),
+                    ));
+                };
+                match a {
+                    U32Boundedness::Bounded(n) => Some(n.min(k)),
+                    U32Boundedness::Unbounded => Some(k),
+                }
+            }
+            _ => None,
+        };
+        Ok(PrimitiveType::U32(match bound {
+            Some(bound) => U32Boundedness::Bounded(bound),
+            None => U32Boundedness::Unbounded,
+        }))
+    }
+}
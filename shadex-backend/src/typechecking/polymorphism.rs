@@ -0,0 +1,178 @@
+use std::{collections::BTreeMap, rc::Rc};
+
+use crate::{
+    execution::ExecutionInformation,
+    nodegraph::{InputInfo, NodeInputReference, NodeTypeInfo, OutputInfo},
+    typechecking::{
+        casts::{Cast, CastKind, implicit_cast},
+        typetypes::{
+            ErrorLocation, MaybeValueType, PrimitiveType, ScalarType, TypeError, TypeErrorKind,
+            U32Boundedness, ValueType,
+        },
+    },
+};
+
+type DeclaredNodeType = NodeTypeInfo<MaybeValueType, ExecutionInformation>;
+
+// The variable a primitive is, or has as its entries like `vec3<T>`.
+fn var_of(prim: PrimitiveType) -> Option<char> {
+    match prim {
+        PrimitiveType::Var(var) | PrimitiveType::Vector(_, ScalarType::Var(var)) => Some(var),
+        _ => None,
+    }
+}
+
+fn mentions_var(typ: &ValueType) -> bool {
+    var_of(typ.output).is_some() || typ.inputs.values().any(|t| mentions_var(t))
+}
+
+// Whether the node type has type variables, like `a @ T; b @ T => val @ T`.
+pub fn is_polymorphic(typ: &DeclaredNodeType) -> bool {
+    let inputs = typ.inputs.iter().map(|i| &i.value_type);
+    let outputs = typ.outputs.iter().map(|o| &o.value_type);
+    inputs
+        .chain(outputs)
+        .any(|t| t.as_ref().is_ok_and(mentions_var))
+}
+
+// What each type variable of one node was inferred to be, and the input it was inferred from.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TypeBindings {
+    pub vars: BTreeMap<char, (PrimitiveType, NodeInputReference)>,
+}
+
+impl TypeBindings {
+    // Infers the variables in the declared type of `input` from the value connected to it.
+    pub fn unify(
+        &mut self,
+        declared: &ValueType,
+        actual: &ValueType,
+        input: &NodeInputReference,
+    ) -> Result<(), TypeError> {
+        match (declared.output, actual.output) {
+            (PrimitiveType::Var(var), actual) => self.bind(var, actual, input)?,
+            (PrimitiveType::Vector(n, ScalarType::Var(var)), PrimitiveType::Vector(m, scalar))
+                if n == m =>
+            {
+                self.bind(var, scalar.primitive(), input)?
+            }
+            _ => {}
+        }
+        for (name, declared_arg) in &declared.inputs {
+            if let Some(actual_arg) = actual.inputs.get(name) {
+                self.unify(declared_arg, actual_arg, input)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Two inputs that disagree on a variable settle on whichever of their types the other one
+    // casts into without losing anything, so a `[3]` and an `f32` make an `f32`.
+    fn bind(
+        &mut self,
+        var: char,
+        prim: PrimitiveType,
+        input: &NodeInputReference,
+    ) -> Result<(), TypeError> {
+        let Some((bound, bound_by)) = self.vars.get(&var) else {
+            self.vars.insert(var, (prim, input.clone()));
+            return Ok(());
+        };
+        let bound = *bound;
+        let exact = |from, to| match implicit_cast(from, to) {
+            Ok(None) => true,
+            Ok(Some(Cast { kind, .. })) => kind != CastKind::Lossy,
+            Err(_) => false,
+        };
+        if exact(prim, bound) {
+            Ok(())
+        } else if exact(bound, prim) {
+            self.vars.insert(var, (prim, input.clone()));
+            Ok(())
+        } else {
+            Err(TypeError::new(
+                TypeErrorKind::UnresolvedTypeVariable,
+                format!(
+                    "Inputs disagree on {}: {} and {} don't have a common type",
+                    var, bound, prim
+                ),
+            )
+            .with_related(
+                ErrorLocation::Input(bound_by.clone()),
+                format!("{} is {} here", var, bound),
+            )
+            .with_related(
+                ErrorLocation::Input(input.clone()),
+                format!("and {} here", prim),
+            ))
+        }
+    }
+
+    // Variables stand for a whole primitive, so bounds aren't kept: nothing says a node keeps
+    // its values in range.
+    fn lookup(&self, var: char) -> Result<PrimitiveType, TypeError> {
+        match self.vars.get(&var) {
+            Some((PrimitiveType::U32(_), _)) => Ok(PrimitiveType::U32(U32Boundedness::Unbounded)),
+            Some((prim, _)) => Ok(*prim),
+            None => Err(TypeError::new(
+                TypeErrorKind::UnresolvedTypeVariable,
+                format!("Can't tell what {} is: no connected input decides it", var),
+            )),
+        }
+    }
+
+    fn substitute(&self, typ: &ValueType) -> Result<ValueType, TypeError> {
+        Ok(ValueType {
+            inputs: typ
+                .inputs
+                .iter()
+                .map(|(name, t)| Ok((name.clone(), Box::new(self.substitute(t)?))))
+                .collect::<Result<_, TypeError>>()?,
+            output: match typ.output {
+                PrimitiveType::Var(var) => self.lookup(var)?,
+                PrimitiveType::Vector(n, ScalarType::Var(var)) => {
+                    let prim = self.lookup(var)?;
+                    let scalar = ScalarType::from_primitive(prim).ok_or_else(|| {
+                        TypeError::new(
+                            TypeErrorKind::UnresolvedTypeVariable,
+                            format!("{} is {}, which can't be a vector entry", var, prim),
+                        )
+                    })?;
+                    PrimitiveType::Vector(n, scalar)
+                }
+                prim => prim,
+            },
+        })
+    }
+
+    // The node type with every variable filled in.
+    pub fn instantiate(&self, typ: &DeclaredNodeType) -> Result<Rc<DeclaredNodeType>, TypeError> {
+        let substitute = |t: &MaybeValueType| match t {
+            Ok(t) => self.substitute(t).map(Ok),
+            Err(e) => Ok(Err(e.clone())),
+        };
+        Ok(Rc::new(NodeTypeInfo {
+            inputs: typ
+                .inputs
+                .iter()
+                .map(|i| {
+                    Ok(InputInfo {
+                        name: i.name.clone(),
+                        value_type: substitute(&i.value_type)?,
+                    })
+                })
+                .collect::<Result<_, TypeError>>()?,
+            outputs: typ
+                .outputs
+                .iter()
+                .map(|o| {
+                    Ok(OutputInfo {
+                        name: o.name.clone(),
+                        value_type: substitute(&o.value_type)?,
+                    })
+                })
+                .collect::<Result<_, TypeError>>()?,
+            annotation: typ.annotation.clone(),
+        }))
+    }
+}
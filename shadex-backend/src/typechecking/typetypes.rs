@@ -1,6 +1,6 @@
-use std::{collections::HashMap, fmt::Display, rc::Rc};
+use std::{collections::HashMap, fmt::Display};
 
-use crate::nodegraph::{FallibleNodeTypeRc, NodeAnnotation, NodeTypeInfo};
+use crate::nodegraph::{FallibleNodeTypeRc, NodeAnnotation, NodeInputReference, NodeRef, ValueRef};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum U32Boundedness {
@@ -8,11 +8,62 @@ pub enum U32Boundedness {
     Bounded(u32),
 }
 
+// What vectors are made of.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ScalarType {
+    F32,
+    I32,
+    U32,
+    Bool,
+    // As in `vec3<T>`.
+    Var(char),
+}
+
+impl ScalarType {
+    pub fn primitive(self) -> PrimitiveType {
+        match self {
+            ScalarType::F32 => PrimitiveType::F32,
+            ScalarType::I32 => PrimitiveType::I32,
+            ScalarType::U32 => PrimitiveType::U32(U32Boundedness::Unbounded),
+            ScalarType::Bool => PrimitiveType::Bool,
+            ScalarType::Var(var) => PrimitiveType::Var(var),
+        }
+    }
+
+    // What a primitive is as a vector entry. Vectors don't keep bounds.
+    pub fn from_primitive(prim: PrimitiveType) -> Option<ScalarType> {
+        match prim {
+            PrimitiveType::F32 => Some(ScalarType::F32),
+            PrimitiveType::I32 => Some(ScalarType::I32),
+            PrimitiveType::U32(_) => Some(ScalarType::U32),
+            PrimitiveType::Bool => Some(ScalarType::Bool),
+            PrimitiveType::Var(var) => Some(ScalarType::Var(var)),
+            PrimitiveType::U8 | PrimitiveType::Vector(..) | PrimitiveType::Matrix(..) => None,
+        }
+    }
+}
+
+impl Display for ScalarType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.primitive())
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum PrimitiveType {
     F32,
     I32,
     U32(U32Boundedness),
+    Bool,
+    // Kept in a u32 on the GPU, since WGSL has nothing smaller.
+    U8,
+    // `vecN<T>`, with N from 2 to 4.
+    Vector(u8, ScalarType),
+    // `matCxR<f32>`: C columns of R rows each, both from 2 to 4.
+    Matrix(u8, u8),
+    // A type variable of a polymorphic node type, like the `T` in `a @ T => val @ T`. Only ever
+    // in declared types; the typechecker fills it in per node.
+    Var(char),
 }
 
 impl Display for PrimitiveType {
@@ -24,6 +75,11 @@ impl Display for PrimitiveType {
                 U32Boundedness::Unbounded => write!(f, "u32"),
                 U32Boundedness::Bounded(bd) => write!(f, "[{}]", *bd),
             },
+            PrimitiveType::Bool => write!(f, "bool"),
+            PrimitiveType::U8 => write!(f, "u8"),
+            PrimitiveType::Vector(n, scalar) => write!(f, "vec{}<{}>", n, scalar),
+            PrimitiveType::Matrix(cols, rows) => write!(f, "mat{}x{}<f32>", cols, rows),
+            PrimitiveType::Var(var) => write!(f, "{}", var),
         }
     }
 }
@@ -74,9 +130,114 @@ impl AccessibleFallibleType for FallibleNodeTypeRc {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TypeErrorKind {
+    // A value's primitive can't be used as the one its input asks for.
+    PrimitiveMismatch,
+    // Two places disagree on the type of an argument.
+    ArgumentConflict,
+    // A node's type, or the type of one of its ports, isn't complete.
+    IncompleteNodeType,
+    // Something the graph reads from isn't there.
+    MissingNode,
+    // A type spec didn't parse.
+    SpecParse,
+    // The graph isn't put together right, like a cycle or a port count that's off.
+    MalformedGraph,
+    // A type variable couldn't be inferred, or the inputs disagree on it.
+    UnresolvedTypeVariable,
+    Other,
+}
+
+// The port (or whole node) an error is about.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ErrorLocation {
+    Input(NodeInputReference),
+    Output(ValueRef),
+    Node(NodeRef),
+}
+
+impl ErrorLocation {
+    pub fn node(&self) -> NodeRef {
+        match self {
+            ErrorLocation::Input(inp) => inp.source_node,
+            ErrorLocation::Output(val) => val.node,
+            ErrorLocation::Node(node) => *node,
+        }
+    }
+}
+
+// Somewhere else that has to do with an error, like the other side of a conflict.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RelatedNote {
+    pub location: ErrorLocation,
+    pub message: String,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct TypeError {
+    pub kind: TypeErrorKind,
     pub message: String,
+    pub location: Option<ErrorLocation>,
+    // Set when the error was only passed along from upstream. Its first related note is then where
+    // it started, if that was known.
+    pub propagated: bool,
+    pub related: Vec<RelatedNote>,
+}
+
+impl TypeError {
+    pub fn new(kind: TypeErrorKind, message: impl Into<String>) -> TypeError {
+        TypeError {
+            kind,
+            message: message.into(),
+            location: None,
+            propagated: false,
+            related: Vec::new(),
+        }
+    }
+
+    pub fn at(mut self, location: ErrorLocation) -> TypeError {
+        self.location = Some(location);
+        self
+    }
+
+    // Places the error somewhere, unless it already knows where it's from.
+    pub fn or_at(self, location: ErrorLocation) -> TypeError {
+        match self.location {
+            Some(_) => self,
+            None => self.at(location),
+        }
+    }
+
+    pub fn with_related(
+        mut self,
+        location: ErrorLocation,
+        message: impl Into<String>,
+    ) -> TypeError {
+        self.related.push(RelatedNote {
+            location,
+            message: message.into(),
+        });
+        self
+    }
+
+    // The same error, as seen from `location` downstream of where it happened.
+    pub fn propagate(&self, location: ErrorLocation) -> TypeError {
+        let mut err = self.clone();
+        if !err.propagated
+            && let Some(origin) = err.location.take()
+        {
+            err.related.insert(
+                0,
+                RelatedNote {
+                    location: origin,
+                    message: "the error starts here".to_string(),
+                },
+            );
+        }
+        err.propagated = true;
+        err.at(location)
+    }
 }
 
 impl Display for TypeError {
@@ -0,0 +1,83 @@
+use crate::{
+    execution::ExecutionInformation,
+    nodegraph::NodeTypeInfo,
+    typechecking::typetypes::{
+        MaybeValueType, PrimitiveType, TypeError, TypeErrorKind, U32Boundedness, ValueType,
+    },
+};
+
+// The two ways a vector gets around: as a `vecN<T>`, or as a function of one `[N]` argument
+// (`comp: [N] -> T`). Pack goes from the second to the first, Unpack back.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VectorConversion {
+    // The argument the entries are read or written through, usually `comp`.
+    pub arg: String,
+    pub size: u8,
+    pub entry: PrimitiveType,
+}
+
+fn signature_error(msg: String) -> TypeError {
+    TypeError::new(TypeErrorKind::IncompleteNodeType, msg)
+}
+
+// Checks that `function` and `vector` are the two sides of the same vector.
+pub fn vector_conversion(
+    function: &ValueType,
+    vector: &ValueType,
+) -> Result<VectorConversion, TypeError> {
+    let PrimitiveType::Vector(size, scalar) = vector.output else {
+        return Err(signature_error(format!(
+            "Expected a vector, not {}",
+            vector.output
+        )));
+    };
+    if !vector.inputs.is_empty() {
+        return Err(signature_error(
+            "A packed vector can't take arguments".to_string(),
+        ));
+    }
+    let mut args = function.inputs.iter();
+    let (Some((arg, arg_type)), None) = (args.next(), args.next()) else {
+        return Err(signature_error(format!(
+            "Expected a function of a single [{}] argument",
+            size
+        )));
+    };
+    if arg_type.output != PrimitiveType::U32(U32Boundedness::Bounded(size as u32))
+        || !arg_type.inputs.is_empty()
+    {
+        return Err(signature_error(format!(
+            "Argument {} is {}, but the vector has {} entries",
+            arg, arg_type, size
+        )));
+    }
+    if function.output != scalar.primitive() {
+        return Err(signature_error(format!(
+            "Entries are {}, but the vector holds {}",
+            function.output, scalar
+        )));
+    }
+    Ok(VectorConversion {
+        arg: arg.clone(),
+        size,
+        entry: function.output,
+    })
+}
+
+// The conversion a Pack or Unpack node does, going by its (instantiated) signature.
+pub fn node_conversion(
+    typ: &NodeTypeInfo<MaybeValueType, ExecutionInformation>,
+) -> Result<VectorConversion, TypeError> {
+    let (Some(input), Some(output)) = (typ.inputs.first(), typ.outputs.first()) else {
+        return Err(signature_error(
+            "Expected one input and one output".to_string(),
+        ));
+    };
+    let (input, output) = (input.value_type.as_ref(), output.value_type.as_ref());
+    let (input, output) = (input.map_err(Clone::clone)?, output.map_err(Clone::clone)?);
+    match typ.annotation {
+        ExecutionInformation::Pack => vector_conversion(input, output),
+        ExecutionInformation::Unpack => vector_conversion(output, input),
+        _ => Err(signature_error("Not a vector conversion".to_string())),
+    }
+}
@@ -0,0 +1,131 @@
+use shadex_backend::{
+    execution::ExecutionInformation,
+    nodegraph::{FallibleNodeTypeRc, NodeGraph},
+    parsing::{SimpleTypeWorld, diagnostics::DiagnosticKind},
+};
+
+mod common;
+use common::{build, world_with};
+
+fn types() -> SimpleTypeWorld<FallibleNodeTypeRc> {
+    world_with(
+        "Pick = i @ u32[4] => val @ f32 with wgsl \"0.\"
+Shift = v @ i32 => val @ i32 with wgsl \"{v}\"
+Texel = i @ u32 => val @ f32 with wgsl \"0.\"",
+    )
+}
+
+// The constants literals turned into, as (extra data, type).
+fn literals(graph: &NodeGraph<FallibleNodeTypeRc>) -> Vec<(String, String)> {
+    let mut found: Vec<(String, String)> = graph
+        .iter_nodes()
+        .filter_map(|(_, node)| {
+            let typ = node.annotation.as_ref().ok()?;
+            if !matches!(typ.annotation, ExecutionInformation::ConstantFromData) {
+                return None;
+            }
+            let out = typ.outputs[0].value_type.as_ref().unwrap().to_string();
+            Some((node.extra_data.clone().unwrap(), out))
+        })
+        .collect();
+    found.sort();
+    found
+}
+
+#[test]
+fn named_arguments_fill_their_slots() {
+    let graph = build(
+        &types(),
+        "C = Constant: 1()\nD = Constant: 2()\nS = AddF(b: D.val, a: C.val)",
+    )
+    .unwrap();
+    let (_, sum) = graph
+        .iter_nodes()
+        .find(|(_, n)| n.inputs.len() == 2)
+        .unwrap();
+    let source_data = |ind: usize| {
+        let src = sum.inputs[ind].unwrap().node;
+        graph.get_node(src).unwrap().extra_data.clone().unwrap()
+    };
+    assert_eq!(
+        (source_data(0), source_data(1)),
+        ("1".to_string(), "2".to_string())
+    );
+
+    // Positional ones go first, and named ones fill in the rest.
+    assert!(
+        build(
+            &types(),
+            "C = Constant: 1()\nOut(Vec3(C.val, z: C.val, y: C.val).val)"
+        )
+        .is_ok()
+    );
+}
+
+#[test]
+fn named_arguments_have_to_make_sense() {
+    let kind = |src: &str| build(&types(), src).unwrap_err().kind;
+    assert_eq!(
+        kind("S = AddF(a: 1, c: 2)"),
+        DiagnosticKind::UnknownArgumentName("c".to_string())
+    );
+    assert_eq!(
+        kind("S = AddF(1, a: 2)"),
+        DiagnosticKind::DuplicateArgument("a".to_string())
+    );
+    assert_eq!(
+        kind("S = AddF(a: 1, 2)"),
+        DiagnosticKind::PositionalAfterNamed
+    );
+    assert_eq!(
+        kind("S = AddF(b: 1)"),
+        DiagnosticKind::MissingArgument("a".to_string())
+    );
+    assert_eq!(
+        kind("S = AddF(1)"),
+        DiagnosticKind::ArityMismatch {
+            expected: 2,
+            found: 1
+        }
+    );
+}
+
+#[test]
+fn literals_take_the_type_of_their_input() {
+    let graph = build(
+        &types(),
+        "A = AddF(1, 2.5)\nP = Pick(3)\nS = Shift(-2)\nI = Texel(i: 7)",
+    )
+    .unwrap();
+    assert_eq!(
+        literals(&graph),
+        [
+            ("-2".to_string(), "i32".to_string()),
+            ("1".to_string(), "f32".to_string()),
+            ("2.5".to_string(), "f32".to_string()),
+            ("3".to_string(), "[4]".to_string()),
+            ("7".to_string(), "u32".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn literals_that_do_not_fit_are_rejected() {
+    let kind = |src: &str| build(&types(), src).unwrap_err().kind;
+    assert_eq!(
+        kind("P = Pick(4)"),
+        DiagnosticKind::LiteralMismatch("[4]".to_string())
+    );
+    assert_eq!(
+        kind("I = Texel(-1)"),
+        DiagnosticKind::LiteralMismatch("u32".to_string())
+    );
+    assert_eq!(
+        kind("I = Texel(0.5)"),
+        DiagnosticKind::LiteralMismatch("u32".to_string())
+    );
+    assert_eq!(
+        kind("S = Shift(1.5)"),
+        DiagnosticKind::LiteralMismatch("i32".to_string())
+    );
+}
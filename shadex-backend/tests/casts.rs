@@ -0,0 +1,92 @@
+use shadex_backend::{
+    execution::Executor,
+    nodegraph::{FallibleNodeTypeRc, NodeGraph},
+    parsing::SimpleTypeWorld,
+    typechecking::{
+        NodeGraphFormalTypeAnalysis,
+        casts::{CastKind, implicit_cast},
+        typetypes::{PrimitiveType, U32Boundedness},
+    },
+};
+
+mod common;
+use common::{add, input, world_with};
+
+const F32: PrimitiveType = PrimitiveType::F32;
+const I32: PrimitiveType = PrimitiveType::I32;
+const U32: PrimitiveType = PrimitiveType::U32(U32Boundedness::Unbounded);
+
+fn bounded(n: u32) -> PrimitiveType {
+    PrimitiveType::U32(U32Boundedness::Bounded(n))
+}
+
+fn kind(from: PrimitiveType, to: PrimitiveType) -> Option<CastKind> {
+    implicit_cast(from, to).unwrap().map(|cast| cast.kind)
+}
+
+#[test]
+fn cast_lattice() {
+    assert_eq!(kind(F32, F32), None);
+    assert_eq!(kind(bounded(3), bounded(3)), None);
+
+    assert_eq!(kind(bounded(3), U32), Some(CastKind::BoundedWidening));
+    assert_eq!(
+        kind(bounded(3), bounded(4)),
+        Some(CastKind::BoundedWidening)
+    );
+    assert_eq!(kind(bounded(1024), F32), Some(CastKind::Lossless));
+    assert_eq!(kind(bounded(1024), I32), Some(CastKind::Lossless));
+    assert_eq!(kind(bounded(1 << 25), F32), Some(CastKind::Lossy));
+    assert_eq!(kind(U32, F32), Some(CastKind::Lossy));
+    assert_eq!(kind(I32, F32), Some(CastKind::Lossy));
+    assert_eq!(kind(F32, I32), Some(CastKind::Lossy));
+    assert_eq!(kind(F32, U32), Some(CastKind::Lossy));
+
+    // Nothing is narrowed into a bound implicitly.
+    assert!(implicit_cast(bounded(4), bounded(3)).is_err());
+    assert!(implicit_cast(U32, bounded(3)).is_err());
+    assert!(implicit_cast(F32, bounded(3)).is_err());
+    assert!(implicit_cast(I32, bounded(3)).is_err());
+}
+
+fn world() -> SimpleTypeWorld<FallibleNodeTypeRc> {
+    world_with("Count = => val @ u32 with builtin Constant")
+}
+
+#[test]
+fn casts_are_recorded_and_emitted() {
+    let world = world();
+    let mut graph = NodeGraph::new();
+    let count = add(&mut graph, &world, "Count", vec![]);
+    let one = add(&mut graph, &world, "Constant", vec![]);
+    let sum = add(&mut graph, &world, "AddF", vec![Some(count), Some(one)]);
+    add(&mut graph, &world, "Out", vec![Some(sum)]);
+
+    let types = NodeGraphFormalTypeAnalysis::analyze(&graph);
+    let notes = types.input_type_notes[&input(sum, 0)].as_ref().unwrap();
+    let cast = notes.cast().unwrap();
+    assert_eq!((cast.from, cast.to, cast.kind), (U32, F32, CastKind::Lossy));
+
+    let prog = Executor::default().run(&graph, &types).ok().unwrap();
+    assert!(prog.text.contains("-> u32 { return 1u; }"));
+    assert!(
+        prog.text
+            .contains("return f32(id0(x,y,component)) + id1(x,y,component);")
+    );
+}
+
+#[test]
+fn output_is_converted_to_f32() {
+    let world = world();
+    let mut graph = NodeGraph::new();
+    let count = add(&mut graph, &world, "Count", vec![]);
+    add(&mut graph, &world, "Out", vec![Some(count)]);
+
+    let types = NodeGraphFormalTypeAnalysis::analyze(&graph);
+    let prog = Executor::default().run(&graph, &types).ok().unwrap();
+    assert_eq!(prog.name, "id1");
+    assert!(
+        prog.text
+            .contains("-> f32 { return f32(id0(x,y,component)); }")
+    );
+}
@@ -0,0 +1,71 @@
+// Fixtures shared by the integration tests. Each test binary only uses some of them.
+#![allow(dead_code)]
+
+use shadex_backend::{
+    nodegraph::{FallibleNodeTypeRc, Node, NodeGraph, NodeInputReference, NodeRef, ValueRef},
+    parsing::{
+        SimpleTypeWorld, construct_node_graph, diagnostics::Diagnostic, parse_whole_input,
+        type_parsing::parse_type_world,
+    },
+};
+
+pub const TYPELAND: &str = include_str!("../../../examples/typeland.shadextypes");
+
+// The example type world.
+pub fn world() -> SimpleTypeWorld<FallibleNodeTypeRc> {
+    parse_type_world(TYPELAND).unwrap()
+}
+
+// The example type world, with `extra` declared on top of it.
+pub fn world_with(extra: &str) -> SimpleTypeWorld<FallibleNodeTypeRc> {
+    parse_type_world(&format!("{}\n{}", TYPELAND, extra)).unwrap()
+}
+
+pub fn build(
+    world: &SimpleTypeWorld<FallibleNodeTypeRc>,
+    src: &str,
+) -> Result<NodeGraph<FallibleNodeTypeRc>, Diagnostic> {
+    parse_whole_input(src.as_bytes()).and_then(|exprs| construct_node_graph(world, exprs))
+}
+
+// A node of the world's `name` type. Its extra data is there for constants, which need some.
+pub fn node(
+    world: &SimpleTypeWorld<FallibleNodeTypeRc>,
+    name: &str,
+    inputs: Vec<Option<ValueRef>>,
+) -> Node<FallibleNodeTypeRc> {
+    Node {
+        annotation: world.node_types[name].clone(),
+        inputs,
+        extra_data: Some("1".to_string()),
+    }
+}
+
+// Adds a node reading the first output of each of `inputs`.
+pub fn add(
+    graph: &mut NodeGraph<FallibleNodeTypeRc>,
+    world: &SimpleTypeWorld<FallibleNodeTypeRc>,
+    name: &str,
+    inputs: Vec<Option<NodeRef>>,
+) -> NodeRef {
+    graph.add_node(node(
+        world,
+        name,
+        inputs.into_iter().map(|node| node.map(val)).collect(),
+    ))
+}
+
+pub fn val(node: NodeRef) -> ValueRef {
+    val_at(node, 0)
+}
+
+pub fn val_at(node: NodeRef, output_index: usize) -> ValueRef {
+    ValueRef { node, output_index }
+}
+
+pub fn input(node: NodeRef, input_ind: usize) -> NodeInputReference {
+    NodeInputReference {
+        source_node: node,
+        input_ind,
+    }
+}
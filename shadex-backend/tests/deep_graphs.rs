@@ -0,0 +1,72 @@
+use shadex_backend::{
+    execution::Executor,
+    nodegraph::NodeGraph,
+    typechecking::{
+        NodeGraphFormalTypeAnalysis, explain::ArgumentOrigin, typetypes::PrimitiveType,
+    },
+};
+
+mod common;
+use common::{add, val, world, world_with};
+
+const CHAIN_LENGTH: usize = 100_000;
+
+// Runs on a stack far smaller than the default, the way wasm would, so anything that recurses
+// along the chain falls over.
+fn with_small_stack(f: impl FnOnce() + Send + 'static) {
+    std::thread::Builder::new()
+        .stack_size(256 * 1024)
+        .spawn(f)
+        .unwrap()
+        .join()
+        .unwrap();
+}
+
+#[test]
+fn long_add_chain() {
+    with_small_stack(|| {
+        let world = world();
+        let mut graph = NodeGraph::new();
+        let mut last = add(&mut graph, &world, "Constant", vec![]);
+        for _ in 0..CHAIN_LENGTH {
+            last = add(&mut graph, &world, "AddF", vec![Some(last), Some(last)]);
+        }
+        let end = val(last);
+        add(&mut graph, &world, "Out", vec![Some(last)]);
+
+        assert_eq!(graph.topological_order().unwrap().len(), CHAIN_LENGTH + 2);
+
+        let types = NodeGraphFormalTypeAnalysis::analyze(&graph);
+        assert_eq!(types.output_type_notes.len(), CHAIN_LENGTH + 1);
+        assert_eq!(types.input_type_notes.len(), 2 * CHAIN_LENGTH + 1);
+        let notes = types.output_type_notes[&end].as_ref().unwrap();
+        assert_eq!(notes.formal_type.output, PrimitiveType::F32);
+        assert!(notes.formal_type.inputs.is_empty());
+
+        let prog = Executor::default().run(&graph, &types).ok().unwrap();
+        assert_eq!(prog.text.matches("fn ").count(), CHAIN_LENGTH + 1);
+    });
+}
+
+#[test]
+fn long_chain_explains_its_argument() {
+    with_small_stack(|| {
+        let world = world_with("X = x @ f32 => val @ f32 with builtin Attr");
+        let mut graph = NodeGraph::new();
+        let x = add(&mut graph, &world, "X", vec![None]);
+        let mut last = x;
+        for _ in 0..CHAIN_LENGTH {
+            last = add(&mut graph, &world, "AddF", vec![Some(last), Some(last)]);
+        }
+        let (x, last) = (val(x), val(last));
+
+        let types = NodeGraphFormalTypeAnalysis::analyze(&graph);
+        let explained = types.explain(&graph, last).unwrap();
+        assert_eq!(explained.len(), 1);
+        let paths = explained[0].paths();
+        assert_eq!(paths.len(), 1);
+        assert_eq!(paths[0].0, ArgumentOrigin::Attr(x));
+        assert_eq!(paths[0].1.len(), CHAIN_LENGTH);
+        assert_eq!(paths[0].1.last(), Some(&last));
+    });
+}
@@ -0,0 +1,70 @@
+use shadex_backend::parsing::diagnostics::DiagnosticKind;
+
+mod common;
+use common::{build, world};
+
+#[test]
+fn each_use_builds_its_own_copy() {
+    let src = "def Double(a @ f32) => out @ f32 {\n    out = AddF(a, a).val\n}\n\
+               X = Double(Constant: 1().val).out\nY = Double(a: X).out\nOut(Y)";
+    let graph = build(&world(), src).unwrap();
+    // Constant, two AddFs and Out. Nothing is left over from checking the def.
+    assert_eq!(graph.iter_nodes().count(), 4);
+}
+
+#[test]
+fn definitions_can_have_several_outputs() {
+    let src = "def Split(a @ f32) => lo @ f32; hi @ f32 { lo = a; hi = AddF(a, 1).val }\n\
+               S = Split(2)\nOut(Vec3(S.lo, S.hi, S.1).val)";
+    assert!(build(&world(), src).is_ok());
+}
+
+#[test]
+fn unassigned_outputs_are_reported() {
+    let err = build(&world(), "def F(a @ f32) => out @ f32 { other = a }").unwrap_err();
+    assert_eq!(
+        err.kind,
+        DiagnosticKind::MissingDefinitionOutput("out".to_string())
+    );
+}
+
+#[test]
+fn body_is_checked_against_the_signature() {
+    let err = build(&world(), "def F(a @ f32) => out @ u32 { out = a }").unwrap_err();
+    assert!(matches!(err.kind, DiagnosticKind::SignatureMismatch { .. }));
+
+    // Depending on an argument the signature doesn't declare is a mismatch too.
+    let err = build(&world(), "def F() => out @ f32 { out = Vec3(1, 2, 3).val }").unwrap_err();
+    assert!(matches!(err.kind, DiagnosticKind::SignatureMismatch { .. }));
+
+    // Being constant with respect to a declared argument is fine.
+    assert!(
+        build(
+            &world(),
+            "def F() => out @ comp: [3] -> f32 { out = Constant: 1().val }"
+        )
+        .is_ok()
+    );
+}
+
+#[test]
+fn definitions_only_see_what_came_before() {
+    let err = build(&world(), "def F() => out @ f32 { out = F().out }").unwrap_err();
+    assert_eq!(err.kind, DiagnosticKind::UnknownType("F".to_string()));
+
+    let err = build(
+        &world(),
+        "X = Constant: 1().val\ndef F() => out @ f32 { out = X }",
+    )
+    .unwrap_err();
+    assert_eq!(err.kind, DiagnosticKind::UnboundName("X".to_string()));
+}
+
+#[test]
+fn definitions_cannot_shadow_node_types() {
+    let err = build(&world(), "def AddF() => out @ f32 { out = 1 }").unwrap_err();
+    assert_eq!(
+        err.kind,
+        DiagnosticKind::DuplicateNodeType("AddF".to_string())
+    );
+}
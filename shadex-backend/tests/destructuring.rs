@@ -0,0 +1,77 @@
+use shadex_backend::{
+    nodegraph::FallibleNodeTypeRc,
+    parsing::{SimpleTypeWorld, diagnostics::DiagnosticKind},
+};
+
+mod common;
+use common::{build, world_with};
+
+fn types() -> SimpleTypeWorld<FallibleNodeTypeRc> {
+    world_with("Split = v @ f32 => lo @ f32; hi @ f32; mid @ f32 with wgsl \"{v}\"")
+}
+
+#[test]
+fn outputs_are_bound_by_position() {
+    let graph = build(&types(), "(a, b, c) = Split(1)\nOut(Vec3(c, b, a).val)").unwrap();
+    // Constant, Split, Vec3 and Out.
+    assert_eq!(graph.iter_nodes().count(), 4);
+}
+
+#[test]
+fn underscore_skips_an_output() {
+    assert!(
+        build(
+            &types(),
+            "(lo, _, mid,) = Split(1)\nOut(Vec3(lo, mid, lo).val)"
+        )
+        .is_ok()
+    );
+
+    let err = build(
+        &types(),
+        "(lo, _, mid) = Split(1)\nOut(Vec3(lo, _, mid).val)",
+    )
+    .unwrap_err();
+    assert_eq!(err.kind, DiagnosticKind::Syntax);
+}
+
+#[test]
+fn name_count_has_to_match() {
+    let err = build(&types(), "(a, b) = Split(1)").unwrap_err();
+    assert_eq!(
+        err.kind,
+        DiagnosticKind::DestructureMismatch {
+            outputs: 3,
+            names: 2
+        }
+    );
+}
+
+#[test]
+fn only_nodes_can_be_destructured() {
+    let err = build(&types(), "(a, b) = Split(1).lo").unwrap_err();
+    assert_eq!(err.kind, DiagnosticKind::NotDestructurable);
+}
+
+#[test]
+fn definitions_can_be_destructured() {
+    let src = "def Twice(a @ f32) => x @ f32; y @ f32 { x = a; y = AddF(a, a).val }\n\
+               (p, q) = Twice(2)\nOut(Vec3(p, q, p).val)";
+    assert!(build(&types(), src).is_ok());
+}
+
+#[test]
+fn identical_constructions_stay_separate() {
+    let graph = build(&types(), "X = AddF(NULL, NULL)\nY = AddF(NULL, NULL)").unwrap();
+    assert_eq!(graph.iter_nodes().count(), 2);
+
+    let src = "X = Vec3(Split(1).lo, Split(1).hi, Split(1).mid).val\nOut(X)";
+    let graph = build(&types(), src).unwrap();
+    // Three Splits, each with a constant for its argument.
+    assert_eq!(graph.iter_nodes().count(), 8);
+
+    // A node used through its name is the same node every time.
+    let src = "(lo, hi, mid) = Split(1)\nS = Split(1)\nOut(Vec3(lo, S.hi, S.lo).val)";
+    let graph = build(&types(), src).unwrap();
+    assert_eq!(graph.iter_nodes().count(), 6);
+}
@@ -0,0 +1,91 @@
+use shadex_backend::parsing::{
+    construct_node_graph,
+    diagnostics::{Diagnostic, DiagnosticKind, SourceLocation, SourceSpan},
+    parse_whole_input,
+};
+
+mod common;
+use common::world;
+
+fn construct_err(src: &str) -> Diagnostic {
+    let exprs = parse_whole_input(src.as_bytes()).unwrap();
+    construct_node_graph(&world(), exprs).unwrap_err()
+}
+
+fn at(src: &str, diag: &Diagnostic) -> (SourceLocation, String) {
+    let text = src[diag.span.start..diag.span.end].to_string();
+    (diag.span.locate(src), text)
+}
+
+#[test]
+fn construction_errors_say_what_and_where() {
+    let src = "C = Constant: 1()\nS = AddF(C.val, Nope.val)";
+    let diag = construct_err(src);
+    assert_eq!(diag.kind, DiagnosticKind::UnboundName("Nope".to_string()));
+    assert_eq!(
+        at(src, &diag),
+        (
+            SourceLocation {
+                line: 2,
+                column: 17
+            },
+            "Nope".to_string()
+        )
+    );
+
+    let src = "C = Konstant: 1()";
+    let diag = construct_err(src);
+    assert_eq!(
+        diag.kind,
+        DiagnosticKind::UnknownType("Konstant".to_string())
+    );
+    assert_eq!(diag.span.locate(src), SourceLocation { line: 1, column: 5 });
+
+    let diag = construct_err("C = Constant: 1()\nS = AddF(C.value, C.val)");
+    assert_eq!(
+        diag.kind,
+        DiagnosticKind::BadOutputName("value".to_string())
+    );
+
+    // A node with no output picked isn't a value.
+    let diag = construct_err("C = Constant: 1()\nS = AddF(C, C.val)");
+    assert_eq!(diag.kind, DiagnosticKind::NonValueArgument);
+
+    let diag = construct_err("C = Constant: 1()\nS = AddF(C.val)");
+    assert_eq!(
+        diag.kind,
+        DiagnosticKind::ArityMismatch {
+            expected: 2,
+            found: 1
+        }
+    );
+}
+
+#[test]
+fn syntax_errors_point_at_the_rest_of_the_line() {
+    let src = "C = Constant: 1()\nS = AddF(C.val,, C.val)\n";
+    let diag = parse_whole_input(src.as_bytes()).unwrap_err();
+    assert_eq!(diag.kind, DiagnosticKind::Syntax);
+    assert_eq!(diag.span.locate(src).line, 2);
+    assert_eq!(diag.span.locate_end(src).line, 2);
+}
+
+#[test]
+fn rendering_underlines_the_span() {
+    let src = "C = Constant: 1()\nS = AddF(C.val, Nope.val)";
+    let diag = Diagnostic::new(
+        DiagnosticKind::UnboundName("Nope".to_string()),
+        SourceSpan::new(34, 38),
+    );
+    assert_eq!(
+        diag.render(src),
+        "error: `Nope` is not defined\n  --> 2:17\n  |\n2 | S = AddF(C.val, Nope.val)\n  |                 ^^^^"
+    );
+
+    // Spans running past their line only get underlined to its end, and empty ones get one caret.
+    let src = "ab\ncd";
+    let long = Diagnostic::new(DiagnosticKind::Syntax, SourceSpan::new(1, 5));
+    assert!(long.render(src).ends_with("1 | ab\n  |  ^"));
+    let empty = Diagnostic::new(DiagnosticKind::Syntax, SourceSpan::new(0, 0));
+    assert!(empty.render(src).ends_with("1 | ab\n  | ^"));
+}
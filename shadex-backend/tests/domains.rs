@@ -0,0 +1,102 @@
+use shadex_backend::{
+    execution::Executor,
+    nodegraph::{FallibleNodeTypeRc, NodeGraph, NodeRef},
+    parsing::SimpleTypeWorld,
+    typechecking::{
+        NodeGraphFormalTypeAnalysis,
+        domains::{DomainGuard, OutOfDomainPolicy, fit_argument},
+        typetypes::{PrimitiveType, U32Boundedness, ValueType},
+    },
+};
+
+mod common;
+use common::{add, input, world_with};
+
+fn bounded(n: u32) -> ValueType {
+    ValueType::primitive(PrimitiveType::U32(U32Boundedness::Bounded(n)))
+}
+
+fn unbounded() -> ValueType {
+    ValueType::primitive(PrimitiveType::U32(U32Boundedness::Unbounded))
+}
+
+fn fit(source: ValueType, wanted: ValueType) -> Option<u32> {
+    fit_argument("comp", &source, &wanted, OutOfDomainPolicy::Clamp)
+        .unwrap()
+        .map(|guard| guard.bound)
+}
+
+#[test]
+fn narrower_domains_fit_and_wider_ones_are_guarded() {
+    assert_eq!(fit(bounded(4), bounded(3)), None);
+    assert_eq!(fit(unbounded(), bounded(3)), None);
+    assert_eq!(fit(bounded(3), bounded(3)), None);
+    assert_eq!(fit(bounded(3), bounded(4)), Some(3));
+    assert_eq!(fit(bounded(3), unbounded()), Some(3));
+
+    let f32 = ValueType::primitive(PrimitiveType::F32);
+    assert!(fit_argument("comp", &bounded(3), &f32, OutOfDomainPolicy::Clamp).is_err());
+    assert!(fit_argument("comp", &bounded(3), &bounded(4), OutOfDomainPolicy::Error).is_err());
+}
+
+fn world() -> SimpleTypeWorld<FallibleNodeTypeRc> {
+    world_with("Rgba = col @ comp: u32[4] -> f32 => val @ comp: u32[4] -> f32 with wgsl \"{col}\"")
+}
+
+// An RGB colour read as RGBA, then shown.
+fn rgb_as_rgba() -> (NodeGraph<FallibleNodeTypeRc>, NodeRef) {
+    let world = world();
+    let mut graph = NodeGraph::new();
+    let c = add(&mut graph, &world, "Constant", vec![]);
+    let rgb = add(&mut graph, &world, "Vec3", vec![Some(c), Some(c), Some(c)]);
+    let rgba = add(&mut graph, &world, "Rgba", vec![Some(rgb)]);
+    add(&mut graph, &world, "Out", vec![Some(rgba)]);
+    (graph, rgba)
+}
+
+fn run(policy: OutOfDomainPolicy) -> (NodeGraphFormalTypeAnalysis, Option<String>) {
+    let (graph, rgba) = rgb_as_rgba();
+    let types = NodeGraphFormalTypeAnalysis::analyze_with_policy(&graph, policy);
+    let notes = &types.input_type_notes[&input(rgba, 0)];
+    if let Ok(notes) = notes {
+        assert_eq!(
+            notes.domain_guards(),
+            &[DomainGuard {
+                arg: "comp".to_string(),
+                bound: 3,
+                policy,
+            }]
+        );
+    }
+    let text = Executor::default()
+        .run(&graph, &types)
+        .ok()
+        .map(|prog| prog.text);
+    (types, text)
+}
+
+#[test]
+fn out_of_domain_reads_are_clamped() {
+    let (_, text) = run(OutOfDomainPolicy::Clamp);
+    assert!(
+        text.unwrap()
+            .contains("return id1(x,y,min(component, u32(2)));")
+    );
+}
+
+#[test]
+fn out_of_domain_reads_are_zeroed() {
+    let (_, text) = run(OutOfDomainPolicy::Zero);
+    assert!(
+        text.unwrap().contains(
+            "return select(f32(0), id1(x,y,min(component, u32(2))), component < u32(3));"
+        )
+    );
+}
+
+#[test]
+fn out_of_domain_reads_can_be_refused() {
+    let (types, text) = run(OutOfDomainPolicy::Error);
+    assert!(text.is_none());
+    assert!(types.input_type_notes.values().any(|notes| notes.is_err()));
+}
@@ -0,0 +1,144 @@
+use std::{collections::HashMap, rc::Rc};
+
+use shadex_backend::{
+    nodegraph::{FallibleNodeTypeRc, NodeGraph, NodeRef},
+    parsing::SimpleTypeWorld,
+    typechecking::{
+        NodeGraphFormalTypeAnalysis,
+        explain::{ArgumentExplanation, ArgumentOrigin, Derivation},
+    },
+};
+
+mod common;
+use common::{input, node, val, world_with};
+
+fn world() -> SimpleTypeWorld<FallibleNodeTypeRc> {
+    world_with(
+        "X = x @ f32 => val @ f32 with builtin Attr
+Y = y @ f32 => val @ f32 with builtin Attr",
+    )
+}
+
+struct Graph {
+    graph: NodeGraph<FallibleNodeTypeRc>,
+    names: HashMap<NodeRef, String>,
+}
+
+impl Graph {
+    fn add(
+        &mut self,
+        world: &SimpleTypeWorld<FallibleNodeTypeRc>,
+        name: &str,
+        inputs: Vec<Option<NodeRef>>,
+    ) -> NodeRef {
+        let node = self.graph.add_node(node(
+            world,
+            name,
+            inputs.into_iter().map(|node| node.map(val)).collect(),
+        ));
+        self.names
+            .insert(node, format!("{}#{}", name, self.names.len()));
+        node
+    }
+
+    fn describe(&self, explanation: &ArgumentExplanation) -> Vec<String> {
+        explanation.describe(|node| self.names[&node].clone())
+    }
+}
+
+fn new_graph() -> Graph {
+    Graph {
+        graph: NodeGraph::new(),
+        names: HashMap::new(),
+    }
+}
+
+#[test]
+fn arguments_are_traced_back_to_attrs() {
+    let world = world();
+    let mut g = new_graph();
+    let x = g.add(&world, "X", vec![None]);
+    let y = g.add(&world, "Y", vec![None]);
+    let s = g.add(&world, "AddF", vec![Some(x), Some(y)]);
+    let t = g.add(&world, "AddF", vec![Some(s), Some(x)]);
+    let types = NodeGraphFormalTypeAnalysis::analyze(&g.graph);
+
+    let explained = types.explain(&g.graph, val(t)).unwrap();
+    let args: Vec<&str> = explained.iter().map(|e| e.arg.as_str()).collect();
+    assert_eq!(args, ["x", "y"]);
+    assert_eq!(
+        g.describe(&explained[0]),
+        ["x comes from X#0 via AddF#2, AddF#3"]
+    );
+    assert_eq!(
+        g.describe(&explained[1]),
+        ["y comes from Y#1 via AddF#2, AddF#3"]
+    );
+
+    // `t` gets x both straight from the Attr and through `s`, which share one derivation.
+    let Derivation::Through(at, from) = &*explained[0].derivation else {
+        panic!("x should come through t");
+    };
+    assert_eq!(*at, val(t));
+    assert_eq!(from.len(), 2);
+    let Derivation::Through(_, through_s) = &*from[0] else {
+        panic!("x should come through s");
+    };
+    assert!(Rc::ptr_eq(&through_s[0], &from[1]));
+    assert_eq!(*from[1], Derivation::Origin(ArgumentOrigin::Attr(val(x))));
+
+    // The Attr's own input is where the attribute comes in unconnected.
+    let explained = types.explain_input(&g.graph, &input(x, 0)).unwrap();
+    assert_eq!(
+        explained[0].paths(),
+        [(ArgumentOrigin::FreeVariable(input(x, 0)), vec![])]
+    );
+}
+
+#[test]
+fn declared_outputs_free_inputs_and_constants() {
+    let world = world();
+    let mut g = new_graph();
+    let c = g.add(&world, "Constant", vec![]);
+    let v = g.add(&world, "Vec3", vec![Some(c), Some(c), Some(c)]);
+    let out = g.add(&world, "Out", vec![Some(v)]);
+    let free = g.add(&world, "AddF", vec![None, Some(c)]);
+    let types = NodeGraphFormalTypeAnalysis::analyze(&g.graph);
+
+    let explained = types.explain_input(&g.graph, &input(out, 0)).unwrap();
+    let lines: Vec<String> = explained.iter().flat_map(|e| g.describe(e)).collect();
+    assert_eq!(
+        lines,
+        [
+            "comp comes from the declared output of Vec3#1",
+            "x comes from input 0 of Out#2, which is constant in it",
+            "y comes from input 0 of Out#2, which is constant in it",
+        ]
+    );
+
+    let explained = types.explain(&g.graph, val(free)).unwrap();
+    assert_eq!(explained[0].arg, "a");
+    assert_eq!(
+        explained[0].paths(),
+        [(
+            ArgumentOrigin::FreeVariable(input(free, 0)),
+            vec![val(free)]
+        )]
+    );
+    assert_eq!(
+        g.describe(&explained[0]),
+        ["a comes from unconnected input 0 of AddF#3"]
+    );
+}
+
+#[test]
+fn broken_ports_have_nothing_to_explain() {
+    let world = world();
+    let mut g = new_graph();
+    let c = g.add(&world, "Constant", vec![]);
+    let i = g.add(&world, "Dot3", vec![Some(c), Some(c)]);
+    let types = NodeGraphFormalTypeAnalysis::analyze(&g.graph);
+
+    assert!(types.explain(&g.graph, val(i)).is_err());
+    assert!(types.explain(&g.graph, val(c)).unwrap().is_empty());
+}
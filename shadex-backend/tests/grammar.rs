@@ -0,0 +1,102 @@
+use shadex_backend::parsing::{
+    NodeExpression, SpannedExpression,
+    diagnostics::{Diagnostic, DiagnosticKind},
+    parse_whole_input,
+};
+
+fn parse(src: &str) -> Vec<SpannedExpression> {
+    match parse_whole_input(src.as_bytes()) {
+        Ok(exprs) => exprs,
+        Err(diag) => panic!("{}", diag.render(src)),
+    }
+}
+
+fn parse_err(src: &str) -> Diagnostic {
+    parse_whole_input(src.as_bytes()).expect_err("expected a syntax error")
+}
+
+fn construction_args(expr: &NodeExpression) -> usize {
+    match expr {
+        NodeExpression::Construction(_, _, args) => args.len(),
+        NodeExpression::Assignment(_, rhs) | NodeExpression::Output(rhs, _) => {
+            construction_args(&rhs.item)
+        }
+        other => panic!("not a construction: {:?}", other),
+    }
+}
+
+#[test]
+fn line_and_block_comments_are_skipped() {
+    let src =
+        "// header\nA = Constant: 1()  // trailing\n/* block\n comment */ B = Constant: 2()\n";
+    let exprs = parse(src);
+    assert_eq!(exprs.len(), 2);
+}
+
+#[test]
+fn comments_inside_constructions() {
+    let exprs = parse("X = Vec3(/* r */ A, // g\n B, C)");
+    assert_eq!(construction_args(&exprs[0].item), 3);
+}
+
+#[test]
+fn semicolons_separate_statements_on_one_line() {
+    let exprs = parse("A = Constant: 1(); B = Constant: 2();;\nOut(A)");
+    assert_eq!(exprs.len(), 3);
+}
+
+#[test]
+fn statements_on_one_line_need_a_separator() {
+    let src = "Out(Col)R = Constant: 1()";
+    let diag = parse_err(src);
+    assert_eq!(diag.kind, DiagnosticKind::Syntax);
+    // Reported at the second statement, not at the start of the line.
+    assert_eq!(diag.span.start, src.find('R').unwrap());
+}
+
+#[test]
+fn constructions_span_several_lines() {
+    let src = "Col = Vec3(\n    AddF(\n        A,\n        B\n    ).val,\n    G,\n    B,\n).val\nOut(Col)\n";
+    let exprs = parse(src);
+    assert_eq!(exprs.len(), 2);
+    assert_eq!(construction_args(&exprs[0].item), 3);
+}
+
+#[test]
+fn output_selector_on_next_line() {
+    let exprs = parse("X = Constant: 1()\n    .val");
+    assert_eq!(exprs.len(), 1);
+    assert!(matches!(
+        &exprs[0].item,
+        NodeExpression::Assignment(_, rhs) if matches!(rhs.item, NodeExpression::Output(..))
+    ));
+}
+
+#[test]
+fn bare_extra_data_is_trimmed() {
+    let exprs = parse("Constant:   0.5  ()");
+    match &exprs[0].item {
+        NodeExpression::Construction(_, data, _) => assert_eq!(data.as_deref(), Some("0.5")),
+        other => panic!("not a construction: {:?}", other),
+    }
+}
+
+#[test]
+fn unterminated_block_comment_is_an_error() {
+    let src = "A = Constant: 1()\n/* never closed\nB = Constant: 2()";
+    let diag = parse_err(src);
+    assert_eq!(diag.span.start, src.find("/*").unwrap());
+}
+
+#[test]
+fn spans_skip_leading_comments() {
+    let src = "/* note */ Out(A)";
+    let exprs = parse(src);
+    assert_eq!(exprs[0].span.start, src.find("Out").unwrap());
+    assert_eq!(exprs[0].span.end, src.len());
+}
+
+#[test]
+fn only_trivia_is_an_empty_graph() {
+    assert!(parse("  // nothing here\n/* or here */\n;\n").is_empty());
+}
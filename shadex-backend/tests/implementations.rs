@@ -0,0 +1,80 @@
+use shadex_backend::{
+    execution::{ExecutionInformation, Executor, WgslTemplate},
+    parsing::{diagnostics::DiagnosticKind, type_parsing::parse_type_world},
+    typechecking::NodeGraphFormalTypeAnalysis,
+};
+
+mod common;
+use common::{build, world};
+
+#[test]
+fn declarations_bind_to_builtins_and_templates() {
+    let world = parse_type_world(
+        "Sum = a @ f32; b @ f32 => val @ f32 with builtin Add
+Px = x @ f32 => val @ f32 with builtin Attr
+Lerp = a @ f32; b @ f32; t @ f32 => val @ f32 with wgsl \"mix({a}, {b}, {t})\"
+Bare = => val @ f32",
+    )
+    .unwrap();
+    let exec = |name: &str| world.node_types[name].as_ref().unwrap().annotation.clone();
+    assert!(matches!(exec("Sum"), ExecutionInformation::Add));
+    assert!(matches!(exec("Px"), ExecutionInformation::Attr(attr) if attr == "x"));
+    assert!(matches!(exec("Bare"), ExecutionInformation::ERR));
+    let ExecutionInformation::Wgsl(template) = exec("Lerp") else {
+        panic!("Lerp should have a WGSL body");
+    };
+    let calls = ["A".to_string(), "B".to_string(), "T".to_string()];
+    assert_eq!(template.expand(&calls), "return mix(A, B, T);");
+}
+
+#[test]
+fn templates_keep_doubled_braces_and_whole_bodies() {
+    let template = WgslTemplate::parse("if {c} > 0. {{ return {c}; }} return 0.;", &["c"]).unwrap();
+    assert_eq!(
+        template.expand(&["f(x)".to_string()]),
+        "if f(x) > 0. { return f(x); } return 0.;"
+    );
+    assert_eq!(
+        WgslTemplate::parse("{ c }", &["c"])
+            .unwrap()
+            .expand(&["v".to_string()]),
+        "return v;"
+    );
+    assert_eq!(WgslTemplate::parse("{d}", &["c"]).unwrap_err(), "d");
+}
+
+#[test]
+fn bad_implementations_are_reported_but_keep_the_type() {
+    let src = "A = a @ f32 => val @ f32 with builtin Nope
+B = a @ f32 => val @ f32 with builtin Add
+C = a @ f32 => val @ f32 with wgsl \"{b}\"";
+    let err = parse_type_world(src).unwrap_err();
+    let kinds: Vec<DiagnosticKind> = err.diagnostics.iter().map(|d| d.kind.clone()).collect();
+    assert_eq!(
+        kinds,
+        [
+            DiagnosticKind::UnknownBuiltin("Nope".to_string()),
+            DiagnosticKind::ArityMismatch {
+                expected: 2,
+                found: 1
+            },
+            DiagnosticKind::UnknownTemplateInput("b".to_string()),
+        ]
+    );
+    for name in ["A", "B", "C"] {
+        let typ = err.world.node_types[name].as_ref().unwrap();
+        assert!(matches!(typ.annotation, ExecutionInformation::ERR));
+    }
+}
+
+#[test]
+fn text_defined_libraries_execute() {
+    let src = "C = Constant: 0.5()\nM = MulF(C.val, C.val)\nOut(Vec3(M.val, C.val, C.val).val)";
+    let graph = build(&world(), src).unwrap();
+    let types = NodeGraphFormalTypeAnalysis::analyze(&graph);
+    let prog = Executor::default().run(&graph, &types).ok().unwrap();
+    assert!(
+        prog.text
+            .contains("{ return id0(x,y,component) * id0(x,y,component); }")
+    );
+}
@@ -0,0 +1,137 @@
+use std::path::Path;
+
+use shadex_backend::parsing::{
+    SimpleTypeWorld,
+    diagnostics::DiagnosticKind,
+    imports::{LoadError, MemoryLoader, load_type_world, resolve_import},
+    load_node_graph,
+};
+
+const MATH: &str = "AddF = a @ f32; b @ f32 => val @ f32 with builtin Add\n\
+                    Constant = => val @ f32 with builtin Constant\n";
+
+fn kinds(err: &LoadError) -> Vec<DiagnosticKind> {
+    err.diagnostics.iter().map(|d| d.kind.clone()).collect()
+}
+
+#[test]
+fn paths_resolve_relative_to_the_importer() {
+    assert_eq!(
+        resolve_import(
+            Some(Path::new("lib/graphs/main.shadex")),
+            "../types/./m.shadextypes"
+        ),
+        Path::new("lib/types/m.shadextypes")
+    );
+    assert_eq!(
+        resolve_import(Some(Path::new("main.shadex")), "../m.shadextypes"),
+        Path::new("../m.shadextypes")
+    );
+}
+
+#[test]
+fn type_worlds_import_with_and_without_namespace() {
+    let loader = MemoryLoader::new()
+        .with_file("types/math.shadextypes", MATH)
+        .with_file(
+            "types/all.shadextypes",
+            "import \"math.shadextypes\"\nimport \"math.shadextypes\" as math\n\
+             Out = val @ f32 => with builtin Out\n",
+        );
+    let world = load_type_world(&loader, Path::new("types/all.shadextypes")).unwrap();
+    for name in ["AddF", "Constant", "math::AddF", "math::Constant", "Out"] {
+        assert!(world.node_types.contains_key(name), "missing {}", name);
+    }
+}
+
+#[test]
+fn graphs_import_types_and_use_subgraphs() {
+    let loader = MemoryLoader::new()
+        .with_file("lib/math.shadextypes", MATH)
+        .with_file(
+            "lib/half.shadex",
+            "import \"math.shadextypes\" as m\nHalf = m::Constant: 0.5().val\n",
+        )
+        .with_file(
+            "main.shadex",
+            "import \"lib/math.shadextypes\" as math\nuse \"lib/half.shadex\" as H\n\
+             Sum = math::AddF(H::Half, H::Half).val\n",
+        );
+    let graph =
+        load_node_graph(&loader, &SimpleTypeWorld::new(), Path::new("main.shadex")).unwrap();
+    assert_eq!(graph.iter_nodes().count(), 2);
+}
+
+#[test]
+fn subgraph_bindings_need_their_alias() {
+    let loader = MemoryLoader::new()
+        .with_file("math.shadextypes", MATH)
+        .with_file(
+            "half.shadex",
+            "import \"math.shadextypes\"\nHalf = Constant: 0.5().val\n",
+        )
+        .with_file("main.shadex", "use \"half.shadex\" as H\nX = Half\n");
+    let err =
+        load_node_graph(&loader, &SimpleTypeWorld::new(), Path::new("main.shadex")).unwrap_err();
+    assert_eq!(
+        kinds(&err),
+        vec![DiagnosticKind::UnboundName("Half".to_string())]
+    );
+}
+
+#[test]
+fn import_cycles_are_reported() {
+    let loader = MemoryLoader::new()
+        .with_file("a.shadextypes", "import \"b.shadextypes\"\n")
+        .with_file("b.shadextypes", "import \"a.shadextypes\"\n");
+    let err = load_type_world(&loader, Path::new("a.shadextypes")).unwrap_err();
+    assert!(kinds(&err).contains(&DiagnosticKind::ImportCycle("a.shadextypes".to_string())));
+    let cycle = err
+        .diagnostics
+        .iter()
+        .find(|d| matches!(d.kind, DiagnosticKind::ImportCycle(_)))
+        .unwrap();
+    assert_eq!(cycle.file.as_deref(), Some(Path::new("b.shadextypes")));
+
+    let loader = MemoryLoader::new().with_file("main.shadex", "use \"main.shadex\" as Me\n");
+    let err =
+        load_node_graph(&loader, &SimpleTypeWorld::new(), Path::new("main.shadex")).unwrap_err();
+    assert_eq!(
+        kinds(&err),
+        vec![DiagnosticKind::ImportCycle("main.shadex".to_string())]
+    );
+}
+
+#[test]
+fn errors_in_imported_files_point_into_them() {
+    let loader = MemoryLoader::new()
+        .with_file(
+            "bad.shadextypes",
+            "AddF = a @ f32 => val @ f32 with builtin Nope\n",
+        )
+        .with_file("main.shadex", "import \"bad.shadextypes\"\n");
+    let err =
+        load_node_graph(&loader, &SimpleTypeWorld::new(), Path::new("main.shadex")).unwrap_err();
+    assert_eq!(err.diagnostics.len(), 2);
+    assert_eq!(
+        err.diagnostics[0].file.as_deref(),
+        Some(Path::new("bad.shadextypes"))
+    );
+    assert_eq!(
+        err.diagnostics[1].file.as_deref(),
+        Some(Path::new("main.shadex"))
+    );
+    assert!(err.render().contains("bad.shadextypes:1:"));
+}
+
+#[test]
+fn missing_files_are_reported_at_the_import() {
+    let loader = MemoryLoader::new().with_file("main.shadex", "\n  use \"gone.shadex\" as G\n");
+    let err =
+        load_node_graph(&loader, &SimpleTypeWorld::new(), Path::new("main.shadex")).unwrap_err();
+    assert!(matches!(
+        err.diagnostics[0].kind,
+        DiagnosticKind::ImportFailed { .. }
+    ));
+    assert!(err.render().contains("main.shadex:2:"));
+}
@@ -0,0 +1,170 @@
+use shadex_backend::{
+    nodegraph::{FallibleNodeTypeRc, NodeGraph, NodeRef},
+    parsing::SimpleTypeWorld,
+    typechecking::{
+        NodeGraphFormalTypeAnalysis,
+        casts::CastKind,
+        incremental::{GraphEdit, same_signature},
+        typetypes::{TypeError, TypeErrorKind},
+    },
+};
+
+mod common;
+use common::{input, node, val, world_with};
+
+fn world() -> SimpleTypeWorld<FallibleNodeTypeRc> {
+    world_with("Count = => val @ u32 with builtin Constant")
+}
+
+fn assert_up_to_date(types: &NodeGraphFormalTypeAnalysis, graph: &NodeGraph<FallibleNodeTypeRc>) {
+    let fresh = NodeGraphFormalTypeAnalysis::analyze(graph);
+    assert_eq!(types.output_type_notes, fresh.output_type_notes);
+    assert_eq!(types.input_type_notes, fresh.input_type_notes);
+    assert_eq!(types.validation.problems, fresh.validation.problems);
+}
+
+// c1 -> add -> vec3 -> out, with c2 feeding the other two Vec3 inputs.
+fn pipeline(
+    world: &SimpleTypeWorld<FallibleNodeTypeRc>,
+) -> (NodeGraph<FallibleNodeTypeRc>, [NodeRef; 5]) {
+    let mut graph = NodeGraph::new();
+    let c1 = graph.add_node(node(world, "Constant", vec![]));
+    let c2 = graph.add_node(node(world, "Constant", vec![]));
+    let add = graph.add_node(node(world, "AddF", vec![None; 2]));
+    let vec3 = graph.add_node(node(world, "Vec3", vec![None; 3]));
+    let out = graph.add_node(node(world, "Out", vec![None; 1]));
+    graph.connect(input(add, 0), val(c1)).unwrap();
+    graph.connect(input(vec3, 0), val(add)).unwrap();
+    graph.connect(input(vec3, 1), val(c2)).unwrap();
+    graph.connect(input(vec3, 2), val(c2)).unwrap();
+    graph.connect(input(out, 0), val(vec3)).unwrap();
+    (graph, [c1, c2, add, vec3, out])
+}
+
+#[test]
+fn rewiring_matches_a_fresh_analysis() {
+    let world = world();
+    let (mut graph, [c1, c2, add, ..]) = pipeline(&world);
+    let mut types = NodeGraphFormalTypeAnalysis::analyze(&graph);
+
+    graph.connect(input(add, 1), val(c2)).unwrap();
+    types.update(&graph, &[GraphEdit::InputRewired(input(add, 1))]);
+    assert_up_to_date(&types, &graph);
+
+    graph.disconnect(input(add, 0)).unwrap();
+    graph.disconnect(input(add, 1)).unwrap();
+    let edits = [
+        GraphEdit::InputRewired(input(add, 0)),
+        GraphEdit::InputRewired(input(add, 1)),
+    ];
+    types.update(&graph, &edits);
+    assert_up_to_date(&types, &graph);
+    assert!(types.output_type_notes.contains_key(&val(c1)));
+}
+
+#[test]
+fn adding_and_removing_nodes_matches_a_fresh_analysis() {
+    let world = world();
+    let (mut graph, [_, c2, add, vec3, _]) = pipeline(&world);
+    let mut types = NodeGraphFormalTypeAnalysis::analyze(&graph);
+
+    let mul = graph.add_node(node(&world, "MulF", vec![None; 2]));
+    graph.connect(input(mul, 0), val(c2)).unwrap();
+    graph.connect(input(vec3, 1), val(mul)).unwrap();
+    let edits = [
+        GraphEdit::NodeAdded(mul),
+        GraphEdit::InputRewired(input(vec3, 1)),
+    ];
+    types.update(&graph, &edits);
+    assert_up_to_date(&types, &graph);
+
+    // Removing add leaves vec3's first input free, without an edit of its own.
+    graph.remove_node(add);
+    types.update(&graph, &[GraphEdit::NodeRemoved(add)]);
+    assert_up_to_date(&types, &graph);
+    assert!(!types.output_type_notes.contains_key(&val(add)));
+}
+
+#[test]
+fn type_changes_reach_everything_downstream() {
+    let world = world();
+    let (mut graph, [c1, _, add, vec3, _]) = pipeline(&world);
+    let mut types = NodeGraphFormalTypeAnalysis::analyze(&graph);
+    assert!(types.output_type_notes[&val(vec3)].is_ok());
+
+    let count = world.node_types["Count"].clone();
+    assert!(!same_signature(
+        graph.get_node(c1).map(|n| &n.annotation).unwrap(),
+        &count
+    ));
+    graph.set_annotation(c1, count);
+    types.update(&graph, &[GraphEdit::TypeChanged(c1)]);
+    assert_up_to_date(&types, &graph);
+    // The u32 now goes through a cast on its way into add.
+    let notes = types.input_type_notes[&input(add, 0)].as_ref().unwrap();
+    assert_eq!(notes.cast().map(|cast| cast.kind), Some(CastKind::Lossy));
+}
+
+#[test]
+fn notes_outside_the_edit_are_reused() {
+    let world = world();
+    let (mut graph, [c1, _, add, vec3, _]) = pipeline(&world);
+    let mut types = NodeGraphFormalTypeAnalysis::analyze(&graph);
+
+    // Plant notes that a fresh analysis would never produce, to see which ones survive.
+    let planted = Err(TypeError::new(TypeErrorKind::Other, "planted"));
+    types.output_type_notes.insert(val(c1), planted.clone());
+    types.output_type_notes.insert(val(vec3), planted.clone());
+
+    graph.disconnect(input(add, 0)).unwrap();
+    types.update(&graph, &[GraphEdit::InputRewired(input(add, 0))]);
+    assert_eq!(types.output_type_notes[&val(c1)], planted);
+    // vec3 is downstream of the edit, so it was worked out again.
+    assert!(types.output_type_notes[&val(vec3)].is_ok());
+}
+
+#[test]
+fn cycles_can_be_made_and_broken() {
+    let world = world();
+    let (mut graph, [_, _, add, vec3, _]) = pipeline(&world);
+    let mut types = NodeGraphFormalTypeAnalysis::analyze(&graph);
+
+    let loop_back = graph.add_node(node(&world, "MulF", vec![None; 2]));
+    graph.connect(input(loop_back, 0), val(add)).unwrap();
+    graph.connect(input(add, 1), val(loop_back)).unwrap();
+    let edits = [
+        GraphEdit::NodeAdded(loop_back),
+        GraphEdit::InputRewired(input(add, 1)),
+    ];
+    types.update(&graph, &edits);
+    assert_up_to_date(&types, &graph);
+    assert!(types.output_type_notes[&val(vec3)].is_err());
+
+    graph.disconnect(input(add, 1)).unwrap();
+    types.update(&graph, &[GraphEdit::InputRewired(input(add, 1))]);
+    assert_up_to_date(&types, &graph);
+    assert!(types.output_type_notes[&val(vec3)].is_ok());
+}
+
+#[test]
+fn problems_of_clean_nodes_are_kept() {
+    let world = world();
+    let (mut graph, [c1, c2, add, ..]) = pipeline(&world);
+    let gone = graph.add_node(node(&world, "Constant", vec![]));
+    graph.remove_node(gone);
+    let mut broken = node(&world, "AddF", vec![None; 2]);
+    broken.inputs[0] = Some(val(gone));
+    graph.add_node(broken);
+    let mut types = NodeGraphFormalTypeAnalysis::analyze(&graph);
+    assert_eq!(types.validation.problems.len(), 1);
+
+    // An edit elsewhere doesn't look at the broken node again, but its problem stays reported.
+    graph.connect(input(add, 1), val(c2)).unwrap();
+    types.update(&graph, &[GraphEdit::InputRewired(input(add, 1))]);
+    assert_up_to_date(&types, &graph);
+
+    graph.remove_node(c1);
+    types.update(&graph, &[GraphEdit::NodeRemoved(c1)]);
+    assert_up_to_date(&types, &graph);
+    assert_eq!(types.validation.problems.len(), 1);
+}
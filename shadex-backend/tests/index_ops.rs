@@ -0,0 +1,121 @@
+use shadex_backend::{
+    execution::Executor,
+    nodegraph::{FallibleNodeTypeRc, NodeGraph, NodeRef},
+    parsing::SimpleTypeWorld,
+    typechecking::{
+        NodeGraphFormalTypeAnalysis, assess_value_type,
+        index_ops::IndexOp,
+        typetypes::{PrimitiveType, U32Boundedness},
+    },
+};
+
+mod common;
+use common::{add, build, val, world_with};
+
+fn bounded(n: u32) -> PrimitiveType {
+    PrimitiveType::U32(U32Boundedness::Bounded(n))
+}
+
+const U32: PrimitiveType = PrimitiveType::U32(U32Boundedness::Unbounded);
+
+#[test]
+fn bounds_follow_the_arithmetic() {
+    let result = |op: IndexOp, operands: &[PrimitiveType]| op.result_type(operands, U32).unwrap();
+    assert_eq!(
+        result(IndexOp::Add, &[bounded(1024), bounded(8)]),
+        bounded(1031)
+    );
+    assert_eq!(result(IndexOp::Add, &[bounded(1024), U32]), U32);
+    assert_eq!(result(IndexOp::Mul, &[bounded(3), bounded(5)]), bounded(9));
+    assert_eq!(result(IndexOp::Mul, &[bounded(u32::MAX), bounded(3)]), U32);
+    assert_eq!(result(IndexOp::Mod, &[U32, bounded(3)]), bounded(2));
+    assert_eq!(
+        result(IndexOp::Mod, &[bounded(2), bounded(100)]),
+        bounded(2)
+    );
+    assert_eq!(result(IndexOp::Mod, &[U32, U32]), U32);
+
+    assert_eq!(
+        IndexOp::Clamp.result_type(&[U32], bounded(3)).unwrap(),
+        bounded(3)
+    );
+    assert_eq!(
+        IndexOp::Clamp
+            .result_type(&[bounded(2)], bounded(3))
+            .unwrap(),
+        bounded(2)
+    );
+    assert!(IndexOp::Clamp.result_type(&[U32], U32).is_err());
+    assert!(
+        IndexOp::Add
+            .result_type(&[PrimitiveType::F32, U32], U32)
+            .is_err()
+    );
+}
+
+fn world() -> SimpleTypeWorld<FallibleNodeTypeRc> {
+    world_with(
+        "Row = => val @ u32[3] with builtin Constant
+Width = => val @ u32[5] with builtin Constant
+Col = => val @ u32[4] with builtin Constant",
+    )
+}
+
+#[test]
+fn flattened_table_index_keeps_its_bound() {
+    let world = world();
+    let mut graph = NodeGraph::new();
+    let row = add(&mut graph, &world, "Row", vec![]);
+    let width = add(&mut graph, &world, "Width", vec![]);
+    let col = add(&mut graph, &world, "Col", vec![]);
+    let start = add(&mut graph, &world, "IndexMul", vec![Some(row), Some(width)]);
+    let flat = add(&mut graph, &world, "IndexAdd", vec![Some(start), Some(col)]);
+    let channel = add(&mut graph, &world, "Clamp3", vec![Some(flat)]);
+    add(&mut graph, &world, "Out", vec![Some(channel)]);
+
+    let types = NodeGraphFormalTypeAnalysis::analyze(&graph);
+    let output = |node| types.output_type_notes[&val(node)].as_ref().unwrap();
+
+    let flat_type = &output(flat).formal_type;
+    assert_eq!(flat_type.output, bounded(12));
+    let props = assess_value_type(flat_type);
+    assert!(props.can_index_texture_axis);
+    assert!(!props.can_index_vector);
+
+    let channel_type = &output(channel).formal_type;
+    assert_eq!(channel_type.output, bounded(3));
+    assert!(assess_value_type(channel_type).can_index_vector);
+
+    let prog = Executor::default().run(&graph, &types).ok().unwrap();
+    assert!(
+        prog.text
+            .contains("return id0(x,y,component) * id1(x,y,component);")
+    );
+    assert!(prog.text.contains("return min(id4(x,y,component), 2u);"));
+}
+
+#[test]
+fn literal_operands_are_as_big_as_they_are() {
+    let world = world();
+    let src = "R = Row: 2()\nS = IndexAdd(R.val, 8)\nP = IndexMul(4, b: 2)\nM = IndexMod(S.val, 5)";
+    let graph = build(&world, src).unwrap();
+    let types = NodeGraphFormalTypeAnalysis::analyze(&graph);
+    let mut nodes: Vec<NodeRef> = graph
+        .iter_nodes()
+        .filter(|(_, node)| node.inputs.len() == 2)
+        .map(|(node, _)| node)
+        .collect();
+    nodes.sort();
+    let results: Vec<PrimitiveType> = nodes
+        .into_iter()
+        .map(|node| {
+            types.output_type_notes[&val(node)]
+                .as_ref()
+                .unwrap()
+                .formal_type
+                .output
+        })
+        .collect();
+    // [3] + [9], [5] * [3], and [11] mod [6], in the order they're written.
+    assert_eq!(results, [bounded(11), bounded(9), bounded(5)]);
+}
@@ -0,0 +1,116 @@
+use shadex_backend::nodegraph::{
+    GraphEditError, Node, NodeAnnotation, NodeGraph, NodeRef, ValueRef,
+};
+
+mod common;
+use common::{input, val};
+
+#[derive(Debug, Clone, PartialEq)]
+struct Tag(&'static str);
+
+impl NodeAnnotation for Tag {}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Len(usize);
+
+impl NodeAnnotation for Len {}
+
+fn node(tag: &'static str, inputs: Vec<Option<ValueRef>>) -> Node<Tag> {
+    Node {
+        annotation: Tag(tag),
+        inputs,
+        extra_data: None,
+    }
+}
+
+// a -> b -> c, and a -> c.
+fn chain() -> (NodeGraph<Tag>, NodeRef, NodeRef, NodeRef) {
+    let mut graph = NodeGraph::new();
+    let a = graph.add_node(node("a", vec![]));
+    let b = graph.add_node(node("b", vec![Some(val(a))]));
+    let c = graph.add_node(node("c", vec![Some(val(b)), Some(val(a))]));
+    (graph, a, b, c)
+}
+
+#[test]
+fn consumers_follow_the_inputs() {
+    let (graph, a, b, c) = chain();
+    let of_a: Vec<_> = graph.consumers(a).cloned().collect();
+    assert_eq!(of_a, vec![input(b, 0), input(c, 1)]);
+    assert_eq!(graph.consumers(c).count(), 0);
+}
+
+#[test]
+fn removing_a_node_disconnects_its_consumers() {
+    let (mut graph, a, b, c) = chain();
+    let removed = graph.remove_node(a).unwrap();
+    assert_eq!(removed.annotation, Tag("a"));
+    assert!(graph.get_node(a).is_none());
+    assert_eq!(graph.get_node(b).unwrap().inputs, vec![None]);
+    assert_eq!(graph.get_node(c).unwrap().inputs, vec![Some(val(b)), None]);
+
+    // Removing b takes it out of c's inputs and out of the index.
+    graph.remove_node(b);
+    assert_eq!(graph.get_node(c).unwrap().inputs, vec![None, None]);
+    assert_eq!(graph.iter_nodes().count(), 1);
+    assert!(graph.remove_node(b).is_none());
+}
+
+#[test]
+fn connect_rewires_and_reports_the_old_source() {
+    let (mut graph, a, b, c) = chain();
+    let previous = graph.connect(input(c, 0), val(a)).unwrap();
+    assert_eq!(previous, Some(val(b)));
+    assert_eq!(graph.consumers(b).count(), 0);
+    assert_eq!(graph.consumers(a).count(), 3);
+
+    assert_eq!(graph.disconnect(input(c, 0)).unwrap(), Some(val(a)));
+    assert_eq!(graph.disconnect(input(c, 0)).unwrap(), None);
+    assert_eq!(graph.consumers(a).count(), 2);
+}
+
+#[test]
+fn bad_edits_are_errors() {
+    let (mut graph, a, b, _) = chain();
+    assert_eq!(
+        graph.connect(input(b, 3), val(a)),
+        Err(GraphEditError::MissingInput(input(b, 3)))
+    );
+    graph.remove_node(a);
+    assert_eq!(
+        graph.connect(input(b, 0), val(a)),
+        Err(GraphEditError::MissingNode(a))
+    );
+    assert_eq!(
+        graph.disconnect(input(a, 0)),
+        Err(GraphEditError::MissingNode(a))
+    );
+}
+
+#[test]
+fn annotations_can_be_replaced_and_mapped() {
+    let (mut graph, a, b, c) = chain();
+    assert_eq!(graph.set_annotation(b, Tag("B")), Some(Tag("b")));
+
+    let mapped = graph.map_annotations(|_, tag| Len(tag.0.len()));
+    assert_eq!(mapped.get_node(b).unwrap().annotation, Len(1));
+    assert_eq!(
+        mapped.get_node(c).unwrap().inputs,
+        vec![Some(val(b)), Some(val(a))]
+    );
+    assert_eq!(mapped.consumers(a).count(), 2);
+
+    graph.remove_node(a);
+    assert!(graph.set_annotation(a, Tag("gone")).is_none());
+}
+
+#[test]
+fn extra_data_can_be_replaced() {
+    let (mut graph, a, b, _) = chain();
+    assert_eq!(graph.set_extra_data(b, Some("2".to_string())), Some(None));
+    assert_eq!(graph.get_node(b).unwrap().extra_data.as_deref(), Some("2"));
+    assert_eq!(graph.consumers(a).count(), 2);
+
+    graph.remove_node(a);
+    assert!(graph.set_extra_data(a, None).is_none());
+}
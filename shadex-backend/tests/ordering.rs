@@ -0,0 +1,65 @@
+use std::collections::HashSet;
+
+use shadex_backend::{
+    execution::Executor, nodegraph::NodeGraph, typechecking::NodeGraphFormalTypeAnalysis,
+};
+
+mod common;
+use common::{build, input, node, val, world};
+
+#[test]
+fn sources_come_before_their_consumers() {
+    let mut graph = NodeGraph::new();
+    let add = graph.add_node(node(&world(), "AddF", vec![None, None]));
+    let c = graph.add_node(node(&world(), "Constant", vec![]));
+    let mul = graph.add_node(node(&world(), "MulF", vec![Some(val(add)), Some(val(c))]));
+    graph.connect(input(add, 0), val(c)).unwrap();
+
+    assert_eq!(graph.topological_order(), Ok(vec![c, add, mul]));
+
+    // Inputs from outside the nodes asked about are taken as ordered already.
+    let part = HashSet::from([mul, add]);
+    assert_eq!(graph.topological_order_of(&part), Ok(vec![add, mul]));
+}
+
+#[test]
+fn cycles_are_reported_with_their_nodes() {
+    let mut graph = NodeGraph::new();
+    let c = graph.add_node(node(&world(), "Constant", vec![]));
+    let a = graph.add_node(node(&world(), "AddF", vec![Some(val(c)), None]));
+    let b = graph.add_node(node(&world(), "MulF", vec![Some(val(a)), Some(val(c))]));
+    let after = graph.add_node(node(&world(), "AddF", vec![Some(val(b)), Some(val(c))]));
+    graph.connect(input(a, 1), val(b)).unwrap();
+
+    let cycle = graph.topological_order().unwrap_err();
+    assert_eq!(cycle.nodes, vec![a, b]);
+    assert_eq!(cycle.ordered, vec![c]);
+
+    // Analysis gives up on the cycle and what hangs off it, instead of overflowing the stack.
+    let types = NodeGraphFormalTypeAnalysis::analyze(&graph);
+    assert!(types.output_type_notes[&val(c)].is_ok());
+    assert!(types.output_type_notes[&val(a)].is_err());
+    assert!(types.output_type_notes[&val(after)].is_err());
+
+    graph.add_node(node(&world(), "Out", vec![Some(val(after))]));
+    let err = Executor::default().run(&graph, &types).err().unwrap();
+    assert!(err.message.contains("cycle"));
+}
+
+#[test]
+fn self_loops_are_cycles() {
+    let mut graph = NodeGraph::new();
+    let a = graph.add_node(node(&world(), "AddF", vec![None, None]));
+    graph.connect(input(a, 0), val(a)).unwrap();
+    assert_eq!(graph.topological_order().unwrap_err().nodes, vec![a]);
+}
+
+#[test]
+fn shared_nodes_are_emitted_once() {
+    let graph = build(&world(), "X = AddF(1, 2).val\nOut(Vec3(X, X, X).val)").unwrap();
+    let types = NodeGraphFormalTypeAnalysis::analyze(&graph);
+
+    let prog = Executor::default().run(&graph, &types).ok().unwrap();
+    // Two constants, AddF and Vec3.
+    assert_eq!(prog.text.matches("fn ").count(), 4);
+}
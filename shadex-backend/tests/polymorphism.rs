@@ -0,0 +1,125 @@
+use std::rc::Rc;
+
+use shadex_backend::{
+    execution::Executor,
+    nodegraph::{FallibleNodeTypeRc, NodeGraph, NodeRef},
+    parsing::SimpleTypeWorld,
+    typechecking::{
+        NodeGraphFormalTypeAnalysis,
+        casts::CastKind,
+        typetypes::{ErrorLocation, PrimitiveType, TypeErrorKind, U32Boundedness},
+    },
+};
+
+mod common;
+use common::{add, build, input, val, world_with};
+
+const U32: PrimitiveType = PrimitiveType::U32(U32Boundedness::Unbounded);
+
+fn world() -> SimpleTypeWorld<FallibleNodeTypeRc> {
+    world_with(
+        "Count = => val @ u32 with builtin Constant
+Row = => val @ u32[3] with builtin Constant
+Neg = => val @ i32 with builtin Constant",
+    )
+}
+
+#[test]
+fn variables_are_inferred_per_node() {
+    let world = world();
+    let mut graph = NodeGraph::new();
+    let count = add(&mut graph, &world, "Count", vec![]);
+    let row = add(&mut graph, &world, "Row", vec![]);
+    let c = add(&mut graph, &world, "Constant", vec![]);
+    let ints = add(&mut graph, &world, "Add", vec![Some(count), Some(row)]);
+    let floats = add(&mut graph, &world, "Add", vec![Some(row), Some(c)]);
+    let types = NodeGraphFormalTypeAnalysis::analyze(&graph);
+    let output = |node| {
+        &types.output_type_notes[&val(node)]
+            .as_ref()
+            .unwrap()
+            .formal_type
+    };
+
+    // The bound doesn't survive: nothing says the sum is still below 3.
+    assert_eq!(output(ints).output, U32);
+    assert_eq!(output(floats).output, PrimitiveType::F32);
+    let row_into_floats = types.input_type_notes[&input(floats, 0)].as_ref().unwrap();
+    assert_eq!(row_into_floats.cast().unwrap().kind, CastKind::Lossless);
+
+    // Both nodes share their declared type, but not their instances.
+    let instance = |node: NodeRef| types.instances[&node].as_ref().unwrap().clone();
+    assert!(!Rc::ptr_eq(&instance(ints), &instance(floats)));
+    assert_eq!(
+        instance(ints).inputs[1].value_type.as_ref().unwrap().output,
+        U32
+    );
+    assert!(!types.instances.contains_key(&count));
+}
+
+#[test]
+fn instances_are_what_gets_executed() {
+    let world = world();
+    let mut graph = NodeGraph::new();
+    let count = add(&mut graph, &world, "Count", vec![]);
+    let row = add(&mut graph, &world, "Row", vec![]);
+    let sum = add(&mut graph, &world, "Add", vec![Some(count), Some(row)]);
+    add(&mut graph, &world, "Out", vec![Some(sum)]);
+    let types = NodeGraphFormalTypeAnalysis::analyze(&graph);
+
+    let prog = Executor::default().run(&graph, &types).ok().unwrap();
+    assert!(prog.text.contains(
+        "fn id2(x: f32, y: f32, component: u32) -> u32 { return id0(x,y,component) + id1(x,y,component); }"
+    ));
+}
+
+#[test]
+fn inputs_that_disagree_are_an_error() {
+    let world = world();
+    let mut graph = NodeGraph::new();
+    let count = add(&mut graph, &world, "Count", vec![]);
+    let c = add(&mut graph, &world, "Constant", vec![]);
+    let sum = add(&mut graph, &world, "Add", vec![Some(count), Some(c)]);
+    let lonely = add(&mut graph, &world, "Add", vec![None, None]);
+    let types = NodeGraphFormalTypeAnalysis::analyze(&graph);
+
+    let err = types.output_type_notes[&val(sum)].as_ref().unwrap_err();
+    assert_eq!(err.kind, TypeErrorKind::UnresolvedTypeVariable);
+    assert!(!err.propagated);
+    let related: Vec<_> = err.related.iter().map(|n| n.location.clone()).collect();
+    assert_eq!(
+        related,
+        vec![
+            ErrorLocation::Input(input(sum, 0)),
+            ErrorLocation::Input(input(sum, 1))
+        ]
+    );
+    assert!(types.input_type_notes[&input(sum, 0)].is_err());
+
+    let err = types.output_type_notes[&val(lonely)].as_ref().unwrap_err();
+    assert_eq!(err.kind, TypeErrorKind::UnresolvedTypeVariable);
+}
+
+#[test]
+fn literals_go_along_with_the_other_inputs() {
+    let world = world();
+    let output_of_add = |src: &str| {
+        let graph = build(&world, src).unwrap();
+        let types = NodeGraphFormalTypeAnalysis::analyze(&graph);
+        let (node, _) = graph
+            .iter_nodes()
+            .find(|(_, n)| world.type_name(&n.annotation).as_deref() == Some("Add"))
+            .unwrap();
+        types.output_type_notes[&val(node)]
+            .as_ref()
+            .ok()
+            .map(|notes| notes.formal_type.output)
+    };
+    assert_eq!(output_of_add("Add(1, 2)"), Some(U32));
+    assert_eq!(output_of_add("Add(1, 2.5)"), Some(PrimitiveType::F32));
+    assert_eq!(
+        output_of_add("Add(Neg: 1().val, 2)"),
+        Some(PrimitiveType::I32)
+    );
+    assert_eq!(output_of_add("Add(Count: 1().val, 2.5)"), None);
+}
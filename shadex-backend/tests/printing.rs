@@ -0,0 +1,145 @@
+use shadex_backend::{
+    nodegraph::{FallibleNodeTypeRc, Node, NodeGraph},
+    parsing::{
+        SimpleTypeWorld,
+        printing::{PrintError, print_with_type_world},
+        type_parsing::parse_type_world,
+    },
+};
+
+mod common;
+use common::{build, input, val, world_with};
+
+fn types() -> SimpleTypeWorld<FallibleNodeTypeRc> {
+    world_with(
+        "Split = v @ f32 => lo @ f32; hi @ f32; mid @ f32 with wgsl \"{v}\"
+Named = => val @ f32 with wgsl \"0.\"
+Shift = v @ i32 => val @ i32 with wgsl \"{v}\"",
+    )
+}
+
+// Printing, reading the text back and printing again has to land on the same text.
+fn round_trip(src: &str) -> String {
+    let world = types();
+    let printed = print_with_type_world(&world, &build(&world, src).unwrap()).unwrap();
+    let reprinted = print_with_type_world(&world, &build(&world, &printed).unwrap()).unwrap();
+    assert_eq!(printed, reprinted);
+    printed
+}
+
+#[test]
+fn outputs_are_picked_by_name() {
+    let printed =
+        round_trip("C = Constant: 1()\n(lo, hi, mid) = Split(C.val)\nOut(Vec3(mid, lo, hi).val)");
+    assert_eq!(
+        printed,
+        "n0 = Constant: 1()
+n1 = Split(n0.val)
+n2 = Vec3(n1.mid, n1.lo, n1.hi)
+Out(n2.val)
+"
+    );
+}
+
+#[test]
+fn unconnected_inputs_print_as_null() {
+    let printed = round_trip("S = AddF(NULL, Constant: 2().val)\nT = MulF(S.val, NULL)");
+    assert_eq!(
+        printed,
+        "n0 = Constant: 2()
+n1 = AddF(NULL, n0.val)
+n2 = MulF(n1.val, NULL)
+"
+    );
+}
+
+#[test]
+fn extra_data_is_quoted_unless_it_is_a_number() {
+    let printed = round_trip("A = Named: \"hello there\"()\nB = Named: \" 1\"()\nC = Named: 2.5()");
+    assert_eq!(
+        printed,
+        "n0 = Named: \"hello there\"()
+n1 = Named: \" 1\"()
+n2 = Named: 2.5()
+"
+    );
+
+    let world = types();
+    let mut graph = NodeGraph::new();
+    let quoted = graph.add_node(Node {
+        annotation: world.node_types["Named"].clone(),
+        inputs: vec![],
+        extra_data: Some("say \"hi\"".to_string()),
+    });
+    assert_eq!(
+        print_with_type_world(&world, &graph),
+        Err(PrintError::UnprintableExtraData(quoted))
+    );
+}
+
+#[test]
+fn literals_are_written_where_they_are_used() {
+    let printed = round_trip("X = AddF(1, 2.5).val\nOut(Vec3(X, X, 3).val)\nS = Shift(-2)");
+    assert_eq!(
+        printed,
+        "n0 = AddF(1.0, 2.5)
+n1 = Vec3(n0.val, n0.val, 3.0)
+Out(n1.val)
+n3 = Shift(-2)
+"
+    );
+}
+
+#[test]
+fn names_follow_the_graph_not_the_source() {
+    // The same graph written two ways prints the same, with names given in emission order.
+    let a = round_trip(
+        "Zed = Constant: 1()\nYak = Constant: 2()\nOut(Vec3(Zed.val, Yak.val, Zed.val).val)",
+    );
+    let b = round_trip(
+        "Q = Constant: 1()\nP = Constant: 2()\nV = Vec3(Q.val, P.val, Q.val)\nOut(V.val)",
+    );
+    assert_eq!(a, b);
+    assert!(a.starts_with("n0 = Constant: 1()\nn1 = Constant: 2()\n"));
+}
+
+#[test]
+fn types_missing_from_the_world_are_reported() {
+    let world = types();
+    let mut graph = NodeGraph::new();
+    let stray = parse_type_world("Stray = => val @ f32").unwrap();
+    let node = graph.add_node(Node {
+        annotation: stray.node_types["Stray"].clone(),
+        inputs: vec![],
+        extra_data: None,
+    });
+    assert_eq!(
+        print_with_type_world(&world, &graph),
+        Err(PrintError::UnnamedType(node))
+    );
+}
+
+#[test]
+fn cycles_and_dangling_inputs_cannot_be_printed() {
+    let world = types();
+    let mut graph = build(&world, "S = AddF(NULL, NULL)").unwrap();
+    let (sum, _) = graph.iter_nodes().next().unwrap();
+    graph.connect(input(sum, 0), val(sum)).unwrap();
+    assert_eq!(
+        print_with_type_world(&world, &graph),
+        Err(PrintError::Cycle)
+    );
+
+    let mut graph = build(&world, "C = Constant: 1()").unwrap();
+    let (c, _) = graph.iter_nodes().next().unwrap();
+    graph.remove_node(c);
+    let reader = graph.add_node(Node {
+        annotation: world.node_types["AddF"].clone(),
+        inputs: vec![None, Some(val(c))],
+        extra_data: None,
+    });
+    assert_eq!(
+        print_with_type_world(&world, &graph),
+        Err(PrintError::DanglingInput(reader))
+    );
+}
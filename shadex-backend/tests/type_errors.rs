@@ -0,0 +1,98 @@
+use shadex_backend::{
+    nodegraph::{FallibleNodeTypeRc, NodeGraph, NodeInputReference},
+    parsing::SimpleTypeWorld,
+    typechecking::{
+        NodeGraphFormalTypeAnalysis,
+        typetypes::{ErrorLocation, TypeError, TypeErrorKind},
+    },
+};
+
+mod common;
+use common::{add, input, val, world_with};
+
+fn world() -> SimpleTypeWorld<FallibleNodeTypeRc> {
+    world_with(
+        "Count = => val @ u32 with builtin Constant
+Pick = i @ u32[3] => val @ f32 with wgsl \"f32({i})\"
+Any = => val @ comp: u32 -> f32 with wgsl \"1.0\"
+Real = => val @ comp: f32 -> f32 with wgsl \"1.0\"",
+    )
+}
+
+fn input_error(types: &NodeGraphFormalTypeAnalysis, inp: NodeInputReference) -> &TypeError {
+    types.input_type_notes[&inp].as_ref().unwrap_err()
+}
+
+#[test]
+fn errors_start_at_their_cause_and_propagate_from_there() {
+    let world = world();
+    let mut graph = NodeGraph::new();
+    let count = add(&mut graph, &world, "Count", vec![]);
+    let pick = add(&mut graph, &world, "Pick", vec![Some(count)]);
+    let out = add(&mut graph, &world, "Out", vec![Some(pick)]);
+    let types = NodeGraphFormalTypeAnalysis::analyze(&graph);
+
+    let root = input_error(&types, input(pick, 0));
+    assert_eq!(root.kind, TypeErrorKind::PrimitiveMismatch);
+    assert_eq!(root.location, Some(ErrorLocation::Input(input(pick, 0))));
+    assert!(!root.propagated);
+    assert_eq!(root.related[0].location, ErrorLocation::Output(val(count)));
+
+    let downstream = input_error(&types, input(out, 0));
+    assert_eq!(downstream.kind, TypeErrorKind::PrimitiveMismatch);
+    assert_eq!(
+        downstream.location,
+        Some(ErrorLocation::Input(input(out, 0)))
+    );
+    assert!(downstream.propagated);
+    // However far it went, it still points back to where it started.
+    assert_eq!(
+        downstream.related[0].location,
+        ErrorLocation::Input(input(pick, 0))
+    );
+    assert_eq!(downstream.message, root.message);
+}
+
+#[test]
+fn argument_conflicts_point_at_both_inputs() {
+    let world = world();
+    let mut graph = NodeGraph::new();
+    let any = add(&mut graph, &world, "Any", vec![]);
+    let real = add(&mut graph, &world, "Real", vec![]);
+    let sum = add(&mut graph, &world, "AddF", vec![Some(any), Some(real)]);
+    let types = NodeGraphFormalTypeAnalysis::analyze(&graph);
+
+    let err = types.output_type_notes[&val(sum)].as_ref().unwrap_err();
+    assert_eq!(err.kind, TypeErrorKind::ArgumentConflict);
+    assert_eq!(err.location, Some(ErrorLocation::Output(val(sum))));
+    let related: Vec<_> = err.related.iter().map(|n| n.location.clone()).collect();
+    assert_eq!(
+        related,
+        vec![
+            ErrorLocation::Input(input(sum, 0)),
+            ErrorLocation::Input(input(sum, 1))
+        ]
+    );
+}
+
+#[test]
+fn malformed_graphs_get_their_own_kinds() {
+    let world = world();
+    let mut graph = NodeGraph::new();
+    let c = add(&mut graph, &world, "Constant", vec![]);
+    let sum = add(&mut graph, &world, "AddF", vec![Some(c), Some(c)]);
+    let gone = add(&mut graph, &world, "Constant", vec![]);
+    graph.remove_node(gone);
+    // Built with a reference to a node that's gone, the way a bad file would be.
+    let dangling = add(&mut graph, &world, "AddF", vec![Some(gone), Some(c)]);
+    graph.connect(input(sum, 1), val(sum)).unwrap();
+    let types = NodeGraphFormalTypeAnalysis::analyze(&graph);
+
+    let err = input_error(&types, input(dangling, 0));
+    assert_eq!(err.kind, TypeErrorKind::MissingNode);
+    assert!(!err.propagated);
+
+    let err = input_error(&types, input(sum, 0));
+    assert_eq!(err.kind, TypeErrorKind::MalformedGraph);
+    assert!(!err.propagated);
+}
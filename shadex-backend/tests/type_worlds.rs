@@ -0,0 +1,56 @@
+use shadex_backend::parsing::{diagnostics::DiagnosticKind, type_parsing::parse_type_world};
+
+mod common;
+use common::TYPELAND;
+
+#[test]
+fn bad_declarations_are_all_reported_and_the_rest_kept() {
+    let src = "AddF = a @ f32; b @ f32 => val @ f32
+Broken = a @ f33 => val @ f32
+Constant = => val @ f32
+AlsoBroken = => val @
+Out = val @ f32 =>";
+    let err = parse_type_world(src).unwrap_err();
+
+    let lines: Vec<usize> = err
+        .diagnostics
+        .iter()
+        .map(|d| d.span.locate(src).line)
+        .collect();
+    assert_eq!(lines, [2, 4]);
+    assert!(
+        err.diagnostics
+            .iter()
+            .all(|d| d.kind == DiagnosticKind::Syntax)
+    );
+
+    let mut names: Vec<&str> = err.world.node_types.keys().map(String::as_str).collect();
+    names.sort();
+    assert_eq!(names, ["AddF", "Constant", "Out"]);
+    assert!(err.render(src).contains(" --> 4:"));
+}
+
+#[test]
+fn duplicate_names_keep_the_first_declaration() {
+    let src = "Constant = => val @ f32
+Constant = => val @ u32";
+    let err = parse_type_world(src).unwrap_err();
+    assert_eq!(err.diagnostics.len(), 1);
+    assert_eq!(
+        err.diagnostics[0].kind,
+        DiagnosticKind::DuplicateNodeType("Constant".to_string())
+    );
+    assert_eq!(err.diagnostics[0].span.locate(src).line, 2);
+
+    let constant = err.world.node_types["Constant"].as_ref().unwrap();
+    assert_eq!(
+        constant.outputs[0].value_type.as_ref().unwrap().to_string(),
+        "f32"
+    );
+}
+
+#[test]
+fn a_clean_file_is_not_an_error() {
+    let world = parse_type_world(TYPELAND).unwrap();
+    assert!(world.node_types.contains_key("Vec3"));
+}
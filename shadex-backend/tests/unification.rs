@@ -0,0 +1,87 @@
+use shadex_backend::{
+    nodegraph::{FallibleNodeTypeRc, NodeGraph, ValueRef},
+    parsing::SimpleTypeWorld,
+    typechecking::{
+        ArgumentWidening, NodeGraphFormalTypeAnalysis,
+        casts::narrowest,
+        typetypes::{PrimitiveType, U32Boundedness, ValueType},
+    },
+};
+
+mod common;
+use common::{add, val, world_with};
+
+fn bounded(n: u32) -> ValueType {
+    ValueType::primitive(PrimitiveType::U32(U32Boundedness::Bounded(n)))
+}
+
+fn unbounded() -> ValueType {
+    ValueType::primitive(PrimitiveType::U32(U32Boundedness::Unbounded))
+}
+
+#[test]
+fn narrowest_picks_the_tightest_bound() {
+    assert_eq!(narrowest(&bounded(3), &bounded(4)), Some(bounded(3)));
+    assert_eq!(narrowest(&bounded(4), &bounded(3)), Some(bounded(3)));
+    assert_eq!(narrowest(&unbounded(), &bounded(3)), Some(bounded(3)));
+    assert_eq!(narrowest(&unbounded(), &unbounded()), Some(unbounded()));
+    let f32 = ValueType::primitive(PrimitiveType::F32);
+    assert_eq!(narrowest(&unbounded(), &f32), None);
+}
+
+fn world() -> SimpleTypeWorld<FallibleNodeTypeRc> {
+    world_with(
+        "Three = => val @ comp: u32[3] -> f32 with wgsl \"1.0\"
+Four = => val @ comp: u32[4] -> f32 with wgsl \"1.0\"
+Any = => val @ comp: u32 -> f32 with wgsl \"1.0\"
+Real = => val @ comp: f32 -> f32 with wgsl \"1.0\"",
+    )
+}
+
+fn sum_of(a: &str, b: &str) -> (NodeGraph<FallibleNodeTypeRc>, ValueRef) {
+    let world = world();
+    let mut graph = NodeGraph::new();
+    let a = add(&mut graph, &world, a, vec![]);
+    let b = add(&mut graph, &world, b, vec![]);
+    let sum = add(&mut graph, &world, "AddF", vec![Some(a), Some(b)]);
+    add(&mut graph, &world, "Out", vec![Some(sum)]);
+    (graph, val(sum))
+}
+
+#[test]
+fn differently_bounded_arguments_are_narrowed() {
+    for (a, b, wide) in [("Four", "Three", bounded(4)), ("Any", "Three", unbounded())] {
+        let (graph, sum) = sum_of(a, b);
+        let types = NodeGraphFormalTypeAnalysis::analyze(&graph);
+        let notes = types.output_type_notes[&sum].as_ref().unwrap();
+        assert_eq!(*notes.formal_type.inputs["comp"], bounded(3));
+        assert_eq!(notes.inputs_parameterized_by["comp"], bounded(3));
+        assert_eq!(
+            notes.widened_inputs,
+            vec![ArgumentWidening {
+                input_ind: 0,
+                arg: "comp".to_string(),
+                from: bounded(3),
+                to: wide,
+            }]
+        );
+        // The output still fits where a [3] is expected.
+        assert!(types.input_type_notes.values().all(|notes| notes.is_ok()));
+    }
+}
+
+#[test]
+fn matching_arguments_widen_nothing() {
+    let (graph, sum) = sum_of("Three", "Three");
+    let types = NodeGraphFormalTypeAnalysis::analyze(&graph);
+    let notes = types.output_type_notes[&sum].as_ref().unwrap();
+    assert!(notes.widened_inputs.is_empty());
+}
+
+#[test]
+fn irreconcilable_arguments_are_still_errors() {
+    let (graph, sum) = sum_of("Any", "Real");
+    let types = NodeGraphFormalTypeAnalysis::analyze(&graph);
+    let err = types.output_type_notes[&sum].as_ref().unwrap_err();
+    assert!(err.message.contains("comp"));
+}
@@ -0,0 +1,88 @@
+use shadex_backend::{
+    execution::Executor,
+    nodegraph::{NodeGraph, validation::GraphProblem},
+    typechecking::NodeGraphFormalTypeAnalysis,
+};
+
+mod common;
+use common::{input, node, val, val_at, world};
+
+#[test]
+fn well_formed_graphs_have_no_problems() {
+    let mut graph = NodeGraph::new();
+    let c = graph.add_node(node(&world(), "Constant", vec![]));
+    graph.add_node(node(&world(), "AddF", vec![Some(val(c)), None]));
+    assert!(graph.validate().is_valid());
+}
+
+#[test]
+fn dangling_inputs_are_reported() {
+    let mut graph = NodeGraph::new();
+    let c = graph.add_node(node(&world(), "Constant", vec![]));
+    graph.remove_node(c);
+    // Built with a reference to a node that's gone, the way a bad file would be.
+    let add = graph.add_node(node(&world(), "AddF", vec![None, Some(val(c))]));
+
+    let report = graph.validate();
+    assert_eq!(
+        report.problems,
+        vec![GraphProblem::DanglingInput {
+            input: input(add, 1),
+            source: val(c),
+        }]
+    );
+
+    let types = NodeGraphFormalTypeAnalysis::analyze(&graph);
+    assert!(types.input_type_notes[&input(add, 1)].is_err());
+    assert!(types.output_type_notes[&val(add)].is_err());
+}
+
+#[test]
+fn out_of_range_outputs_are_reported() {
+    let mut graph = NodeGraph::new();
+    let c = graph.add_node(node(&world(), "Constant", vec![]));
+    let add = graph.add_node(node(
+        &world(),
+        "AddF",
+        vec![Some(val(c)), Some(val_at(c, 2))],
+    ));
+
+    let report = graph.validate();
+    assert_eq!(
+        report.problems,
+        vec![GraphProblem::OutputOutOfRange {
+            input: input(add, 1),
+            source: val_at(c, 2),
+            outputs: 1,
+        }]
+    );
+    let types = NodeGraphFormalTypeAnalysis::analyze(&graph);
+    assert!(types.input_type_notes[&input(add, 0)].is_ok());
+    assert!(types.input_type_notes[&input(add, 1)].is_err());
+}
+
+#[test]
+fn input_count_mismatches_are_reported() {
+    let mut graph = NodeGraph::new();
+    let c = graph.add_node(node(&world(), "Constant", vec![]));
+    let add = graph.add_node(node(&world(), "AddF", vec![Some(val(c))]));
+    let out = graph.add_node(node(&world(), "Out", vec![Some(val(add))]));
+
+    let report = graph.validate();
+    assert_eq!(
+        report.problems,
+        vec![GraphProblem::InputCountMismatch {
+            node: add,
+            expected: 2,
+            found: 1,
+        }]
+    );
+
+    // Analysis reports the node as broken instead of indexing past its inputs.
+    let types = NodeGraphFormalTypeAnalysis::analyze(&graph);
+    assert!(types.output_type_notes[&val(add)].is_err());
+    assert!(types.input_type_notes[&input(out, 0)].is_err());
+
+    let err = Executor::default().run(&graph, &types).err().unwrap();
+    assert!(err.message.contains("takes 2"));
+}
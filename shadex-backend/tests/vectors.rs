@@ -0,0 +1,168 @@
+use shadex_backend::{
+    execution::Executor,
+    nodegraph::{FallibleNodeTypeRc, NodeGraph, ValueRef},
+    parsing::{SimpleTypeWorld, type_parsing::parse_type_world},
+    typechecking::{
+        NodeGraphFormalTypeAnalysis,
+        casts::{CastKind, implicit_cast, narrowest},
+        typetypes::{PrimitiveType, ScalarType, TypeErrorKind, U32Boundedness, ValueType},
+    },
+};
+
+mod common;
+use common::{build, val, world_with};
+
+const F32: PrimitiveType = PrimitiveType::F32;
+const U32: PrimitiveType = PrimitiveType::U32(U32Boundedness::Unbounded);
+
+fn world() -> SimpleTypeWorld<FallibleNodeTypeRc> {
+    world_with(
+        "Flag = => val @ bool with builtin Constant
+Byte = => val @ u8 with builtin Constant
+Ints = => val @ vec4<i32> with builtin Constant
+Frame = => val @ mat3x4<f32> with builtin Constant
+BadPack = v @ comp: [4] -> f32 => val @ vec3<f32> with builtin Pack",
+    )
+}
+
+fn output_of(world: &SimpleTypeWorld<FallibleNodeTypeRc>, name: &str) -> PrimitiveType {
+    let typ = world.node_types[name].as_ref().unwrap();
+    typ.outputs[0].value_type.as_ref().unwrap().output
+}
+
+fn node_named(
+    world: &SimpleTypeWorld<FallibleNodeTypeRc>,
+    graph: &NodeGraph<FallibleNodeTypeRc>,
+    name: &str,
+) -> ValueRef {
+    let (node, _) = graph
+        .iter_nodes()
+        .find(|(_, n)| world.type_name(&n.annotation).as_deref() == Some(name))
+        .unwrap();
+    val(node)
+}
+
+#[test]
+fn new_primitives_parse_and_display() {
+    let world = world();
+    assert_eq!(output_of(&world, "Flag"), PrimitiveType::Bool);
+    assert_eq!(output_of(&world, "Byte"), PrimitiveType::U8);
+    assert_eq!(
+        output_of(&world, "Ints"),
+        PrimitiveType::Vector(4, ScalarType::I32)
+    );
+    assert_eq!(output_of(&world, "Frame"), PrimitiveType::Matrix(3, 4));
+
+    let shown: Vec<String> = ["Flag", "Byte", "Ints", "Frame"]
+        .iter()
+        .map(|name| output_of(&world, name).to_string())
+        .collect();
+    assert_eq!(shown, ["bool", "u8", "vec4<i32>", "mat3x4<f32>"]);
+
+    assert!(parse_type_world("V = => val @ vec5<f32> with builtin Constant").is_err());
+    assert!(parse_type_world("V = => val @ mat3x3<i32> with builtin Constant").is_err());
+}
+
+#[test]
+fn casts_between_new_primitives() {
+    let kind = |from, to| implicit_cast(from, to).map(|cast| cast.map(|c| c.kind));
+    let vec3 = |scalar| PrimitiveType::Vector(3, scalar);
+
+    let bounded = |n| PrimitiveType::U32(U32Boundedness::Bounded(n));
+    assert_eq!(kind(PrimitiveType::U8, U32), Ok(Some(CastKind::Lossless)));
+    assert_eq!(kind(PrimitiveType::U8, F32), Ok(Some(CastKind::Lossless)));
+    assert_eq!(
+        kind(PrimitiveType::U8, bounded(256)),
+        Ok(Some(CastKind::Lossless))
+    );
+    assert!(kind(PrimitiveType::U8, bounded(255)).is_err());
+    assert_eq!(
+        kind(bounded(3), PrimitiveType::U8),
+        Ok(Some(CastKind::Lossless))
+    );
+    assert!(kind(bounded(257), PrimitiveType::U8).is_err());
+    assert!(kind(U32, PrimitiveType::U8).is_err());
+    // Being cast exactly doesn't make a u8 the same thing as a bounded index.
+    assert_eq!(
+        narrowest(
+            &ValueType::primitive(PrimitiveType::U8),
+            &ValueType::primitive(bounded(3))
+        ),
+        None
+    );
+    assert_eq!(
+        kind(vec3(ScalarType::U32), vec3(ScalarType::F32)),
+        Ok(Some(CastKind::Lossy))
+    );
+
+    assert!(
+        kind(
+            vec3(ScalarType::F32),
+            PrimitiveType::Vector(4, ScalarType::F32)
+        )
+        .is_err()
+    );
+    assert!(kind(vec3(ScalarType::Bool), vec3(ScalarType::F32)).is_err());
+    assert!(kind(PrimitiveType::Bool, F32).is_err());
+    assert!(kind(vec3(ScalarType::F32), F32).is_err());
+    assert!(kind(PrimitiveType::Matrix(3, 3), PrimitiveType::Matrix(3, 4)).is_err());
+}
+
+#[test]
+fn pack_and_unpack_go_through_vec3() {
+    let world = world();
+    let graph = build(
+        &world,
+        "C = Constant: 1()
+V = Vec3(C.val, C.val, C.val)
+P = Pack3(V.val)
+D = Dot3(P.val, P.val)
+U = Unpack3(P.val)
+Out(U.val)",
+    )
+    .unwrap();
+    let types = NodeGraphFormalTypeAnalysis::analyze(&graph);
+    let output = |name| {
+        types.output_type_notes[&node_named(&world, &graph, name)]
+            .as_ref()
+            .unwrap()
+            .formal_type
+            .to_string()
+    };
+    assert_eq!(output("Pack3"), "vec3<f32>");
+    assert_eq!(output("Unpack3"), "(comp: [3] -> f32)");
+    assert_eq!(output("Dot3"), "f32");
+
+    let prog = Executor::default().run(&graph, &types).ok().unwrap();
+    assert!(prog.text.contains(
+        "fn id2(x: f32, y: f32, component: u32) -> vec3<f32> { return vec3<f32>(id1(x,y,0u), id1(x,y,1u), id1(x,y,2u)); }"
+    ));
+    assert!(prog.text.contains(
+        "fn id3(x: f32, y: f32, component: u32) -> f32 { return id2(x,y,component)[component]; }"
+    ));
+}
+
+#[test]
+fn conversions_have_to_line_up() {
+    let world = world();
+    let graph = build(
+        &world,
+        "C = Constant: 1()
+V = Vec3(C.val, C.val, C.val)
+B = BadPack(V.val)
+D = Dot3(V.val, V.val)",
+    )
+    .unwrap();
+    let types = NodeGraphFormalTypeAnalysis::analyze(&graph);
+
+    let err = types.output_type_notes[&node_named(&world, &graph, "BadPack")]
+        .as_ref()
+        .unwrap_err();
+    assert_eq!(err.kind, TypeErrorKind::IncompleteNodeType);
+
+    // A function of `comp` isn't a vector until it's packed.
+    let err = types.output_type_notes[&node_named(&world, &graph, "Dot3")]
+        .as_ref()
+        .unwrap_err();
+    assert_eq!(err.kind, TypeErrorKind::PrimitiveMismatch);
+}
@@ -63,8 +63,21 @@ impl eframe::App for App {
                 ui.heading("Examples to load");
                 for (name, content) in EXAMPLE_PROGRAMS {
                     if ui.button(name).clicked() {
-                        self.graph_ui_state = serde_json::from_str(content).unwrap();
-                        *load = true;
+                        // Examples are either GUI saves or hand-written .shadex text.
+                        match GraphUIState::load(content) {
+                            Ok(state) => {
+                                self.graph_ui_state = state;
+                                *load = true;
+                            }
+                            Err(e) => eprintln!("Could not load example {}:\n{}", name, e),
+                        }
+                    }
+                }
+                ui.separator();
+                if ui.button("Copy as .shadex").clicked() {
+                    match self.graph_ui_state.graph_state.visual_graph.to_text() {
+                        Ok(text) => ui.ctx().copy_text(text),
+                        Err(e) => eprintln!("Could not print graph: {}", e),
                     }
                 }
             });
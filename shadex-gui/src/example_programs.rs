@@ -1,4 +1,5 @@
-pub const EXAMPLE_PROGRAMS: [(&'static str, &'static str); 2] = [
+pub const EXAMPLE_PROGRAMS: [(&'static str, &'static str); 3] = [
     ("Gray", include_str!("../examples/gray.shadex")),
     ("RGB", include_str!("../examples/rgb.shadex")),
+    ("Gradient", include_str!("../examples/gradient.shadex")),
 ];
@@ -0,0 +1,611 @@
+use std::path::{Path, PathBuf};
+
+use shadex_backend::{
+    execution::ExecutionInformation,
+    nodegraph::{FallibleNodeTypeRc, NodeGraph, NodeTypeInfo, ValueRef},
+    parsing::{
+        Binding, DefinitionExpression, GraphSourceMap, NodeExpression, SimpleTypeWorld, Spanned,
+        SpannedExpression,
+        diagnostics::{Diagnostic, SourceSpan},
+        imports::{LoadError, SourceLoader, load_type_world, resolve_import},
+        load_node_graph_mapped, parse_whole_input,
+        type_parsing::declaration_spans,
+    },
+    typechecking::{
+        NodeGraphFormalTypeAnalysis, NodeInputReference,
+        typetypes::{MaybeValueType, ValueType},
+    },
+};
+
+use crate::analysis::symbols::{SymbolKind, SymbolTable};
+
+mod symbols;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileDiagnostic {
+    pub span: SourceSpan,
+    pub message: String,
+    // Other places in the same file that have to do with it.
+    pub related: Vec<(SourceSpan, String)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompletionKind {
+    NodeType,
+    Variable,
+    Output,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Completion {
+    pub label: String,
+    pub kind: CompletionKind,
+    pub detail: Option<String>,
+}
+
+// A place in some file, for jumping to.
+pub type Location = (PathBuf, SourceSpan);
+
+// Diagnostics of a .shadextypes file, including the imports it makes.
+pub fn type_world_diagnostics(loader: &dyn SourceLoader, path: &Path) -> Vec<FileDiagnostic> {
+    let path = resolve_import(None, &path.to_string_lossy());
+    match load_type_world(loader, &path) {
+        Ok(_) => Vec::new(),
+        // Errors inside imported files show up as a failed import here.
+        Err(err) => err
+            .diagnostics
+            .iter()
+            .filter(|d| d.file.as_deref().is_none_or(|f| f == path))
+            .map(|d| FileDiagnostic {
+                span: d.span,
+                message: d.message(),
+                related: Vec::new(),
+            })
+            .collect(),
+    }
+}
+
+// Everything the server knows about one .shadex file.
+pub struct GraphAnalysis {
+    pub path: PathBuf,
+    pub diagnostics: Vec<FileDiagnostic>,
+    text: String,
+    parsed: bool,
+    exprs: Vec<SpannedExpression>,
+    symbols: SymbolTable,
+    // The types the file can see: the type world it's built against plus its own imports.
+    world: SimpleTypeWorld<FallibleNodeTypeRc>,
+    // Where those types are declared, with the namespace they're imported under.
+    type_files: Vec<(Option<String>, PathBuf)>,
+    uses: Vec<(String, PathBuf)>,
+    built: Option<(GraphSourceMap, NodeGraphFormalTypeAnalysis)>,
+}
+
+fn load_error_summary(path: &Path, err: &LoadError) -> FileDiagnostic {
+    let first = err
+        .diagnostics
+        .first()
+        .map_or(String::new(), |d| d.message());
+    FileDiagnostic {
+        span: SourceSpan::default(),
+        message: format!("type world `{}` has errors: {}", path.display(), first),
+        related: Vec::new(),
+    }
+}
+
+fn find_definition<'e>(
+    exprs: &'e [SpannedExpression],
+    name: &str,
+) -> Option<&'e DefinitionExpression> {
+    exprs.iter().find_map(|expr| match &expr.item {
+        NodeExpression::Definition(def) if def.name.item == name => Some(def.as_ref()),
+        NodeExpression::Definition(def) => find_definition(&def.body, name),
+        _ => None,
+    })
+}
+
+fn port_list<'p>(ports: impl Iterator<Item = (&'p str, &'p MaybeValueType)>) -> String {
+    ports
+        .map(|(name, typ)| match typ {
+            Ok(typ) => format!("{} @ {}", name, typ),
+            Err(e) => format!("{} @ {}", name, e),
+        })
+        .collect::<Vec<String>>()
+        .join("; ")
+}
+
+// Written like a .shadextypes declaration.
+fn type_signature(name: &str, typ: &NodeTypeInfo<MaybeValueType, ExecutionInformation>) -> String {
+    let inputs = port_list(
+        typ.inputs
+            .iter()
+            .map(|inp| (inp.name.as_str(), &inp.value_type)),
+    );
+    let outputs = port_list(
+        typ.outputs
+            .iter()
+            .map(|outp| (outp.name.as_deref().unwrap_or("_"), &outp.value_type)),
+    );
+    format!("{} = {} => {}", name, inputs, outputs)
+}
+
+fn param_list(params: &[(Spanned<String>, ValueType)]) -> String {
+    params
+        .iter()
+        .map(|(name, typ)| format!("{} @ {}", name.item, typ))
+        .collect::<Vec<String>>()
+        .join("; ")
+}
+
+fn definition_signature(def: &DefinitionExpression) -> String {
+    format!(
+        "def {}({}) => {}",
+        def.name.item,
+        param_list(&def.inputs),
+        param_list(&def.outputs)
+    )
+}
+
+// Type errors are reported on the node they start at. Errors a node only passes along from
+// upstream stay quiet, otherwise one mistake would light up everything downstream.
+fn type_diagnostics(
+    graph: &NodeGraph<FallibleNodeTypeRc>,
+    analysis: &NodeGraphFormalTypeAnalysis,
+    source_map: &GraphSourceMap,
+    path: &Path,
+) -> Vec<FileDiagnostic> {
+    let mut diagnostics: Vec<FileDiagnostic> = Vec::new();
+    for (node_ref, node) in graph.iter_nodes() {
+        let Some((Some(file), span)) = source_map.nodes.get(&node_ref) else {
+            continue;
+        };
+        // Broken types are reported by the type world they come from.
+        let (true, Ok(typ)) = (file == path, &node.annotation) else {
+            continue;
+        };
+        let input_errors = (0..typ.inputs.len()).filter_map(|input_ind| {
+            let notes = analysis.input_type_notes.get(&NodeInputReference {
+                source_node: node_ref,
+                input_ind,
+            });
+            notes?.as_ref().err()
+        });
+        let output_errors = (0..typ.outputs.len()).filter_map(|output_index| {
+            let notes = analysis.output_type_notes.get(&ValueRef {
+                node: node_ref,
+                output_index,
+            });
+            notes?.as_ref().err()
+        });
+        let mut errors = input_errors.chain(output_errors).filter(|e| !e.propagated);
+        if let Some(err) = errors.next() {
+            let related = err
+                .related
+                .iter()
+                .filter_map(|note| match source_map.nodes.get(&note.location.node()) {
+                    Some((Some(file), span)) if file == path => Some((*span, note.message.clone())),
+                    _ => None,
+                })
+                .collect();
+            diagnostics.push(FileDiagnostic {
+                span: *span,
+                message: err.message.clone(),
+                related,
+            });
+        }
+    }
+    diagnostics.sort_by_key(|d| (d.span.start, d.span.end));
+    diagnostics.dedup();
+    diagnostics
+}
+
+impl GraphAnalysis {
+    // `types` is the type world the graph is built against, if there is one.
+    pub fn new(loader: &dyn SourceLoader, types: Option<&Path>, path: &Path) -> Self {
+        let path = resolve_import(None, &path.to_string_lossy());
+        let mut analysis = GraphAnalysis {
+            text: loader.load(&path).unwrap_or_default(),
+            path,
+            diagnostics: Vec::new(),
+            parsed: false,
+            exprs: Vec::new(),
+            symbols: SymbolTable::default(),
+            world: SimpleTypeWorld::new(),
+            type_files: Vec::new(),
+            uses: Vec::new(),
+            built: None,
+        };
+
+        if let Some(types) = types {
+            match load_type_world(loader, types) {
+                Ok(world) => analysis.world = world,
+                Err(err) => analysis.diagnostics.push(load_error_summary(types, &err)),
+            }
+            analysis.type_files.push((None, types.to_path_buf()));
+        }
+        let root_world = analysis.world.clone();
+
+        match parse_whole_input(analysis.text.as_bytes()) {
+            Ok(exprs) => analysis.exprs = exprs,
+            Err(diag) => {
+                analysis.diagnostics.push(FileDiagnostic {
+                    span: diag.span,
+                    message: diag.message(),
+                    related: Vec::new(),
+                });
+                return analysis;
+            }
+        }
+        analysis.parsed = true;
+        analysis.symbols = SymbolTable::new(&analysis.exprs);
+
+        for expr in &analysis.exprs {
+            match &expr.item {
+                NodeExpression::Import(target, namespace) => {
+                    let file = resolve_import(Some(&analysis.path), &target.item);
+                    let namespace = namespace.as_ref().map(|ns| ns.item.clone());
+                    // Broken imports are reported by the build below.
+                    if let Ok(world) = load_type_world(loader, &file) {
+                        analysis.world.merge(world, namespace.as_deref());
+                    }
+                    analysis.type_files.push((namespace, file));
+                }
+                NodeExpression::Use(target, alias) => {
+                    let file = resolve_import(Some(&analysis.path), &target.item);
+                    analysis.uses.push((alias.item.clone(), file));
+                }
+                _ => {}
+            }
+        }
+
+        match load_node_graph_mapped(loader, &root_world, &analysis.path) {
+            Ok((graph, source_map)) => {
+                let types = NodeGraphFormalTypeAnalysis::analyze(&graph);
+                analysis.diagnostics.extend(type_diagnostics(
+                    &graph,
+                    &types,
+                    &source_map,
+                    &analysis.path,
+                ));
+                analysis.built = Some((source_map, types));
+            }
+            Err(err) => {
+                for diag in &err.diagnostics {
+                    let located = analysis.locate(diag, &err);
+                    analysis.diagnostics.push(located);
+                }
+            }
+        }
+        analysis
+    }
+
+    // While the file doesn't parse, what was known from the last time it did is still
+    // good enough for completion.
+    pub fn carry_over(&mut self, previous: GraphAnalysis) {
+        if self.parsed {
+            return;
+        }
+        self.exprs = previous.exprs;
+        self.symbols = previous.symbols;
+        self.uses = previous.uses;
+        self.built = previous.built;
+        if self.type_files.len() < previous.type_files.len() {
+            self.world = previous.world;
+            self.type_files = previous.type_files;
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    // Diagnostics from other files are shown at the import or `use` that brought them in.
+    fn locate(&self, diag: &Diagnostic, err: &LoadError) -> FileDiagnostic {
+        let Some(file) = diag.file.as_ref().filter(|f| **f != self.path) else {
+            return FileDiagnostic {
+                span: diag.span,
+                message: diag.message(),
+                related: Vec::new(),
+            };
+        };
+        let span = self
+            .exprs
+            .iter()
+            .find(|expr| match &expr.item {
+                NodeExpression::Import(target, _) | NodeExpression::Use(target, _) => {
+                    resolve_import(Some(&self.path), &target.item) == *file
+                }
+                _ => false,
+            })
+            .map_or(SourceSpan::default(), |expr| expr.span);
+        let position = err.sources.get(file).map_or(String::new(), |source| {
+            let loc = diag.span.locate(source);
+            format!(":{}:{}", loc.line, loc.column)
+        });
+        FileDiagnostic {
+            span,
+            message: format!("{}{}: {}", file.display(), position, diag.message()),
+            related: Vec::new(),
+        }
+    }
+
+    fn value_type_text(&self, value: Option<ValueRef>) -> String {
+        let Some(value) = value else {
+            return "free input (`NULL`)".to_string();
+        };
+        let notes = self
+            .built
+            .as_ref()
+            .and_then(|(_, types)| types.output_type_notes.get(&value));
+        match notes {
+            Some(Ok(notes)) => format!("`{}`", notes.formal_type),
+            Some(Err(e)) => format!("type error: {}", e.message),
+            None => "unknown type".to_string(),
+        }
+    }
+
+    fn built_binding(&self, span: SourceSpan) -> Option<&Binding> {
+        self.built.as_ref()?.0.bindings.get(&span)
+    }
+
+    fn describe_binding(&self, name: &str, span: SourceSpan) -> Option<String> {
+        let binding = self.symbols.symbols.iter().find(|s| s.span == span)?;
+        if let SymbolKind::Binding {
+            declared: Some(typ),
+            ..
+        } = &binding.kind
+        {
+            return Some(format!("`{}`: `{}`", name, typ));
+        }
+        Some(match self.built_binding(span)? {
+            Binding::Value(value) => format!("`{}`: {}", name, self.value_type_text(*value)),
+            Binding::Outputs(outputs) => {
+                let mut text = format!("`{}`: node", name);
+                for (output, value) in outputs {
+                    text += &format!("\n- `{}`: {}", output, self.value_type_text(*value));
+                }
+                text
+            }
+            Binding::Literal(literal) => format!("`{}`: literal `{}`", name, literal),
+        })
+    }
+
+    fn describe_type(&self, name: &str) -> Option<String> {
+        let signature = match find_definition(&self.exprs, name) {
+            Some(def) => definition_signature(def),
+            None => match self.world.node_types.get(name)? {
+                Ok(typ) => type_signature(name, typ),
+                Err(e) => format!("{} = {}", name, e),
+            },
+        };
+        Some(format!("```\n{}\n```", signature))
+    }
+
+    // The binding of the variable a field is selected from.
+    fn field_base_binding(&self, variable: &str, scope: usize, offset: usize) -> Option<&Binding> {
+        let binding = self.symbols.resolve(variable, scope, offset)?;
+        self.built_binding(binding.span)
+    }
+
+    pub fn hover(&self, offset: usize) -> Option<String> {
+        let symbol = self.symbols.at(offset)?;
+        match &symbol.kind {
+            SymbolKind::Reference => {
+                let binding =
+                    self.symbols
+                        .resolve(&symbol.name, symbol.scope, symbol.span.start)?;
+                self.describe_binding(&symbol.name, binding.span)
+            }
+            SymbolKind::Binding { .. } => self.describe_binding(&symbol.name, symbol.span),
+            SymbolKind::TypeName | SymbolKind::Definition => self.describe_type(&symbol.name),
+            SymbolKind::Field {
+                of_variable,
+                of_type,
+            } => {
+                if let Some(Binding::Outputs(outputs)) = of_variable
+                    .as_ref()
+                    .and_then(|v| self.field_base_binding(v, symbol.scope, symbol.span.start))
+                {
+                    let (_, value) = outputs.iter().find(|(name, _)| *name == symbol.name)?;
+                    return Some(format!(
+                        "`{}`: {}",
+                        symbol.name,
+                        self.value_type_text(*value)
+                    ));
+                }
+                let typ = self
+                    .world
+                    .node_types
+                    .get(of_type.as_ref()?)?
+                    .as_ref()
+                    .ok()?;
+                let output = typ
+                    .outputs
+                    .iter()
+                    .find(|outp| outp.name.as_deref() == Some(&symbol.name))?;
+                let declared = match &output.value_type {
+                    Ok(t) => t.to_string(),
+                    Err(e) => e.to_string(),
+                };
+                Some(format!("`{}`: `{}`", symbol.name, declared))
+            }
+        }
+    }
+
+    fn declaration_in_type_file(
+        loader: &dyn SourceLoader,
+        file: &Path,
+        name: &str,
+    ) -> Option<Location> {
+        let text = loader.load(file).ok()?;
+        declaration_spans(&text)
+            .into_iter()
+            .find(|decl| decl.item == name)
+            .map(|decl| (file.to_path_buf(), decl.span))
+    }
+
+    // Symbols of a `use`d file, from its text alone.
+    fn used_file_symbols(loader: &dyn SourceLoader, file: &Path) -> Option<SymbolTable> {
+        let text = loader.load(file).ok()?;
+        let exprs = parse_whole_input(text.as_bytes()).ok()?;
+        Some(SymbolTable::new(&exprs))
+    }
+
+    fn type_declaration(&self, loader: &dyn SourceLoader, name: &str) -> Option<Location> {
+        if let Some(def) = self.symbols.definition(name) {
+            return Some((self.path.clone(), def.span));
+        }
+        if let Some((prefix, rest)) = name.split_once("::") {
+            if let Some((_, file)) = self.uses.iter().find(|(alias, _)| alias == prefix) {
+                let symbols = Self::used_file_symbols(loader, file)?;
+                return symbols.definition(rest).map(|def| (file.clone(), def.span));
+            }
+            return self
+                .type_files
+                .iter()
+                .filter(|(ns, _)| ns.as_deref() == Some(prefix))
+                .find_map(|(_, file)| Self::declaration_in_type_file(loader, file, rest));
+        }
+        // Earlier type worlds win, same as when they're merged.
+        self.type_files
+            .iter()
+            .filter(|(ns, _)| ns.is_none())
+            .find_map(|(_, file)| Self::declaration_in_type_file(loader, file, name))
+    }
+
+    pub fn definition(&self, loader: &dyn SourceLoader, offset: usize) -> Option<Location> {
+        let symbol = self.symbols.at(offset)?;
+        match &symbol.kind {
+            SymbolKind::Reference => {
+                if let Some(binding) =
+                    self.symbols
+                        .resolve(&symbol.name, symbol.scope, symbol.span.start)
+                {
+                    return Some((self.path.clone(), binding.span));
+                }
+                // `Alias::name`, from a `use`d file.
+                let (prefix, rest) = symbol.name.split_once("::")?;
+                let (_, file) = self.uses.iter().find(|(alias, _)| alias == prefix)?;
+                let symbols = Self::used_file_symbols(loader, file)?;
+                let binding = symbols.resolve_at_end(rest)?;
+                Some((file.clone(), binding.span))
+            }
+            SymbolKind::Binding { .. } | SymbolKind::Definition => {
+                Some((self.path.clone(), symbol.span))
+            }
+            SymbolKind::TypeName => self.type_declaration(loader, &symbol.name),
+            SymbolKind::Field {
+                of_variable,
+                of_type,
+            } => {
+                let typ = match of_type {
+                    Some(typ) => typ.clone(),
+                    None => {
+                        let binding = self.symbols.resolve(
+                            of_variable.as_ref()?,
+                            symbol.scope,
+                            symbol.span.start,
+                        )?;
+                        match &binding.kind {
+                            SymbolKind::Binding {
+                                constructed: Some(typ),
+                                ..
+                            } => typ.clone(),
+                            _ => return None,
+                        }
+                    }
+                };
+                self.type_declaration(loader, &typ)
+            }
+        }
+    }
+
+    fn output_completions(&self, variable: &str, offset: usize) -> Vec<Completion> {
+        let scope = self.symbols.scope_at(offset);
+        let Some(binding) = self.symbols.resolve(variable, scope, offset) else {
+            return Vec::new();
+        };
+        let output = |label: &str, detail: Option<String>| Completion {
+            label: label.to_string(),
+            kind: CompletionKind::Output,
+            detail,
+        };
+        // Once built, the value knows its outputs, whatever made it.
+        if let Some(Binding::Outputs(outputs)) = self.built_binding(binding.span) {
+            return outputs
+                .iter()
+                .map(|(name, value)| output(name, Some(self.value_type_text(*value))))
+                .collect();
+        }
+        let SymbolKind::Binding {
+            constructed: Some(typ),
+            ..
+        } = &binding.kind
+        else {
+            return Vec::new();
+        };
+        if let Some(def) = find_definition(&self.exprs, typ) {
+            return def
+                .outputs
+                .iter()
+                .map(|(name, typ)| output(&name.item, Some(typ.to_string())))
+                .collect();
+        }
+        match self.world.node_types.get(typ) {
+            Some(Ok(typ)) => typ
+                .outputs
+                .iter()
+                .filter_map(|outp| {
+                    let detail = outp.value_type.as_ref().ok().map(ToString::to_string);
+                    Some(output(outp.name.as_deref()?, detail))
+                })
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn completions(&self, offset: usize) -> Vec<Completion> {
+        let before = self.text.get(..offset).unwrap_or(&self.text);
+        let word_start = before.trim_end_matches(|c: char| c.is_ascii_alphanumeric());
+        if let Some(base) = word_start.strip_suffix('.') {
+            let variable_start = base
+                .trim_end_matches(|c: char| c.is_ascii_alphanumeric() || c == ':')
+                .len();
+            return self.output_completions(&base[variable_start..], offset);
+        }
+
+        let mut completions: Vec<Completion> = self
+            .world
+            .node_types
+            .iter()
+            .map(|(name, typ)| Completion {
+                label: name.clone(),
+                kind: CompletionKind::NodeType,
+                detail: typ.as_ref().ok().map(|typ| type_signature(name, typ)),
+            })
+            .collect();
+        completions.extend(self.symbols.symbols.iter().filter_map(|s| {
+            if !matches!(s.kind, SymbolKind::Definition) {
+                return None;
+            }
+            Some(Completion {
+                label: s.name.clone(),
+                kind: CompletionKind::NodeType,
+                detail: find_definition(&self.exprs, &s.name).map(definition_signature),
+            })
+        }));
+        completions.sort_by(|a, b| a.label.cmp(&b.label));
+        let scope = self.symbols.scope_at(offset);
+        completions.extend(
+            self.symbols
+                .variable_names(scope)
+                .into_iter()
+                .map(|name| Completion {
+                    label: name.to_string(),
+                    kind: CompletionKind::Variable,
+                    detail: None,
+                }),
+        );
+        completions
+    }
+}
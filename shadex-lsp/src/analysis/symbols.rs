@@ -0,0 +1,204 @@
+use shadex_backend::{
+    parsing::{NodeExpression, SpannedExpression, diagnostics::SourceSpan},
+    typechecking::typetypes::ValueType,
+};
+
+#[derive(Debug, Clone)]
+pub enum SymbolKind {
+    // Where a name gets its value: an assignment, a destructuring or a def's signature.
+    // References from `visible_from` on see it.
+    Binding {
+        visible_from: usize,
+        // The node type, when the value is a construction written right there.
+        constructed: Option<String>,
+        // Def parameters and outputs have a type before anything is built.
+        declared: Option<ValueType>,
+    },
+    Reference,
+    TypeName,
+    // `.name` after a variable or a construction.
+    Field {
+        of_variable: Option<String>,
+        of_type: Option<String>,
+    },
+    Definition,
+}
+
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    pub name: String,
+    pub span: SourceSpan,
+    // 0 is the file itself, every def body gets its own.
+    pub scope: usize,
+    pub kind: SymbolKind,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct SymbolTable {
+    pub symbols: Vec<Symbol>,
+    // The whole def each scope is the body of. The file's own scope has none.
+    scopes: Vec<Option<SourceSpan>>,
+}
+
+fn constructed_type(expr: &SpannedExpression) -> Option<String> {
+    match &expr.item {
+        NodeExpression::Construction(name, _, _) => Some(name.item.clone()),
+        NodeExpression::Assignment(_, rhs) => constructed_type(rhs),
+        _ => None,
+    }
+}
+
+impl SymbolTable {
+    pub fn new(exprs: &[SpannedExpression]) -> Self {
+        let mut table = SymbolTable {
+            symbols: Vec::new(),
+            scopes: vec![None],
+        };
+        for expr in exprs {
+            table.collect(expr, 0);
+        }
+        table
+    }
+
+    fn push(&mut self, name: &str, span: SourceSpan, scope: usize, kind: SymbolKind) {
+        self.symbols.push(Symbol {
+            name: name.to_string(),
+            span,
+            scope,
+            kind,
+        });
+    }
+
+    fn collect(&mut self, expr: &SpannedExpression, scope: usize) {
+        match &expr.item {
+            NodeExpression::Identifier(name) => {
+                self.push(name, expr.span, scope, SymbolKind::Reference)
+            }
+            NodeExpression::Assignment(name, rhs) => {
+                self.collect(rhs, scope);
+                let kind = SymbolKind::Binding {
+                    visible_from: expr.span.end,
+                    constructed: constructed_type(rhs),
+                    declared: None,
+                };
+                self.push(&name.item, name.span, scope, kind);
+            }
+            NodeExpression::Destructure(names, rhs) => {
+                self.collect(rhs, scope);
+                for name in names {
+                    if let Some(item) = &name.item {
+                        let kind = SymbolKind::Binding {
+                            visible_from: expr.span.end,
+                            constructed: None,
+                            declared: None,
+                        };
+                        self.push(item, name.span, scope, kind);
+                    }
+                }
+            }
+            NodeExpression::Construction(name, _, args) => {
+                self.push(&name.item, name.span, scope, SymbolKind::TypeName);
+                for arg in args {
+                    self.collect(&arg.value, scope);
+                }
+            }
+            NodeExpression::Output(inner, field) => {
+                self.collect(inner, scope);
+                let of_variable = match &inner.item {
+                    NodeExpression::Identifier(name) => Some(name.clone()),
+                    _ => None,
+                };
+                let kind = SymbolKind::Field {
+                    of_variable,
+                    of_type: constructed_type(inner),
+                };
+                self.push(&field.item, field.span, scope, kind);
+            }
+            NodeExpression::Definition(def) => {
+                self.push(&def.name.item, def.name.span, scope, SymbolKind::Definition);
+                let body_scope = self.scopes.len();
+                self.scopes.push(Some(expr.span));
+                for (name, typ) in &def.inputs {
+                    let kind = SymbolKind::Binding {
+                        visible_from: name.span.end,
+                        constructed: None,
+                        declared: Some(typ.clone()),
+                    };
+                    self.push(&name.item, name.span, body_scope, kind);
+                }
+                // Outputs are only there to be hovered, the body's assignments are what counts.
+                for (name, typ) in &def.outputs {
+                    let kind = SymbolKind::Binding {
+                        visible_from: usize::MAX,
+                        constructed: None,
+                        declared: Some(typ.clone()),
+                    };
+                    self.push(&name.item, name.span, body_scope, kind);
+                }
+                for expr in &def.body {
+                    self.collect(expr, body_scope);
+                }
+            }
+            NodeExpression::FloatLiteral(_)
+            | NodeExpression::IntLiteral(_)
+            | NodeExpression::FreeVariable
+            | NodeExpression::Import(..)
+            | NodeExpression::Use(..) => {}
+        }
+    }
+
+    pub fn at(&self, offset: usize) -> Option<&Symbol> {
+        self.symbols
+            .iter()
+            .find(|s| s.span.start <= offset && offset <= s.span.end)
+    }
+
+    // The binding a reference to `name` at `offset` sees: the last one before it in the same scope.
+    pub fn resolve(&self, name: &str, scope: usize, offset: usize) -> Option<&Symbol> {
+        self.symbols
+            .iter()
+            .filter(|s| s.name == name && s.scope == scope)
+            .filter_map(|s| match s.kind {
+                SymbolKind::Binding { visible_from, .. } if visible_from <= offset => {
+                    Some((visible_from, s))
+                }
+                _ => None,
+            })
+            .max_by_key(|(visible_from, _)| *visible_from)
+            .map(|(_, s)| s)
+    }
+
+    // Where a name ends up once the whole file has run.
+    pub fn resolve_at_end(&self, name: &str) -> Option<&Symbol> {
+        self.resolve(name, 0, usize::MAX)
+    }
+
+    pub fn definition(&self, name: &str) -> Option<&Symbol> {
+        self.symbols
+            .iter()
+            .find(|s| s.name == name && matches!(s.kind, SymbolKind::Definition))
+    }
+
+    pub fn variable_names(&self, scope: usize) -> Vec<&str> {
+        let mut names: Vec<&str> = self
+            .symbols
+            .iter()
+            .filter(|s| s.scope == scope && matches!(s.kind, SymbolKind::Binding { .. }))
+            .map(|s| s.name.as_str())
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    // The innermost def body around `offset`, or 0.
+    pub fn scope_at(&self, offset: usize) -> usize {
+        self.scopes
+            .iter()
+            .enumerate()
+            .filter_map(|(scope, span)| Some((scope, (*span)?)))
+            .filter(|(_, span)| span.start <= offset && offset <= span.end)
+            .min_by_key(|(_, span)| span.len())
+            .map_or(0, |(scope, _)| scope)
+    }
+}
@@ -0,0 +1,9 @@
+use lsp_server::Connection;
+
+// Speaks LSP over stdio; editors start it themselves.
+fn main() -> Result<(), Box<dyn std::error::Error + Sync + Send>> {
+    let (connection, io_threads) = Connection::stdio();
+    shadex_lsp::server::run(connection)?;
+    io_threads.join()?;
+    Ok(())
+}
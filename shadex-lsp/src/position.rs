@@ -0,0 +1,43 @@
+use lsp_types::{Position, Range};
+use shadex_backend::parsing::diagnostics::SourceSpan;
+
+// LSP counts columns in UTF-16 code units, while spans are byte offsets.
+pub fn offset_to_position(text: &str, offset: usize) -> Position {
+    let mut offset = offset.min(text.len());
+    while !text.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    let before = &text[..offset];
+    let line_start = before.rfind('\n').map_or(0, |p| p + 1);
+    Position {
+        line: before.matches('\n').count() as u32,
+        character: before[line_start..].encode_utf16().count() as u32,
+    }
+}
+
+// Positions past the end of a line or of the text are clamped to it.
+pub fn position_to_offset(text: &str, position: Position) -> usize {
+    let mut line_start = 0;
+    for _ in 0..position.line {
+        match text[line_start..].find('\n') {
+            Some(p) => line_start += p + 1,
+            None => return text.len(),
+        }
+    }
+    let line = text[line_start..].split('\n').next().unwrap_or("");
+    let mut units = 0;
+    for (ind, c) in line.char_indices() {
+        if units >= position.character as usize {
+            return line_start + ind;
+        }
+        units += c.len_utf16();
+    }
+    line_start + line.len()
+}
+
+pub fn span_to_range(text: &str, span: SourceSpan) -> Range {
+    Range {
+        start: offset_to_position(text, span.start),
+        end: offset_to_position(text, span.end),
+    }
+}
@@ -0,0 +1,326 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    path::{Path, PathBuf},
+};
+
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::{
+    CompletionItem, CompletionItemKind, CompletionOptions, CompletionParams, CompletionResponse,
+    Diagnostic, DiagnosticRelatedInformation, DiagnosticSeverity, DidChangeTextDocumentParams,
+    DidCloseTextDocumentParams, DidOpenTextDocumentParams, GotoDefinitionParams,
+    GotoDefinitionResponse, Hover, HoverContents, HoverParams, HoverProviderCapability,
+    InitializeParams, Location, MarkupContent, MarkupKind, OneOf, Position,
+    PublishDiagnosticsParams, ServerCapabilities, TextDocumentSyncCapability, TextDocumentSyncKind,
+    Uri,
+    notification::{
+        DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, DidSaveTextDocument,
+        Notification as _, PublishDiagnostics,
+    },
+    request::{Completion, GotoDefinition, HoverRequest, Request as _},
+};
+use shadex_backend::parsing::imports::{FsLoader, SourceLoader, resolve_import};
+
+use crate::{
+    analysis::{CompletionKind, FileDiagnostic, GraphAnalysis, type_world_diagnostics},
+    position::{position_to_offset, span_to_range},
+};
+
+type ServerError = Box<dyn Error + Sync + Send>;
+
+fn uri_to_path(uri: &Uri) -> Option<PathBuf> {
+    let path = url::Url::parse(uri.as_str()).ok()?.to_file_path().ok()?;
+    Some(resolve_import(None, &path.to_string_lossy()))
+}
+
+fn path_to_uri(path: &Path) -> Option<Uri> {
+    url::Url::from_file_path(path).ok()?.as_str().parse().ok()
+}
+
+fn is_type_world(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "shadextypes")
+}
+
+struct Document {
+    uri: Uri,
+    text: String,
+}
+
+// Open documents shadow what's on disk, so imports see unsaved edits.
+struct OverlayLoader<'d> {
+    documents: &'d HashMap<PathBuf, Document>,
+}
+
+impl SourceLoader for OverlayLoader<'_> {
+    fn load(&self, path: &Path) -> Result<String, String> {
+        match self.documents.get(path) {
+            Some(doc) => Ok(doc.text.clone()),
+            None => FsLoader.load(path),
+        }
+    }
+}
+
+struct Server {
+    connection: Connection,
+    documents: HashMap<PathBuf, Document>,
+    graphs: HashMap<PathBuf, GraphAnalysis>,
+    // From `initializationOptions: { "typeWorld": "path" }`. Without it, a graph is built against
+    // the first .shadextypes file next to it.
+    type_world: Option<PathBuf>,
+}
+
+fn type_world_option(params: &InitializeParams) -> Option<PathBuf> {
+    let option = params
+        .initialization_options
+        .as_ref()?
+        .get("typeWorld")?
+        .as_str()?;
+    let path = PathBuf::from(option);
+    if path.is_absolute() {
+        return Some(path);
+    }
+    let root = params
+        .workspace_folders
+        .as_ref()
+        .and_then(|folders| folders.first())
+        .and_then(|folder| uri_to_path(&folder.uri))?;
+    Some(resolve_import(None, &root.join(path).to_string_lossy()))
+}
+
+pub fn run(connection: Connection) -> Result<(), ServerError> {
+    let capabilities = ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        definition_provider: Some(OneOf::Left(true)),
+        completion_provider: Some(CompletionOptions {
+            trigger_characters: Some(vec![".".to_string()]),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    let params = connection.initialize(serde_json::to_value(capabilities)?)?;
+    let params: InitializeParams = serde_json::from_value(params)?;
+
+    let mut server = Server {
+        type_world: type_world_option(&params),
+        connection,
+        documents: HashMap::new(),
+        graphs: HashMap::new(),
+    };
+    let receiver = server.connection.receiver.clone();
+    for message in &receiver {
+        match message {
+            Message::Request(req) => {
+                if server.connection.handle_shutdown(&req)? {
+                    return Ok(());
+                }
+                server.handle_request(req)?;
+            }
+            Message::Notification(not) => server.handle_notification(not)?,
+            Message::Response(_) => {}
+        }
+    }
+    Ok(())
+}
+
+impl Server {
+    fn loader(&self) -> OverlayLoader<'_> {
+        OverlayLoader {
+            documents: &self.documents,
+        }
+    }
+
+    fn types_for(&self, graph: &Path) -> Option<PathBuf> {
+        if self.type_world.is_some() {
+            return self.type_world.clone();
+        }
+        let mut candidates: Vec<PathBuf> = std::fs::read_dir(graph.parent()?)
+            .ok()?
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .filter(|path| is_type_world(path))
+            .collect();
+        candidates.sort();
+        candidates.into_iter().next()
+    }
+
+    fn handle_notification(&mut self, not: Notification) -> Result<(), ServerError> {
+        match not.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let params: DidOpenTextDocumentParams = serde_json::from_value(not.params)?;
+                let doc = params.text_document;
+                if let Some(path) = uri_to_path(&doc.uri) {
+                    let document = Document {
+                        uri: doc.uri,
+                        text: doc.text,
+                    };
+                    self.documents.insert(path, document);
+                }
+            }
+            DidChangeTextDocument::METHOD => {
+                let params: DidChangeTextDocumentParams = serde_json::from_value(not.params)?;
+                let path = uri_to_path(&params.text_document.uri);
+                // Full sync: the last change is the whole new text.
+                let text = params.content_changes.into_iter().last().map(|c| c.text);
+                if let Some((doc, text)) = path.and_then(|p| self.documents.get_mut(&p)).zip(text) {
+                    doc.text = text;
+                }
+            }
+            DidCloseTextDocument::METHOD => {
+                let params: DidCloseTextDocumentParams = serde_json::from_value(not.params)?;
+                if let Some(path) = uri_to_path(&params.text_document.uri) {
+                    self.documents.remove(&path);
+                    self.graphs.remove(&path);
+                    self.publish(params.text_document.uri, "", &[])?;
+                }
+            }
+            // Files that aren't open may have changed on disk.
+            DidSaveTextDocument::METHOD => {}
+            _ => return Ok(()),
+        }
+        self.refresh()
+    }
+
+    // Files depend on each other through imports, so every edit re-checks everything that's open.
+    fn refresh(&mut self) -> Result<(), ServerError> {
+        let mut results = Vec::new();
+        for (path, doc) in &self.documents {
+            if is_type_world(path) {
+                let diagnostics = type_world_diagnostics(&self.loader(), path);
+                results.push((doc.uri.clone(), doc.text.clone(), diagnostics));
+                continue;
+            }
+            let types = self.types_for(path);
+            let mut analysis = GraphAnalysis::new(&self.loader(), types.as_deref(), path);
+            if let Some(previous) = self.graphs.remove(path) {
+                analysis.carry_over(previous);
+            }
+            results.push((
+                doc.uri.clone(),
+                doc.text.clone(),
+                analysis.diagnostics.clone(),
+            ));
+            self.graphs.insert(path.clone(), analysis);
+        }
+        for (uri, text, diagnostics) in results {
+            self.publish(uri, &text, &diagnostics)?;
+        }
+        Ok(())
+    }
+
+    fn publish(
+        &self,
+        uri: Uri,
+        text: &str,
+        diagnostics: &[FileDiagnostic],
+    ) -> Result<(), ServerError> {
+        let diagnostics = diagnostics
+            .iter()
+            .map(|d| Diagnostic {
+                range: span_to_range(text, d.span),
+                severity: Some(DiagnosticSeverity::ERROR),
+                source: Some("shadex".to_string()),
+                message: d.message.clone(),
+                related_information: (!d.related.is_empty()).then(|| {
+                    d.related
+                        .iter()
+                        .map(|(span, message)| DiagnosticRelatedInformation {
+                            location: Location {
+                                uri: uri.clone(),
+                                range: span_to_range(text, *span),
+                            },
+                            message: message.clone(),
+                        })
+                        .collect()
+                }),
+                ..Default::default()
+            })
+            .collect();
+        let params = PublishDiagnosticsParams {
+            uri,
+            diagnostics,
+            version: None,
+        };
+        let not = Notification::new(PublishDiagnostics::METHOD.to_string(), params);
+        self.connection.sender.send(not.into())?;
+        Ok(())
+    }
+
+    fn handle_request(&mut self, req: Request) -> Result<(), ServerError> {
+        let id = req.id.clone();
+        let result = match req.method.as_str() {
+            HoverRequest::METHOD => self.respond::<HoverRequest>(req, Self::hover),
+            GotoDefinition::METHOD => self.respond::<GotoDefinition>(req, Self::definition),
+            Completion::METHOD => self.respond::<Completion>(req, Self::completion),
+            _ => Err((
+                ErrorCode::MethodNotFound,
+                format!("`{}` is not supported", req.method),
+            )),
+        };
+        let response = match result {
+            Ok(value) => Response::new_ok(id, value),
+            Err((code, message)) => Response::new_err(id, code as i32, message),
+        };
+        self.connection.sender.send(response.into())?;
+        Ok(())
+    }
+
+    fn respond<R: lsp_types::request::Request>(
+        &self,
+        req: Request,
+        handler: impl FnOnce(&Self, R::Params) -> R::Result,
+    ) -> Result<serde_json::Value, (ErrorCode, String)> {
+        let params = serde_json::from_value(req.params)
+            .map_err(|e| (ErrorCode::InvalidParams, e.to_string()))?;
+        serde_json::to_value(handler(self, params))
+            .map_err(|e| (ErrorCode::InternalError, e.to_string()))
+    }
+
+    fn graph_at(&self, uri: &Uri, position: Position) -> Option<(&GraphAnalysis, usize)> {
+        let analysis = self.graphs.get(&uri_to_path(uri)?)?;
+        Some((analysis, position_to_offset(analysis.text(), position)))
+    }
+
+    fn hover(&self, params: HoverParams) -> Option<Hover> {
+        let at = params.text_document_position_params;
+        let (analysis, offset) = self.graph_at(&at.text_document.uri, at.position)?;
+        Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: analysis.hover(offset)?,
+            }),
+            range: None,
+        })
+    }
+
+    fn definition(&self, params: GotoDefinitionParams) -> Option<GotoDefinitionResponse> {
+        let at = params.text_document_position_params;
+        let (analysis, offset) = self.graph_at(&at.text_document.uri, at.position)?;
+        let loader = self.loader();
+        let (file, span) = analysis.definition(&loader, offset)?;
+        let text = loader.load(&file).ok()?;
+        Some(GotoDefinitionResponse::Scalar(Location {
+            uri: path_to_uri(&file)?,
+            range: span_to_range(&text, span),
+        }))
+    }
+
+    fn completion(&self, params: CompletionParams) -> Option<CompletionResponse> {
+        let at = params.text_document_position;
+        let (analysis, offset) = self.graph_at(&at.text_document.uri, at.position)?;
+        let items = analysis
+            .completions(offset)
+            .into_iter()
+            .map(|c| CompletionItem {
+                label: c.label,
+                kind: Some(match c.kind {
+                    CompletionKind::NodeType => CompletionItemKind::CLASS,
+                    CompletionKind::Variable => CompletionItemKind::VARIABLE,
+                    CompletionKind::Output => CompletionItemKind::FIELD,
+                }),
+                detail: c.detail,
+                ..Default::default()
+            })
+            .collect();
+        Some(CompletionResponse::Array(items))
+    }
+}
@@ -0,0 +1,168 @@
+use std::path::{Path, PathBuf};
+
+use shadex_backend::parsing::imports::MemoryLoader;
+use shadex_lsp::analysis::{CompletionKind, GraphAnalysis, type_world_diagnostics};
+
+const TYPES: &str = include_str!("../../examples/typeland.shadextypes");
+
+fn loader(graph: &str) -> MemoryLoader {
+    MemoryLoader::new()
+        .with_file("/proj/types.shadextypes", TYPES)
+        .with_file("/proj/main.shadex", graph)
+}
+
+fn analyze(loader: &MemoryLoader) -> GraphAnalysis {
+    GraphAnalysis::new(
+        loader,
+        Some(Path::new("/proj/types.shadextypes")),
+        Path::new("/proj/main.shadex"),
+    )
+}
+
+fn offset_of(src: &str, needle: &str) -> usize {
+    src.find(needle).expect("needle not in source")
+}
+
+#[test]
+fn build_errors_become_diagnostics() {
+    let src = "A = Constant: 1()\nOut(Nope(A.val))";
+    let analysis = analyze(&loader(src));
+    assert_eq!(analysis.diagnostics.len(), 1);
+    assert_eq!(analysis.diagnostics[0].span.start, offset_of(src, "Nope"));
+}
+
+#[test]
+fn type_errors_are_reported_where_they_start() {
+    // An unbounded u32 can't be narrowed into a bounded index.
+    let types = format!(
+        "{}\nCount = => val @ u32 with builtin Constant\nPick = i @ u32[3] => val @ f32 with wgsl \"f32({{i}})\"",
+        TYPES
+    );
+    let src = "N = Count: 3()\nSum = Pick(N.val)\nOut(Sum.val)";
+    let loader = loader(src).with_file("/proj/types.shadextypes", types);
+    let analysis = analyze(&loader);
+    // Out inherits the error, but only Pick gets the diagnostic.
+    assert_eq!(analysis.diagnostics.len(), 1);
+    assert_eq!(analysis.diagnostics[0].span.start, offset_of(src, "Pick"));
+    // It points back at what it's reading from.
+    assert_eq!(
+        analysis.diagnostics[0].related[0].0.start,
+        offset_of(src, "Count")
+    );
+}
+
+#[test]
+fn hover_shows_formal_types() {
+    let src = "Col = Vec3(1, 2, 3).val\nOut(Col)";
+    let analysis = analyze(&loader(src));
+    let hover = analysis.hover(offset_of(src, "Col)") + 1).unwrap();
+    assert_eq!(hover, "`Col`: `(comp: [3] -> f32)`");
+
+    let hover = analysis.hover(offset_of(src, "Vec3")).unwrap();
+    assert!(hover.contains("Vec3 = x @ f32; y @ f32; z @ f32 => val @"));
+}
+
+#[test]
+fn hover_on_a_node_lists_its_outputs() {
+    let src = "V = Vec3(1, 2, 3)\nOut(V.val)";
+    let analysis = analyze(&loader(src));
+    let hover = analysis.hover(0).unwrap();
+    assert_eq!(hover, "`V`: node\n- `val`: `(comp: [3] -> f32)`");
+}
+
+#[test]
+fn definition_of_a_variable_is_its_last_assignment() {
+    let src = "A = Constant: 1()\nA = AddF(A.val, 1)\nOut(A.val)";
+    let loader = loader(src);
+    let analysis = analyze(&loader);
+
+    // Inside its own assignment, `A` still means the earlier one.
+    let (file, span) = analysis
+        .definition(&loader, offset_of(src, "A.val, 1"))
+        .unwrap();
+    assert_eq!(file, PathBuf::from("/proj/main.shadex"));
+    assert_eq!(span.start, 0);
+
+    let (_, span) = analysis
+        .definition(&loader, offset_of(src, "A.val)"))
+        .unwrap();
+    assert_eq!(span.start, offset_of(src, "A = AddF"));
+}
+
+#[test]
+fn definition_of_a_node_type_is_in_its_type_world() {
+    let src = "Out(MulF(1, 2).val)";
+    let loader = loader(src);
+    let analysis = analyze(&loader);
+    let (file, span) = analysis
+        .definition(&loader, offset_of(src, "MulF"))
+        .unwrap();
+    assert_eq!(file, PathBuf::from("/proj/types.shadextypes"));
+    assert_eq!(&TYPES[span.start..span.end], "MulF");
+}
+
+#[test]
+fn definition_of_a_def_and_its_parameters() {
+    let src = "def Twice(a @ f32) => out @ f32 { out = AddF(a, a).val }\nOut(Twice(1).out)";
+    let loader = loader(src);
+    let analysis = analyze(&loader);
+    let (_, span) = analysis
+        .definition(&loader, offset_of(src, "Twice(1)"))
+        .unwrap();
+    assert_eq!(span.start, offset_of(src, "Twice"));
+
+    let (_, span) = analysis
+        .definition(&loader, offset_of(src, "a, a"))
+        .unwrap();
+    assert_eq!(span.start, offset_of(src, "a @"));
+}
+
+#[test]
+fn completes_node_types_and_variables() {
+    let src = "Col = Vec3(1, 2, 3).val\nOut(Col)";
+    let analysis = analyze(&loader(src));
+    let completions = analysis.completions(offset_of(src, "Col)"));
+    let labels: Vec<(&str, CompletionKind)> = completions
+        .iter()
+        .map(|c| (c.label.as_str(), c.kind))
+        .collect();
+    assert!(labels.contains(&("AddF", CompletionKind::NodeType)));
+    assert!(labels.contains(&("Out", CompletionKind::NodeType)));
+    assert!(labels.contains(&("Col", CompletionKind::Variable)));
+}
+
+#[test]
+fn completes_outputs_after_a_dot() {
+    let src = "V = Vec3(1, 2, 3)\nOut(V.val)";
+    let analysis = analyze(&loader(src));
+    let completions = analysis.completions(offset_of(src, "val)"));
+    assert_eq!(completions.len(), 1);
+    assert_eq!(completions[0].label, "val");
+    assert_eq!(completions[0].kind, CompletionKind::Output);
+}
+
+#[test]
+fn completion_survives_a_broken_edit() {
+    let src = "V = Vec3(1, 2, 3)\nOut(V.val)";
+    let loader = loader(src);
+    let previous = analyze(&loader);
+
+    let broken = "V = Vec3(1, 2, 3)\nOut(V.";
+    let loader = loader.with_file("/proj/main.shadex", broken);
+    let mut analysis = analyze(&loader);
+    assert!(!analysis.diagnostics.is_empty());
+    analysis.carry_over(previous);
+    let completions = analysis.completions(broken.len());
+    assert_eq!(completions[0].label, "val");
+}
+
+#[test]
+fn type_world_files_get_their_own_diagnostics() {
+    let loader = MemoryLoader::new().with_file(
+        "/proj/types.shadextypes",
+        "Good = a @ f32 => val @ f32 with builtin Nope",
+    );
+    let diagnostics = type_world_diagnostics(&loader, Path::new("/proj/types.shadextypes"));
+    assert_eq!(diagnostics.len(), 1);
+    assert!(diagnostics[0].message.contains("Nope"));
+}
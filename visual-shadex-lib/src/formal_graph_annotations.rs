@@ -1,17 +1,17 @@
 use std::collections::HashMap;
 
-use serde::{Deserialize, Serialize};
 use shadex_backend::{
     nodegraph::{
-        FallibleNodeTypeRc, NodeAnnotation, NodeAnnotationHas, NodeGraph, NodeRef, TypedNodeGraph,
-        ValueRef,
+        FallibleNodeTypeRc, NodeAnnotation, NodeAnnotationHas, NodeGraph, NodeRef, ValueRef,
     },
     typechecking::{
-        NodeGraphFormalTypeAnalysis, NodeInputReference, typetypes::AccessibleFallibleType,
+        NodeGraphFormalTypeAnalysis, NodeInputReference,
+        explain::ArgumentExplanation,
+        typetypes::{AccessibleFallibleType, TypeError},
     },
 };
 
-use crate::visual_graph::{VNodeId, VNodeInputRef, VNodeOutputRef};
+use crate::visual_graph::VNodeId;
 
 #[derive(Debug, Clone)]
 pub struct MappedNodeAnnotation {
@@ -37,4 +37,33 @@ pub struct FormalGraph {
     pub formal_graph: NodeGraph<MappedNodeAnnotation>,
     pub typecheck: NodeGraphFormalTypeAnalysis,
     pub vnode_to_fnode: HashMap<VNodeId, NodeRef>,
+    // What nodes are called when explaining types, like `AddF#7`.
+    pub node_names: HashMap<NodeRef, String>,
+}
+
+impl FormalGraph {
+    // Where each argument of the output's type comes from, a line each.
+    pub fn explain_output(&self, val_ref: ValueRef) -> Vec<String> {
+        self.describe(self.typecheck.explain(&self.formal_graph, val_ref))
+    }
+
+    pub fn explain_input(&self, inp_ref: &NodeInputReference) -> Vec<String> {
+        self.describe(self.typecheck.explain_input(&self.formal_graph, inp_ref))
+    }
+
+    // Ports with type errors have nothing to explain. Their hover shows the error already, in the
+    // type description above these lines.
+    fn describe(&self, explained: Result<Vec<ArgumentExplanation>, TypeError>) -> Vec<String> {
+        let name = |node| {
+            self.node_names
+                .get(&node)
+                .cloned()
+                .unwrap_or_else(|| "?".to_string())
+        };
+        explained
+            .unwrap_or_default()
+            .iter()
+            .flat_map(|e| e.describe(name))
+            .collect()
+    }
 }
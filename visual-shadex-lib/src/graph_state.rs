@@ -9,7 +9,7 @@ use crate::{
 
 pub struct NodeGraphState {
     pub visual_graph: VisualNodeGraph,
-    pub formal_graph: Result<FormalGraph, ()>,
+    pub formal_graph: FormalGraph,
 }
 
 impl Serialize for NodeGraphState {
@@ -27,11 +27,7 @@ impl<'de> Deserialize<'de> for NodeGraphState {
         D: serde::Deserializer<'de>,
     {
         let vnode_graph = VisualNodeGraph::deserialize(deserializer)?;
-        let formal = vnode_graph.to_formal();
-        Ok(Self {
-            visual_graph: vnode_graph,
-            formal_graph: formal,
-        })
+        Ok(Self::from_visual(vnode_graph))
     }
 }
 
@@ -47,22 +43,27 @@ impl NodeGraphState {
         let typecheck = NodeGraphFormalTypeAnalysis::analyze(&ngraph);
         NodeGraphState {
             visual_graph: VisualNodeGraph::default(),
-            formal_graph: Ok(FormalGraph {
+            formal_graph: FormalGraph {
                 formal_graph: ngraph,
                 typecheck,
                 vnode_to_fnode: HashMap::new(),
-            }),
+                node_names: HashMap::new(),
+            },
+        }
+    }
+
+    pub fn from_visual(visual_graph: VisualNodeGraph) -> Self {
+        let formal = visual_graph.to_formal();
+        NodeGraphState {
+            visual_graph,
+            formal_graph: formal,
         }
     }
 
     pub fn show(&mut self, ui: &mut egui::Ui, mode: &mut InteractionState) -> bool {
-        let changed = self.visual_graph.show(
-            ui,
-            mode,
-            self.formal_graph.as_ref().map(|f| Some(f)).unwrap_or(None),
-        );
+        let changed = self.visual_graph.show(ui, mode, Some(&self.formal_graph));
         if changed {
-            self.formal_graph = self.visual_graph.to_formal();
+            self.visual_graph.sync_formal(&mut self.formal_graph);
         }
 
         changed
@@ -37,7 +37,7 @@ fn smoother_step(t: f32) -> f32 {
     ((6f32 * t - 15f32) * t + 10f32) * t.powi(3)
 }
 
-pub(crate) fn draw_line(start_pt: Pos2, end_pt: Pos2, steps: usize) -> Shape {
+pub(crate) fn draw_line(start_pt: Pos2, end_pt: Pos2, steps: usize, color: Color32) -> Shape {
     let dist = end_pt - start_pt;
     let steps = steps + 2;
     let pts: Vec<Pos2> = (0..=steps)
@@ -68,7 +68,7 @@ pub(crate) fn draw_line(start_pt: Pos2, end_pt: Pos2, steps: usize) -> Shape {
         fill: Color32::TRANSPARENT,
         stroke: PathStroke {
             width: 3f32,
-            color: egui::epaint::ColorMode::Solid(Color32::WHITE),
+            color: egui::epaint::ColorMode::Solid(color),
             kind: egui::StrokeKind::Middle,
         },
     };
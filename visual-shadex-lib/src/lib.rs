@@ -1,29 +1,17 @@
-use std::{collections::HashMap, hash::Hash};
-
-use egui::{
-    Color32, Pos2, Rect, Sense, Shape, Ui, Vec2, emath::TSTransform, epaint::CircleShape, layers,
-    pos2, vec2,
-};
-use serde::{Deserialize, Serialize, Serializer};
-use shadex_backend::{
-    execution::WGPURunner,
-    nodegraph::{NodeGraph, NodeRef},
-    typechecking::NodeGraphFormalTypeAnalysis,
-};
+use egui::{Color32, Pos2, Rect, Vec2, pos2};
+use serde::{Deserialize, Serialize};
+use shadex_backend::execution::WGPURunner;
 
 use crate::{
-    formal_graph_annotations::FormalGraph,
     graph_state::NodeGraphState,
-    visual_graph::{
-        AddInfo, ConstantInfo, VNodeInputRef, VNodeOutputRef, VisualNode, VisualNodeGraph,
-    },
+    visual_graph::{VNodeInputRef, VNodeOutputRef, VisualNodeGraph, text_format::GraphFileFormat},
 };
 
 pub mod graph_state;
 
 pub mod formal_graph_annotations;
 mod helpers;
-mod node_templates;
+pub mod node_templates;
 pub mod visual_graph;
 
 #[derive(Serialize, Deserialize)]
@@ -127,13 +115,12 @@ pub fn visual_shadex_test(
             *change = graphstate.show(ui, mode);
             if *change || force_render {
                 let mut executor = shadex_backend::execution::Executor::default();
-                let text = if let Ok(graph) = &graphstate.formal_graph {
-                    let res = executor.run(&graph.formal_graph, &graph.typecheck);
-                    if let Ok(prog) = res {
-                        log::info!("Executing.");
-                        runner.run_shader(&prog, &output_view.tex_view);
-                    }
-                };
+                let graph = &graphstate.formal_graph;
+                let res = executor.run(&graph.formal_graph, &graph.typecheck);
+                if let Ok(prog) = res {
+                    log::info!("Executing.");
+                    runner.run_shader(&prog, &output_view.tex_view);
+                }
             }
         });
 
@@ -148,3 +135,20 @@ pub struct GraphUIState {
     #[serde(skip)]
     pub interaction_state: InteractionState,
 }
+
+impl GraphUIState {
+    // Loads either a GUI save (JSON) or a hand-written .shadex graph, whichever `content` is.
+    pub fn load(content: &str) -> Result<GraphUIState, String> {
+        match GraphFileFormat::sniff(content) {
+            GraphFileFormat::Json => serde_json::from_str(content).map_err(|e| e.to_string()),
+            GraphFileFormat::Text => {
+                let vgraph = VisualNodeGraph::from_text(content).map_err(|e| e.render(content))?;
+                Ok(GraphUIState {
+                    view_state: ViewState::default(),
+                    graph_state: NodeGraphState::from_visual(vgraph),
+                    interaction_state: InteractionState::default(),
+                })
+            }
+        }
+    }
+}
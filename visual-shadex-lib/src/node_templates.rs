@@ -1,13 +1,17 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, fmt::Display, rc::Rc};
 
 use shadex_backend::{
-    nodegraph::NodeTypeInfo,
-    typechecking::typetypes::{PrimitiveType, U32Boundedness, ValueType},
+    execution::{ExecutionInformation, WgslTemplate},
+    nodegraph::{FallibleNodeTypeRc, InputInfo, NodeTypeInfo, OutputInfo},
+    typechecking::typetypes::{
+        MaybeValueType, PrimitiveType, TypeError, TypeErrorKind, U32Boundedness, ValueType,
+    },
 };
 
-// TODO: Replace this with some kind of "template-reduction-time" expression
 type TemplatePlaceholderIdentifier = String;
 
+pub type TemplateContext = HashMap<TemplatePlaceholderIdentifier, TemplatePlaceholder>;
+
 pub enum TemplatePlaceholder {
     U32Bound(u32),
     PrimitiveType(PrimitiveType),
@@ -18,26 +22,184 @@ pub enum TemplatePlaceholder {
     MiscellaneousF32s(Vec<f32>),
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TemplateError {
+    MissingPlaceholder(TemplatePlaceholderIdentifier),
+    // The placeholder is there, but holds something else than what it's used as.
+    WrongPlaceholderKind(TemplatePlaceholderIdentifier),
+    // Went below zero or past u32::MAX.
+    OutOfRange(String),
+    Parse(String),
+    // A WGSL body refers to an input the node doesn't end up with.
+    UnknownInput(String),
+}
+
+impl Display for TemplateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TemplateError::MissingPlaceholder(id) => write!(f, "no value for {}", id),
+            TemplateError::WrongPlaceholderKind(id) => write!(f, "{} can't be used here", id),
+            TemplateError::OutOfRange(expr) => write!(f, "{} is out of range", expr),
+            TemplateError::Parse(text) => write!(f, "can't make sense of {:?}", text),
+            TemplateError::UnknownInput(name) => write!(f, "body refers to missing input {}", name),
+        }
+    }
+}
+
+fn number(ctx: &TemplateContext, id: &str) -> Result<u32, TemplateError> {
+    match ctx.get(id) {
+        Some(TemplatePlaceholder::U32Bound(n)) => Ok(*n),
+        Some(_) => Err(TemplateError::WrongPlaceholderKind(id.to_string())),
+        None => Err(TemplateError::MissingPlaceholder(id.to_string())),
+    }
+}
+
+// Arithmetic on numeric placeholders, worked out when a template is reduced to a concrete type:
+// `N+1`, `N*M`, `(N-1)*2`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TemplateExpr {
+    Literal(u32),
+    Placeholder(TemplatePlaceholderIdentifier),
+    Add(Box<TemplateExpr>, Box<TemplateExpr>),
+    Sub(Box<TemplateExpr>, Box<TemplateExpr>),
+    Mul(Box<TemplateExpr>, Box<TemplateExpr>),
+}
+
+impl Display for TemplateExpr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TemplateExpr::Literal(n) => write!(f, "{}", n),
+            TemplateExpr::Placeholder(id) => write!(f, "{}", id),
+            TemplateExpr::Add(a, b) => write!(f, "({}+{})", a, b),
+            TemplateExpr::Sub(a, b) => write!(f, "({}-{})", a, b),
+            TemplateExpr::Mul(a, b) => write!(f, "({}*{})", a, b),
+        }
+    }
+}
+
+impl TemplateExpr {
+    pub fn parse(text: &str) -> Result<TemplateExpr, TemplateError> {
+        let tokens = tokenize(text)?;
+        let mut rest = tokens.as_slice();
+        let expr = parse_sum(&mut rest).ok_or_else(|| TemplateError::Parse(text.to_string()))?;
+        match rest {
+            [] => Ok(expr),
+            _ => Err(TemplateError::Parse(text.to_string())),
+        }
+    }
+
+    pub fn evaluate(&self, ctx: &TemplateContext) -> Result<u32, TemplateError> {
+        let (a, b, op): (_, _, fn(u32, u32) -> Option<u32>) = match self {
+            TemplateExpr::Literal(n) => return Ok(*n),
+            TemplateExpr::Placeholder(id) => return number(ctx, id),
+            TemplateExpr::Add(a, b) => (a, b, u32::checked_add),
+            TemplateExpr::Sub(a, b) => (a, b, u32::checked_sub),
+            TemplateExpr::Mul(a, b) => (a, b, u32::checked_mul),
+        };
+        op(a.evaluate(ctx)?, b.evaluate(ctx)?)
+            .ok_or_else(|| TemplateError::OutOfRange(self.to_string()))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Number(u32),
+    Name(String),
+    Op(char),
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, TemplateError> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if "+-*()".contains(c) {
+            chars.next();
+            tokens.push(Token::Op(c));
+        } else if c.is_ascii_alphanumeric() || c == '_' {
+            let mut word = String::new();
+            while let Some(&c) = chars
+                .peek()
+                .filter(|c| c.is_ascii_alphanumeric() || **c == '_')
+            {
+                word.push(c);
+                chars.next();
+            }
+            tokens.push(match word.parse() {
+                Ok(n) => Token::Number(n),
+                Err(_) if word.starts_with(|c: char| c.is_ascii_digit()) => {
+                    return Err(TemplateError::Parse(text.to_string()));
+                }
+                Err(_) => Token::Name(word),
+            });
+        } else {
+            return Err(TemplateError::Parse(text.to_string()));
+        }
+    }
+    Ok(tokens)
+}
+
+// sum := product (("+" | "-") product)*
+fn parse_sum(rest: &mut &[Token]) -> Option<TemplateExpr> {
+    let mut expr = parse_product(rest)?;
+    while let [Token::Op(op @ ('+' | '-')), tail @ ..] = *rest {
+        *rest = tail;
+        let rhs = Box::new(parse_product(rest)?);
+        expr = match op {
+            '+' => TemplateExpr::Add(Box::new(expr), rhs),
+            _ => TemplateExpr::Sub(Box::new(expr), rhs),
+        };
+    }
+    Some(expr)
+}
+
+// product := atom ("*" atom)*
+fn parse_product(rest: &mut &[Token]) -> Option<TemplateExpr> {
+    let mut expr = parse_atom(rest)?;
+    while let [Token::Op('*'), tail @ ..] = *rest {
+        *rest = tail;
+        expr = TemplateExpr::Mul(Box::new(expr), Box::new(parse_atom(rest)?));
+    }
+    Some(expr)
+}
+
+// atom := number | name | "(" sum ")"
+fn parse_atom(rest: &mut &[Token]) -> Option<TemplateExpr> {
+    let (first, tail) = rest.split_first()?;
+    *rest = tail;
+    match first {
+        Token::Number(n) => Some(TemplateExpr::Literal(*n)),
+        Token::Name(id) => Some(TemplateExpr::Placeholder(id.clone())),
+        Token::Op('(') => {
+            let expr = parse_sum(rest)?;
+            match rest.split_first() {
+                Some((Token::Op(')'), tail)) => {
+                    *rest = tail;
+                    Some(expr)
+                }
+                _ => None,
+            }
+        }
+        Token::Op(_) => None,
+    }
+}
+
+// Shorthand for templates written out in code. Only for expressions known to parse.
+fn expr(text: &str) -> TemplateExpr {
+    TemplateExpr::parse(text).unwrap()
+}
+
 pub enum U32BoundednessTemplate {
     Unbounded,
-    Bounded(u32),
-    BoundedPlaceholder(TemplatePlaceholderIdentifier),
+    Bounded(TemplateExpr),
 }
 
 impl U32BoundednessTemplate {
-    pub fn evaluate(
-        &self,
-        ctx: &HashMap<TemplatePlaceholderIdentifier, TemplatePlaceholder>,
-    ) -> Result<U32Boundedness, ()> {
+    pub fn evaluate(&self, ctx: &TemplateContext) -> Result<U32Boundedness, TemplateError> {
         match self {
             U32BoundednessTemplate::Unbounded => Ok(U32Boundedness::Unbounded),
-            U32BoundednessTemplate::Bounded(bd) => Ok(U32Boundedness::Bounded(*bd)),
-            U32BoundednessTemplate::BoundedPlaceholder(id) => {
-                Ok(U32Boundedness::Bounded(match ctx.get(id) {
-                    Some(TemplatePlaceholder::U32Bound(bd)) => Ok(*bd),
-                    _ => Err(()),
-                }?))
-            }
+            U32BoundednessTemplate::Bounded(bd) => Ok(U32Boundedness::Bounded(bd.evaluate(ctx)?)),
         }
     }
 }
@@ -50,14 +212,12 @@ pub enum PrimitiveTypeTemplate {
 }
 
 impl PrimitiveTypeTemplate {
-    pub fn evaluate(
-        &self,
-        ctx: &HashMap<TemplatePlaceholderIdentifier, TemplatePlaceholder>,
-    ) -> Result<PrimitiveType, ()> {
+    pub fn evaluate(&self, ctx: &TemplateContext) -> Result<PrimitiveType, TemplateError> {
         match self {
             PrimitiveTypeTemplate::Placeholder(id) => match ctx.get(id) {
                 Some(TemplatePlaceholder::PrimitiveType(typ)) => Ok(*typ),
-                _ => Err(()),
+                Some(_) => Err(TemplateError::WrongPlaceholderKind(id.clone())),
+                None => Err(TemplateError::MissingPlaceholder(id.clone())),
             },
             PrimitiveTypeTemplate::F32 => Ok(PrimitiveType::F32),
             PrimitiveTypeTemplate::I32 => Ok(PrimitiveType::I32),
@@ -71,37 +231,74 @@ impl PrimitiveTypeTemplate {
 pub enum StringTemplate {
     Literal(String),
     Placeholder(TemplatePlaceholderIdentifier),
+    // The value of a numeric expression, like the `2` in `v2`.
+    Number(TemplateExpr),
+    Concat(Vec<StringTemplate>),
+    // `each` once for every `index` below `count`, with `index` set to it.
+    Repeat {
+        index: TemplatePlaceholderIdentifier,
+        count: TemplateExpr,
+        separator: String,
+        each: Box<StringTemplate>,
+    },
 }
 
 impl StringTemplate {
-    pub fn evaluate(
-        &self,
-        ctx: &HashMap<TemplatePlaceholderIdentifier, TemplatePlaceholder>,
-    ) -> Result<String, ()> {
+    pub fn evaluate(&self, ctx: &mut TemplateContext) -> Result<String, TemplateError> {
         match self {
             StringTemplate::Literal(lit) => Ok(lit.clone()),
             StringTemplate::Placeholder(id) => match ctx.get(id) {
                 Some(TemplatePlaceholder::ArgumentName(name)) => Ok(name.clone()),
-                _ => Err(()),
+                Some(_) => Err(TemplateError::WrongPlaceholderKind(id.clone())),
+                None => Err(TemplateError::MissingPlaceholder(id.clone())),
             },
+            StringTemplate::Number(expr) => Ok(expr.evaluate(ctx)?.to_string()),
+            StringTemplate::Concat(parts) => parts.iter().map(|p| p.evaluate(ctx)).collect(),
+            StringTemplate::Repeat {
+                index,
+                count,
+                separator,
+                each,
+            } => Ok(repeat(ctx, index, count, |ctx| each.evaluate(ctx))?.join(separator)),
         }
     }
 }
 
+// Runs `f` for every index below `count`, with the index placeholder set. The placeholder is
+// only there while it runs.
+fn repeat<T>(
+    ctx: &mut TemplateContext,
+    index: &str,
+    count: &TemplateExpr,
+    mut f: impl FnMut(&mut TemplateContext) -> Result<T, TemplateError>,
+) -> Result<Vec<T>, TemplateError> {
+    let count = count.evaluate(ctx)?;
+    let shadowed = ctx.remove(index);
+    let results = (0..count)
+        .map(|i| {
+            ctx.insert(index.to_string(), TemplatePlaceholder::U32Bound(i));
+            f(ctx)
+        })
+        .collect();
+    ctx.remove(index);
+    if let Some(shadowed) = shadowed {
+        ctx.insert(index.to_string(), shadowed);
+    }
+    results
+}
+
 pub enum ValueTypeTemplate {
     Placeholder(TemplatePlaceholderIdentifier),
     ConcreteShape(ShapedValueTypeTemplate),
 }
 
 impl ValueTypeTemplate {
-    pub fn evaluate(
-        &self,
-        ctx: &HashMap<TemplatePlaceholderIdentifier, TemplatePlaceholder>,
-    ) -> Result<ValueType, ()> {
+    pub fn evaluate(&self, ctx: &mut TemplateContext) -> Result<ValueType, TemplateError> {
         match self {
             ValueTypeTemplate::Placeholder(id) => match ctx.get(id) {
                 Some(TemplatePlaceholder::ValueType(vt)) => Ok(vt.clone()),
-                _ => Err(()),
+                Some(_) => Err(TemplateError::WrongPlaceholderKind(id.clone())),
+                None => Err(TemplateError::MissingPlaceholder(id.clone())),
             },
             ValueTypeTemplate::ConcreteShape(shaped_value_type_template) => {
                 shaped_value_type_template.evaluate(ctx)
@@ -117,10 +314,16 @@ pub struct ShapedValueTypeTemplate {
 }
 
 impl ShapedValueTypeTemplate {
-    pub fn evaluate(
-        &self,
-        ctx: &HashMap<TemplatePlaceholderIdentifier, TemplatePlaceholder>,
-    ) -> Result<ValueType, ()> {
+    // Just a primitive, without arguments.
+    pub fn primitive(out: PrimitiveTypeTemplate) -> ValueTypeTemplate {
+        ValueTypeTemplate::ConcreteShape(ShapedValueTypeTemplate {
+            args: Vec::new(),
+            args_to_absorb: Vec::new(),
+            out,
+        })
+    }
+
+    pub fn evaluate(&self, ctx: &mut TemplateContext) -> Result<ValueType, TemplateError> {
         let mut args = HashMap::new();
         for (key, v) in &self.args {
             let name = key.evaluate(ctx)?;
@@ -131,7 +334,8 @@ impl ShapedValueTypeTemplate {
         for id in &self.args_to_absorb {
             let list = match ctx.get(id) {
                 Some(TemplatePlaceholder::ArgsList(list)) => Ok(list),
-                _ => Err(()),
+                Some(_) => Err(TemplateError::WrongPlaceholderKind(id.clone())),
+                None => Err(TemplateError::MissingPlaceholder(id.clone())),
             }?;
 
             for (n, v) in list {
@@ -146,17 +350,240 @@ impl ShapedValueTypeTemplate {
     }
 }
 
-pub struct TemplatedInputInfo {
-    name: StringTemplate,
-    val_type: ValueTypeTemplate,
+// A port, or a run of ports like `v0`..`v{N-1}` when repeated.
+pub struct TemplatedPortInfo {
+    pub name: StringTemplate,
+    pub val_type: ValueTypeTemplate,
+    // Placeholder for the port's index in the run, and how many there are.
+    pub repeat: Option<(TemplatePlaceholderIdentifier, TemplateExpr)>,
+}
+
+impl TemplatedPortInfo {
+    fn evaluate(
+        &self,
+        ctx: &mut TemplateContext,
+    ) -> Result<Vec<(String, ValueType)>, TemplateError> {
+        let port = |ctx: &mut TemplateContext| {
+            Ok((self.name.evaluate(ctx)?, self.val_type.evaluate(ctx)?))
+        };
+        match &self.repeat {
+            None => Ok(vec![port(ctx)?]),
+            Some((index, count)) => repeat(ctx, index, count, port),
+        }
+    }
 }
 
-pub struct TemplatedOutputInfo {
-    name: StringTemplate,
-    val_type: ValueTypeTemplate,
+pub type TemplatedInputInfo = TemplatedPortInfo;
+pub type TemplatedOutputInfo = TemplatedPortInfo;
+
+pub enum TemplatedExecution {
+    Fixed(ExecutionInformation),
+    // Reduced to a `with wgsl` body, so `{input}` holes refer to the instance's inputs.
+    Wgsl(StringTemplate),
 }
 
 pub struct TemplatedNodeTypeInfo {
-    input_types: Vec<TemplatedInputInfo>,
-    output_types: Vec<TemplatedOutputInfo>,
+    pub input_types: Vec<TemplatedInputInfo>,
+    pub output_types: Vec<TemplatedOutputInfo>,
+    pub execution: TemplatedExecution,
+}
+
+impl TemplatedNodeTypeInfo {
+    pub fn evaluate(
+        &self,
+        ctx: &mut TemplateContext,
+    ) -> Result<NodeTypeInfo<MaybeValueType, ExecutionInformation>, TemplateError> {
+        let mut inputs = Vec::new();
+        for port in &self.input_types {
+            inputs.extend(
+                port.evaluate(ctx)?
+                    .into_iter()
+                    .map(|(name, typ)| InputInfo {
+                        name,
+                        value_type: Ok(typ),
+                    }),
+            );
+        }
+        let mut outputs = Vec::new();
+        for port in &self.output_types {
+            outputs.extend(
+                port.evaluate(ctx)?
+                    .into_iter()
+                    .map(|(name, typ)| OutputInfo {
+                        name: Some(name),
+                        value_type: Ok(typ),
+                    }),
+            );
+        }
+        let annotation = match &self.execution {
+            TemplatedExecution::Fixed(exec) => exec.clone(),
+            TemplatedExecution::Wgsl(body) => {
+                let body = body.evaluate(ctx)?;
+                let names: Vec<&str> = inputs.iter().map(|i| i.name.as_str()).collect();
+                ExecutionInformation::Wgsl(
+                    WgslTemplate::parse(&body, &names).map_err(TemplateError::UnknownInput)?,
+                )
+            }
+        };
+        Ok(NodeTypeInfo {
+            inputs,
+            outputs,
+            annotation,
+        })
+    }
+}
+
+// A numeric parameter the user picks for a family.
+pub struct FamilyParam {
+    pub name: TemplatePlaceholderIdentifier,
+    pub default: u32,
+    pub min: u32,
+}
+
+// Node types that only differ by some numbers, like vectors of each size. Instances are named
+// after the family with the numbers in place, separated by `x`: `Vector4`, `Grid3x4`.
+pub struct NodeFamily {
+    pub name: &'static str,
+    pub prefix: &'static str,
+    pub params: Vec<FamilyParam>,
+    pub template: TemplatedNodeTypeInfo,
+}
+
+impl NodeFamily {
+    pub fn defaults(&self) -> Vec<u32> {
+        self.params.iter().map(|p| p.default).collect()
+    }
+
+    pub fn instance_name(&self, values: &[u32]) -> String {
+        let values: Vec<String> = values.iter().map(u32::to_string).collect();
+        format!("{}{}", self.prefix, values.join("x"))
+    }
+
+    // The values behind an instance name, if it's one of this family's.
+    pub fn parse_instance_name(&self, name: &str) -> Option<Vec<u32>> {
+        let values: Vec<u32> = name
+            .strip_prefix(self.prefix)?
+            .split('x')
+            .map(|v| v.parse().ok())
+            .collect::<Option<_>>()?;
+        let fits = values.len() == self.params.len()
+            && values.iter().zip(&self.params).all(|(v, p)| *v >= p.min);
+        fits.then_some(values)
+    }
+
+    pub fn instantiate(&self, values: &[u32]) -> FallibleNodeTypeRc {
+        let mut ctx: TemplateContext = self
+            .params
+            .iter()
+            .zip(values)
+            .map(|(p, v)| (p.name.clone(), TemplatePlaceholder::U32Bound(*v)))
+            .collect();
+        self.template.evaluate(&mut ctx).map(Rc::new).map_err(|e| {
+            TypeError::new(
+                TypeErrorKind::IncompleteNodeType,
+                format!("{}: {}", self.instance_name(values), e),
+            )
+        })
+    }
+}
+
+fn one_param(name: &str, default: u32) -> Vec<FamilyParam> {
+    vec![FamilyParam {
+        name: name.to_string(),
+        default,
+        min: 1,
+    }]
+}
+
+fn component_of(bound: &str, out: PrimitiveTypeTemplate) -> ValueTypeTemplate {
+    ValueTypeTemplate::ConcreteShape(ShapedValueTypeTemplate {
+        args: vec![(
+            StringTemplate::Literal("component".to_string()),
+            ShapedValueTypeTemplate::primitive(PrimitiveTypeTemplate::U32(
+                U32BoundednessTemplate::Bounded(expr(bound)),
+            )),
+        )],
+        args_to_absorb: Vec::new(),
+        out,
+    })
+}
+
+// N f32s `v0`..`v{N-1}` as one value over `component: [N]`.
+pub fn vector_n() -> NodeFamily {
+    let lit = |s: &str| StringTemplate::Literal(s.to_string());
+    let input = |i: &str| {
+        StringTemplate::Concat(vec![lit("{v"), StringTemplate::Number(expr(i)), lit("}")])
+    };
+    NodeFamily {
+        name: "VectorN",
+        prefix: "Vector",
+        params: one_param("N", 3),
+        template: TemplatedNodeTypeInfo {
+            input_types: vec![TemplatedPortInfo {
+                name: StringTemplate::Concat(vec![lit("v"), StringTemplate::Number(expr("i"))]),
+                val_type: ShapedValueTypeTemplate::primitive(PrimitiveTypeTemplate::F32),
+                repeat: Some(("i".to_string(), expr("N"))),
+            }],
+            output_types: vec![TemplatedPortInfo {
+                name: lit("val"),
+                val_type: component_of("N", PrimitiveTypeTemplate::F32),
+                repeat: None,
+            }],
+            execution: TemplatedExecution::Wgsl(StringTemplate::Concat(vec![
+                StringTemplate::Repeat {
+                    index: "i".to_string(),
+                    count: expr("N-1"),
+                    separator: " ".to_string(),
+                    each: Box::new(StringTemplate::Concat(vec![
+                        lit("if component == "),
+                        StringTemplate::Number(expr("i")),
+                        lit("u {{ return "),
+                        input("i"),
+                        lit("; }}"),
+                    ])),
+                },
+                lit(" return "),
+                input("N-1"),
+                lit(";"),
+            ])),
+        },
+    }
+}
+
+// The sum of the first N components of a value.
+pub fn sum_over() -> NodeFamily {
+    let lit = |s: &str| StringTemplate::Literal(s.to_string());
+    NodeFamily {
+        name: "SumOver[N]",
+        prefix: "SumOver",
+        params: one_param("N", 3),
+        template: TemplatedNodeTypeInfo {
+            input_types: vec![TemplatedPortInfo {
+                name: lit("v"),
+                val_type: component_of("N", PrimitiveTypeTemplate::F32),
+                repeat: None,
+            }],
+            output_types: vec![TemplatedPortInfo {
+                name: lit("val"),
+                val_type: ShapedValueTypeTemplate::primitive(PrimitiveTypeTemplate::F32),
+                repeat: None,
+            }],
+            // `v` is read at the loop's component, which shadows the function's own.
+            execution: TemplatedExecution::Wgsl(StringTemplate::Concat(vec![
+                lit("var total = 0.0; for (var i = 0u; i < "),
+                StringTemplate::Number(expr("N")),
+                lit("u; i++) {{ let component = i; total += {v}; }} return total;"),
+            ])),
+        },
+    }
+}
+
+pub const FAMILIES: [fn() -> NodeFamily; 2] = [vector_n, sum_over];
+
+// The family an instance name like `Vector4` belongs to, with its values.
+pub fn find_instance(name: &str) -> Option<(NodeFamily, Vec<u32>)> {
+    FAMILIES.iter().map(|f| f()).find_map(|family| {
+        let values = family.parse_instance_name(name)?;
+        Some((family, values))
+    })
 }
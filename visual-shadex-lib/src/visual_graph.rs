@@ -1,11 +1,19 @@
+pub mod text_format;
 mod vnode_infos;
-use std::{any, collections::HashMap};
+use std::{
+    any,
+    collections::{HashMap, HashSet},
+};
 
 use egui::{Color32, Pos2, Stroke};
 use serde::{Deserialize, Serialize};
 use shadex_backend::{
-    nodegraph::{FallibleNodeTypeRc, Node, NodeGraph, ValueRef},
-    typechecking::{NodeGraphFormalTypeAnalysis, typetypes::TypeError},
+    nodegraph::{FallibleNodeTypeRc, Node, NodeGraph, NodeInputReference, ValueRef},
+    typechecking::{
+        NodeGraphFormalTypeAnalysis,
+        incremental::{GraphEdit, same_signature},
+        typetypes::TypeError,
+    },
 };
 pub use vnode_infos::{VisualNode, VisualNodeInfo, add::AddInfo, constant::ConstantInfo};
 
@@ -13,10 +21,12 @@ use crate::{
     DraggingState, InteractionState,
     formal_graph_annotations::{FormalGraph, MappedNodeAnnotation},
     helpers::draw_line,
-    visual_graph::vnode_infos::{INITIALIZATIONS, VisualInputPort, VisualOutputPort},
+    visual_graph::vnode_infos::{
+        INITIALIZATIONS, VisualInputPort, VisualOutputPort, WIRE_COLOR, wire_color,
+    },
 };
 
-#[derive(Hash, PartialEq, Eq, Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Hash, PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct VNodeId(usize);
 
 #[derive(Clone, Copy, Deserialize, Serialize)]
@@ -76,6 +86,39 @@ impl VisualNodeGraph {
         self.nodes.get_mut(id).unwrap()
     }
 
+    // Whether feeding `inp` from `outp` would make a node depend on itself.
+    pub fn would_close_cycle(&self, inp: &VNodeInputRef, outp: &VNodeOutputRef) -> bool {
+        let mut seen = HashSet::new();
+        let mut to_visit = vec![outp.source];
+        while let Some(id) = to_visit.pop() {
+            if id == inp.dest {
+                return true;
+            }
+            if !seen.insert(id) {
+                continue;
+            }
+            for port in &self.get_node(&id).input_ports {
+                if let Some(src) = port.input_source {
+                    to_visit.push(src.source);
+                }
+            }
+        }
+        false
+    }
+
+    // Connects the two ports, unless that would close a loop. Returns whether it did.
+    pub fn connect(&mut self, inp: &VNodeInputRef, outp: &VNodeOutputRef) -> bool {
+        if self.would_close_cycle(inp, outp) {
+            return false;
+        }
+        let node = self.get_node_mut(&inp.dest);
+        if node.input_ports.len() <= inp.input_ind {
+            return false;
+        }
+        node.input_ports[inp.input_ind].input_source = Some(*outp);
+        true
+    }
+
     pub fn show(
         &mut self,
         ui: &mut egui::Ui,
@@ -125,22 +168,15 @@ impl VisualNodeGraph {
         }
 
         if any_drag_stopped {
+            // A connection that would close a loop is just dropped.
             if let crate::DraggingState::DraggingLineFromInputPort(inp, Some(outp)) = &mode.dragging
             {
-                let node = self.get_node_mut(&inp.dest);
-                if node.input_ports.len() > inp.input_ind {
-                    node.input_ports[inp.input_ind].input_source = Some(*outp);
-                }
-                *changed = true;
+                *changed = self.connect(inp, outp) || *changed;
             }
             if let crate::DraggingState::DraggingLineFromOutputPort(Some(inp), outp) =
                 &mode.dragging
             {
-                let node = self.get_node_mut(&inp.dest);
-                if node.input_ports.len() > inp.input_ind {
-                    node.input_ports[inp.input_ind].input_source = Some(*outp);
-                }
-                *changed = true;
+                *changed = self.connect(inp, outp) || *changed;
             }
 
             mode.dragging = crate::DraggingState::NotDraggingLine;
@@ -183,7 +219,14 @@ impl VisualNodeGraph {
 
                     let dest_pos = inp.pos;
                     let source_pos = self.get_node(&outp.source).output_ports[outp.output_ind].pos;
-                    line_vec.push(draw_line(source_pos, dest_pos, 100));
+                    let color = wire_color(
+                        &VNodeInputRef {
+                            dest: *nref,
+                            input_ind: ind,
+                        },
+                        formal_graph,
+                    );
+                    line_vec.push(draw_line(source_pos, dest_pos, 100, color));
                 }
             }
         }
@@ -215,7 +258,7 @@ impl VisualNodeGraph {
                 .map(|o| self.get_node(&o.source).output_ports[o.output_ind].pos)
                 .unwrap_or(mouse_pos);
 
-            line_vec.push(draw_line(opos, ipos, 100));
+            line_vec.push(draw_line(opos, ipos, 100, WIRE_COLOR));
         });
 
         ui.painter().set(lines_shape_id, line_vec);
@@ -249,48 +292,112 @@ impl VisualNodeGraph {
         *changed
     }
 
-    pub fn to_formal(&self) -> Result<FormalGraph, ()> {
-        let mut nodegraph = NodeGraph::<MappedNodeAnnotation>::new();
-        let mut vnode_to_fnode = HashMap::new();
+    pub fn to_formal(&self) -> FormalGraph {
+        let nodegraph = NodeGraph::<MappedNodeAnnotation>::new();
+        let mut formal = FormalGraph {
+            typecheck: NodeGraphFormalTypeAnalysis::analyze(&nodegraph),
+            formal_graph: nodegraph,
+            vnode_to_fnode: HashMap::new(),
+            node_names: HashMap::new(),
+        };
+        self.sync_formal(&mut formal);
+        formal
+    }
 
-        for n in &self.nodes {
-            let new_id = nodegraph.add_node(Node {
-                annotation: MappedNodeAnnotation {
-                    type_info: n.1.data.get_shadex_type(),
-                    source_node: n.0.clone(),
+    // Brings `formal` in line with this graph through edits, so only what changed is typechecked again.
+    pub fn sync_formal(&self, formal: &mut FormalGraph) {
+        let nodegraph = &mut formal.formal_graph;
+        let mut edits = Vec::new();
+
+        // Deleted nodes go. So do nodes whose port count changed; they come straight back under a
+        // new id, and their consumers get reconnected below.
+        let stale: Vec<VNodeId> = formal
+            .vnode_to_fnode
+            .iter()
+            .filter(
+                |(vid, fid)| match (self.nodes.get(vid), nodegraph.get_node(**fid)) {
+                    (Some(vnode), Some(fnode)) => vnode.input_ports.len() != fnode.inputs.len(),
+                    _ => true,
                 },
-                inputs: [None].repeat(n.1.input_ports.len()),
-                extra_data: None,
-            });
-
-            vnode_to_fnode.insert(*n.0, new_id);
+            )
+            .map(|(vid, _)| *vid)
+            .collect();
+        for vid in stale {
+            let fid = formal.vnode_to_fnode.remove(&vid).unwrap();
+            nodegraph.remove_node(fid);
+            edits.push(GraphEdit::NodeRemoved(fid));
         }
 
-        for n in &self.nodes {
-            let f_id = vnode_to_fnode.get(n.0).unwrap();
-
-            let fref = nodegraph.get_node_mut(*f_id).unwrap();
-
-            for inp_p in n.1.input_ports.iter().enumerate() {
-                if let Some(src) = inp_p.1.input_source {
-                    let src_node_id = vnode_to_fnode.get(&src.source).unwrap();
-                    let src_node_output_ind = src.output_ind;
-                    let node_output_ref = ValueRef {
-                        node: *src_node_id,
-                        output_index: src_node_output_ind,
+        // Go in id order so the same visual graph always gives the same formal graph.
+        let mut sorted_nodes: Vec<(&VNodeId, &VisualNode)> = self.nodes.iter().collect();
+        sorted_nodes.sort_by_key(|n| *n.0);
+
+        for (vid, vnode) in &sorted_nodes {
+            let type_info = vnode.data.get_shadex_type();
+            match formal.vnode_to_fnode.get(*vid) {
+                Some(fid) => {
+                    let fnode = nodegraph.get_node(*fid).unwrap();
+                    // A constant's value lives in its annotation, but doesn't change any types.
+                    if !same_signature(&fnode.annotation.type_info, &type_info) {
+                        edits.push(GraphEdit::TypeChanged(*fid));
+                    }
+                    let annotation = MappedNodeAnnotation {
+                        type_info,
+                        source_node: **vid,
                     };
+                    nodegraph.set_annotation(*fid, annotation);
+                    nodegraph.set_extra_data(*fid, vnode.data.extra_data());
+                }
+                None => {
+                    let fid = nodegraph.add_node(Node {
+                        annotation: MappedNodeAnnotation {
+                            type_info,
+                            source_node: **vid,
+                        },
+                        inputs: vec![None; vnode.input_ports.len()],
+                        extra_data: vnode.data.extra_data(),
+                    });
+                    formal.vnode_to_fnode.insert(**vid, fid);
+                    edits.push(GraphEdit::NodeAdded(fid));
+                }
+            }
+        }
 
-                    fref.inputs[inp_p.0] = Some(node_output_ref);
+        for (vid, vnode) in &sorted_nodes {
+            let fid = formal.vnode_to_fnode[*vid];
+            for (input_ind, port) in vnode.input_ports.iter().enumerate() {
+                // A port still pointing at a deleted node counts as free.
+                let wanted = port.input_source.and_then(|src| {
+                    Some(ValueRef {
+                        node: *formal.vnode_to_fnode.get(&src.source)?,
+                        output_index: src.output_ind,
+                    })
+                });
+                if nodegraph.get_node(fid).unwrap().inputs[input_ind] == wanted {
+                    continue;
+                }
+                let inp_ref = NodeInputReference {
+                    source_node: fid,
+                    input_ind,
+                };
+                // Both ends are in the graph, and the port counts match after the pass above.
+                match wanted {
+                    Some(src) => nodegraph.connect(inp_ref.clone(), src),
+                    None => nodegraph.disconnect(inp_ref.clone()),
                 }
+                .expect("formal node has the visual node's ports");
+                edits.push(GraphEdit::InputRewired(inp_ref));
             }
         }
 
-        let typecheck = NodeGraphFormalTypeAnalysis::analyze(&nodegraph);
+        formal.node_names = sorted_nodes
+            .iter()
+            .map(|(vid, vnode)| {
+                let name = format!("{}#{}", vnode.data.get_name(), vid.0);
+                (formal.vnode_to_fnode[*vid], name)
+            })
+            .collect();
 
-        Ok(FormalGraph {
-            formal_graph: nodegraph,
-            typecheck,
-            vnode_to_fnode,
-        })
+        formal.typecheck.update(nodegraph, &edits);
     }
 }
@@ -0,0 +1,182 @@
+use std::{collections::HashMap, fmt::Display};
+
+use egui::{Pos2, vec2};
+use shadex_backend::{
+    nodegraph::{FallibleNodeTypeRc, NodeGraph, NodeRef},
+    parsing::{
+        SimpleTypeWorld, construct_node_graph,
+        diagnostics::Diagnostic,
+        parse_whole_input,
+        printing::{PrintError, literal_text, print_node_graph},
+    },
+};
+
+use crate::visual_graph::{
+    VNodeId, VNodeOutputRef, VisualNode, VisualNodeGraph, VisualNodeInfo,
+    vnode_infos::{FROM_TEXT, VisualInputPort, VisualOutputPort, family::FamilyInfo},
+};
+
+// Spacing of the generated layout for graphs that come in as text.
+const COLUMN_WIDTH: f32 = 220f32;
+const ROW_HEIGHT: f32 = 120f32;
+
+// GUI saves are serde JSON, hand-written graphs are .shadex text. Both can end in `.shadex`,
+// so we go by content: JSON saves always start with an object.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphFileFormat {
+    Json,
+    Text,
+}
+
+impl GraphFileFormat {
+    pub fn sniff(content: &str) -> GraphFileFormat {
+        if content.trim_start().starts_with('{') {
+            GraphFileFormat::Json
+        } else {
+            GraphFileFormat::Text
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum TextImportError {
+    Parse(Diagnostic),
+    UnknownNodeType(NodeRef),
+    BadExtraData(String, Option<String>),
+}
+
+impl TextImportError {
+    pub fn render(&self, source: &str) -> String {
+        match self {
+            TextImportError::Parse(diag) => diag.render(source),
+            _ => self.to_string(),
+        }
+    }
+}
+
+impl Display for TextImportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TextImportError::Parse(diag) => write!(f, "{}", diag),
+            TextImportError::UnknownNodeType(node) => {
+                write!(f, "node {:?} has a type the editor doesn't know", node)
+            }
+            TextImportError::BadExtraData(name, data) => {
+                write!(f, "`{}` can't be built from extra data {:?}", name, data)
+            }
+        }
+    }
+}
+
+// The node types the editor knows, under the names they're printed with. Node families have an
+// instance for every size, which the world makes up as names come up.
+fn editor_type_world() -> SimpleTypeWorld<FallibleNodeTypeRc> {
+    let mut world = SimpleTypeWorld::new().with_resolver(|name| {
+        FamilyInfo::from_instance_name(name).map(|data| data.get_shadex_type())
+    });
+    for (name, from_text) in &FROM_TEXT {
+        if let Some(data) = from_text(None) {
+            world
+                .node_types
+                .insert(name.to_string(), data.get_shadex_type());
+        }
+    }
+    world
+}
+
+// Column of each node: the length of the longest path leading into it.
+fn layout_columns(graph: &NodeGraph<FallibleNodeTypeRc>) -> HashMap<NodeRef, usize> {
+    // Text can't describe a cycle, but anything on one would just stay in the first column.
+    let order = graph.topological_order().unwrap_or_else(|e| e.ordered);
+    let mut columns: HashMap<NodeRef, usize> = HashMap::new();
+    for node_ref in order {
+        let node = graph.get_node(node_ref).unwrap();
+        let col = node
+            .inputs
+            .iter()
+            .flatten()
+            .filter_map(|v| columns.get(&v.node))
+            .map(|col| col + 1)
+            .max()
+            .unwrap_or(0);
+        columns.insert(node_ref, col);
+    }
+    columns
+}
+
+impl VisualNodeGraph {
+    pub fn to_text(&self) -> Result<String, PrintError> {
+        let formal = self.to_formal();
+        print_node_graph(&formal.formal_graph, |_, annotation| {
+            Some(
+                self.get_node(&annotation.source_node)
+                    .data
+                    .get_name()
+                    .to_string(),
+            )
+        })
+    }
+
+    pub fn from_text(text: &str) -> Result<VisualNodeGraph, TextImportError> {
+        let world = editor_type_world();
+        let exprs = parse_whole_input(text.as_bytes()).map_err(TextImportError::Parse)?;
+        let formal = construct_node_graph(&world, exprs).map_err(TextImportError::Parse)?;
+
+        let columns = layout_columns(&formal);
+        let mut rows_used: HashMap<usize, usize> = HashMap::new();
+
+        let mut nodes: Vec<(NodeRef, _)> = formal.iter_nodes().collect();
+        nodes.sort_by_key(|(node_ref, _)| *node_ref);
+
+        let mut vgraph = VisualNodeGraph::default();
+        let mut fnode_to_vnode: HashMap<NodeRef, VNodeId> = HashMap::new();
+        for (node_ref, node) in &nodes {
+            // Literal arguments are constants of a type no world names; they become `Constant`s.
+            let name = world
+                .type_name(&node.annotation)
+                .or_else(|| literal_text(node).map(|_| "Constant".to_string()))
+                .ok_or(TextImportError::UnknownNodeType(*node_ref))?;
+            let data = match FROM_TEXT.iter().find(|(n, _)| *n == name) {
+                Some((_, from_text)) => from_text(node.extra_data.as_deref()).ok_or_else(|| {
+                    TextImportError::BadExtraData(name.clone(), node.extra_data.clone())
+                })?,
+                None => Box::new(
+                    FamilyInfo::from_instance_name(&name)
+                        .ok_or(TextImportError::UnknownNodeType(*node_ref))?,
+                ),
+            };
+
+            let col = columns.get(node_ref).copied().unwrap_or(0);
+            let row = rows_used.entry(col).or_insert(0);
+            let position = vec2(col as f32 * COLUMN_WIDTH, *row as f32 * ROW_HEIGHT);
+            *row += 1;
+
+            let output_count = data.get_shadex_type().map_or(0, |t| t.outputs.len());
+            let id = vgraph.add_node(VisualNode {
+                data,
+                position,
+                input_ports: vec![
+                    VisualInputPort {
+                        pos: Pos2::ZERO,
+                        input_source: None,
+                    };
+                    node.inputs.len()
+                ],
+                output_ports: vec![VisualOutputPort { pos: Pos2::ZERO }; output_count],
+            });
+            fnode_to_vnode.insert(*node_ref, id);
+        }
+
+        for (node_ref, node) in &nodes {
+            let vnode = vgraph.get_node_mut(&fnode_to_vnode[node_ref]);
+            for (port, inp) in vnode.input_ports.iter_mut().zip(&node.inputs) {
+                port.input_source = inp.map(|v| VNodeOutputRef {
+                    source: fnode_to_vnode[&v.node],
+                    output_ind: v.output_index,
+                });
+            }
+        }
+
+        Ok(vgraph)
+    }
+}
@@ -12,17 +12,25 @@ use shadex_backend::{
     },
     typechecking::{
         InputTypeNotes, NodeInputReference, OutputPromotion, OutputTypeNotes,
+        casts::CastKind,
         typetypes::{MaybeValueType, TypeError, ValueType},
     },
 };
 
 pub const UNDERSPECIFIED_COLOR: Color32 = Color32::BLUE;
 pub const ERROR_COLOR: Color32 = Color32::YELLOW;
+// Errors that only came from upstream, so the port that caused them stands out.
+pub const PROPAGATED_ERROR_COLOR: Color32 = Color32::DARK_GRAY;
 pub const HOVER_COLOR: Color32 = Color32::WHITE;
 pub const OVERSPECIFIED_COLOR: Color32 = Color32::ORANGE;
 pub const OK_COLOR: Color32 = Color32::LIGHT_RED;
 pub const FREE_VARIABLE_COLOR: Color32 = Color32::DARK_GREEN;
 
+pub const WIRE_COLOR: Color32 = Color32::WHITE;
+pub const LOSSLESS_CAST_WIRE_COLOR: Color32 = Color32::LIGHT_BLUE;
+pub const LOSSY_CAST_WIRE_COLOR: Color32 = Color32::GOLD;
+pub const WIDENING_CAST_WIRE_COLOR: Color32 = Color32::LIGHT_GREEN;
+
 pub const PORT_HB_WIDTH: f32 = 20f32;
 pub const PORT_VIS_RADIUS: f32 = 5f32;
 
@@ -40,6 +48,11 @@ pub trait VisualNodeInfo {
     fn show(&mut self, ui: &mut egui::Ui) -> bool;
     fn get_shadex_type(&self) -> FallibleNodeTypeRc;
     fn get_name(&self) -> &str;
+
+    // Written as `Name: extra_data(...)` when the graph is exported to .shadex text.
+    fn extra_data(&self) -> Option<String> {
+        None
+    }
 }
 
 #[derive(Clone, Deserialize, Serialize)]
@@ -100,6 +113,7 @@ impl<T: NotesWithDotColor> NotesWithDotColor for Result<T, TypeError> {
     fn pick_dot_color(&self) -> Color32 {
         match self {
             Ok(a) => a.pick_dot_color(),
+            Err(e) if e.propagated => PROPAGATED_ERROR_COLOR,
             Err(_) => ERROR_COLOR,
         }
     }
@@ -274,6 +288,14 @@ fn richtext_type_desc<T: RichTextPrintable>(
 
     match typecheck_type {
         Some(Ok(tr)) => tr.print_to_layout_job(ui, &mut job),
+        Some(Err(e)) if e.propagated => RichText::new(format!("Error upstream: {}", e))
+            .color(ui.style().visuals.text_color())
+            .append_to(
+                &mut job,
+                ui.style(),
+                egui::FontSelection::Default,
+                egui::Align::Min,
+            ),
         Some(Err(e)) => RichText::new(format!("Typecheck error! {}", e))
             .color(ui.style().visuals.text_color())
             .append_to(
@@ -295,6 +317,24 @@ fn richtext_type_desc<T: RichTextPrintable>(
     job
 }
 
+// Wires that go through a cast are drawn in the cast's colour, so they stand out from exact matches.
+pub(crate) fn wire_color(vref: &VNodeInputRef, formal_graph: Option<&FormalGraph>) -> Color32 {
+    let cast = formal_graph.and_then(|f| {
+        let fnode_id = f.vnode_to_fnode.get(&vref.dest)?;
+        let notes = f.typecheck.input_type_notes.get(&NodeInputReference {
+            source_node: *fnode_id,
+            input_ind: vref.input_ind,
+        })?;
+        notes.as_ref().ok()?.cast().map(|cast| cast.kind)
+    });
+    match cast {
+        None => WIRE_COLOR,
+        Some(CastKind::Lossless) => LOSSLESS_CAST_WIRE_COLOR,
+        Some(CastKind::Lossy) => LOSSY_CAST_WIRE_COLOR,
+        Some(CastKind::BoundedWidening) => WIDENING_CAST_WIRE_COLOR,
+    }
+}
+
 fn draw_input_port(
     ui: &mut egui::Ui,
     vref: &VNodeInputRef,
@@ -315,14 +355,15 @@ fn draw_input_port(
 
         let hovering = resp.contains_pointer() && mode.dragging.hover_inputs();
 
-        let detailed_type = formal_graph.and_then(|f| {
-            let fnode_id = f.vnode_to_fnode.get(&vref.dest)?;
-            let notes = f.typecheck.input_type_notes.get(&NodeInputReference {
-                source_node: *fnode_id,
+        let formal_ref = formal_graph.and_then(|f| {
+            Some(NodeInputReference {
+                source_node: *f.vnode_to_fnode.get(&vref.dest)?,
                 input_ind: vref.input_ind,
-            })?;
-            Some(notes)
+            })
         });
+        let detailed_type = formal_graph
+            .zip(formal_ref.as_ref())
+            .and_then(|(f, inp_ref)| f.typecheck.input_type_notes.get(inp_ref));
 
         let color = if hovering {
             HOVER_COLOR
@@ -366,6 +407,11 @@ fn draw_input_port(
                 detailed_type,
             ));
             ui.add(label);
+            if let (Some(f), Some(inp_ref)) = (formal_graph, &formal_ref) {
+                for line in f.explain_input(inp_ref) {
+                    ui.label(line);
+                }
+            }
         });
     });
 }
@@ -399,14 +445,15 @@ fn draw_output_ports(
                 Sense::hover() | Sense::drag(),
             );
 
-            let detailed_type = formal_graph.and_then(|f| {
-                let fnode_id = f.vnode_to_fnode.get(&node_ref)?;
-                let notes = f.typecheck.output_type_notes.get(&ValueRef {
-                    node: *fnode_id,
+            let formal_ref = formal_graph.and_then(|f| {
+                Some(ValueRef {
+                    node: *f.vnode_to_fnode.get(&node_ref)?,
                     output_index: i,
-                })?;
-                Some(notes)
+                })
             });
+            let detailed_type = formal_graph
+                .zip(formal_ref)
+                .and_then(|(f, val_ref)| f.typecheck.output_type_notes.get(&val_ref));
 
             let hovering = resp.contains_pointer() && mode.dragging.hover_outputs();
             let color = if hovering {
@@ -441,6 +488,11 @@ fn draw_output_ports(
             resp.on_hover_ui(|ui| {
                 let label = Label::new(richtext_type_desc(strin, ui, &p.value_type, detailed_type));
                 ui.add(label);
+                if let (Some(f), Some(val_ref)) = (formal_graph, formal_ref) {
+                    for line in f.explain_output(val_ref) {
+                        ui.label(line);
+                    }
+                }
             });
         });
     }
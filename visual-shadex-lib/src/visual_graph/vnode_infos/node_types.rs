@@ -1,15 +1,19 @@
-use crate::visual_graph::{
-    AddInfo, ConstantInfo, VisualNodeInfo,
-    vnode_infos::{attr::AttrInfo, out::OutInfo, vector3::Vector3Info},
+use crate::{
+    node_templates::{sum_over, vector_n},
+    visual_graph::{
+        AddInfo, ConstantInfo, VisualNodeInfo,
+        vnode_infos::{attr::AttrInfo, family::FamilyInfo, out::OutInfo, vector3::Vector3Info},
+    },
 };
 
 pub mod add;
 pub mod attr;
 pub mod constant;
+pub mod family;
 pub mod out;
 pub mod vector3;
 
-pub const INITIALIZATIONS: [(&str, fn() -> Box<dyn VisualNodeInfo>); 5] = [
+pub const INITIALIZATIONS: [(&str, fn() -> Box<dyn VisualNodeInfo>); 7] = [
     ("Constant", || Box::new(ConstantInfo::new(0.5f32))),
     ("Out", || Box::new(OutInfo::new())),
     ("Attr", || {
@@ -17,4 +21,34 @@ pub const INITIALIZATIONS: [(&str, fn() -> Box<dyn VisualNodeInfo>); 5] = [
     }),
     ("Add", || Box::new(AddInfo::new())),
     ("Vector", || Box::new(Vector3Info::new())),
+    ("VectorN", || {
+        Box::new(FamilyInfo::with_defaults(vector_n()))
+    }),
+    ("SumOver[N]", || {
+        Box::new(FamilyInfo::with_defaults(sum_over()))
+    }),
+];
+
+// Rebuilds node data from its .shadex spelling, keyed by `VisualNodeInfo::get_name`.
+// Returns `None` if the extra data doesn't make sense for that node type.
+// Instances of node families aren't in here, they go by `FamilyInfo::from_instance_name`.
+pub type FromTextFn = fn(Option<&str>) -> Option<Box<dyn VisualNodeInfo>>;
+
+pub const FROM_TEXT: [(&str, FromTextFn); 6] = [
+    ("Constant", |data| {
+        let val = data.map_or(Some(0.5f32), |d| d.trim().parse().ok())?;
+        Some(Box::new(ConstantInfo::new(val)))
+    }),
+    ("Out", |_| Some(Box::new(OutInfo::new()))),
+    ("Attr", |data| {
+        let (name, typ) = data.map_or(Some(("x", "f32")), |d| d.split_once('@'))?;
+        Some(Box::new(AttrInfo::new(
+            name.trim().to_string(),
+            typ.trim().to_string(),
+        )))
+    }),
+    ("Add", |_| Some(Box::new(AddInfo::new()))),
+    // What Add was called when it only took f32s.
+    ("AddF32", |_| Some(Box::new(AddInfo::new()))),
+    ("Vec3", |_| Some(Box::new(Vector3Info::new()))),
 ];
@@ -9,21 +9,22 @@ use shadex_backend::{
 use crate::visual_graph::VisualNodeInfo;
 
 thread_local! {
+    // a @ T; b @ T => val @ T, so it adds whatever it's given.
     static ADD_TYPE: FallibleNodeTypeRc =
         Ok(Rc::new(NodeTypeInfo {
                 inputs: vec![
                     InputInfo {
                         name: "a".to_string(),
-                        value_type: Ok(ValueType::primitive(PrimitiveType::F32)),
+                        value_type: Ok(ValueType::primitive(PrimitiveType::Var('T'))),
                     },
                     InputInfo {
                         name: "b".to_string(),
-                        value_type: Ok(ValueType::primitive(PrimitiveType::F32)),
+                        value_type: Ok(ValueType::primitive(PrimitiveType::Var('T'))),
                     },
                 ],
                 outputs: vec![OutputInfo {
                     name: None,
-                    value_type: Ok(ValueType::primitive(PrimitiveType::F32)),
+                    value_type: Ok(ValueType::primitive(PrimitiveType::Var('T'))),
                 }],
                 annotation: shadex_backend::execution::ExecutionInformation::Add
             }));
@@ -48,6 +49,6 @@ impl VisualNodeInfo for AddInfo {
     }
 
     fn get_name(&self) -> &str {
-        "AddF32"
+        "Add"
     }
 }
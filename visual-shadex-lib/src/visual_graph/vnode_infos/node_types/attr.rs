@@ -87,4 +87,8 @@ impl VisualNodeInfo for AttrInfo {
     fn get_name(&self) -> &str {
         "Attr"
     }
+
+    fn extra_data(&self) -> Option<String> {
+        Some(format!("{} @ {}", self.data.name, self.data.type_str))
+    }
 }
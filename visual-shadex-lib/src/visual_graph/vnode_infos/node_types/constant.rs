@@ -57,4 +57,8 @@ impl VisualNodeInfo for ConstantInfo {
     fn get_name(&self) -> &str {
         "Constant"
     }
+
+    fn extra_data(&self) -> Option<String> {
+        Some(self.val.to_string())
+    }
 }
@@ -0,0 +1,98 @@
+use serde::{Deserialize, Serialize};
+use shadex_backend::nodegraph::FallibleNodeTypeRc;
+
+use crate::{
+    node_templates::{FAMILIES, NodeFamily, find_instance},
+    visual_graph::VisualNodeInfo,
+};
+
+#[derive(Serialize, Deserialize)]
+pub struct FamilyInfoData {
+    pub family: String,
+    pub values: Vec<u32>,
+}
+
+// An instance of a node family, with its numbers picked in the node itself.
+pub struct FamilyInfo {
+    pub data: FamilyInfoData,
+    family: NodeFamily,
+    name: String,
+    instance: FallibleNodeTypeRc,
+}
+
+impl FamilyInfo {
+    pub fn new(family: NodeFamily, values: Vec<u32>) -> Self {
+        Self {
+            data: FamilyInfoData {
+                family: family.name.to_string(),
+                values: values.clone(),
+            },
+            name: family.instance_name(&values),
+            instance: family.instantiate(&values),
+            family,
+        }
+    }
+
+    pub fn with_defaults(family: NodeFamily) -> Self {
+        let values = family.defaults();
+        Self::new(family, values)
+    }
+
+    // Rebuilds an instance from its name, like `Vector4`.
+    pub fn from_instance_name(name: &str) -> Option<Self> {
+        let (family, values) = find_instance(name)?;
+        Some(Self::new(family, values))
+    }
+}
+
+impl Serialize for FamilyInfo {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        self.data.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for FamilyInfo {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let data = FamilyInfoData::deserialize(deserializer)?;
+        let family = FAMILIES
+            .iter()
+            .map(|f| f())
+            .find(|f| f.name == data.family)
+            .ok_or_else(|| serde::de::Error::custom(format!("no node family {}", data.family)))?;
+        Ok(Self::new(family, data.values))
+    }
+}
+
+#[typetag::serde]
+impl VisualNodeInfo for FamilyInfo {
+    fn show(&mut self, ui: &mut egui::Ui) -> bool {
+        let mut changed = false;
+        for (param, value) in self.family.params.iter().zip(&mut self.data.values) {
+            ui.horizontal(|ui| {
+                ui.label(&param.name);
+                changed |= ui
+                    .add(egui::DragValue::new(value).range(param.min..=64))
+                    .changed();
+            });
+        }
+        if changed {
+            self.name = self.family.instance_name(&self.data.values);
+            self.instance = self.family.instantiate(&self.data.values);
+        }
+        changed
+    }
+
+    fn get_shadex_type(&self) -> FallibleNodeTypeRc {
+        self.instance.clone()
+    }
+
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+}
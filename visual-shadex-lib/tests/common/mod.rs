@@ -0,0 +1,22 @@
+// Fixtures shared by the integration tests. Each test binary only uses some of them.
+#![allow(dead_code)]
+
+use shadex_backend::nodegraph::{NodeInputReference, NodeRef, ValueRef};
+
+// A small graph in the editor's node types, with a node of each kind the tests poke at.
+pub const SRC: &str =
+    "C = Constant: 1()\nD = Constant: 2()\nS = Add(C.0, D.0)\nOut(Vec3(S.0, S.0, D.0).0)";
+
+pub fn val(node: NodeRef) -> ValueRef {
+    ValueRef {
+        node,
+        output_index: 0,
+    }
+}
+
+pub fn input(node: NodeRef, input_ind: usize) -> NodeInputReference {
+    NodeInputReference {
+        source_node: node,
+        input_ind,
+    }
+}
@@ -0,0 +1,99 @@
+use std::collections::HashMap;
+
+use shadex_backend::{
+    execution::Executor,
+    typechecking::typetypes::{PrimitiveType, U32Boundedness},
+};
+use visual_shadex_lib::{
+    node_templates::{
+        TemplateContext, TemplateError, TemplateExpr, TemplatePlaceholder, sum_over, vector_n,
+    },
+    visual_graph::VisualNodeGraph,
+};
+
+fn context(values: &[(&str, u32)]) -> TemplateContext {
+    values
+        .iter()
+        .map(|(id, n)| (id.to_string(), TemplatePlaceholder::U32Bound(*n)))
+        .collect()
+}
+
+#[test]
+fn expressions_are_worked_out_at_reduction_time() {
+    let ctx = context(&[("N", 3), ("M", 4)]);
+    let eval = |text: &str| TemplateExpr::parse(text).unwrap().evaluate(&ctx);
+    assert_eq!(eval("N+1"), Ok(4));
+    assert_eq!(eval("N*M"), Ok(12));
+    assert_eq!(eval("(N - 1) * 2 + M"), Ok(8));
+    assert_eq!(eval("2+N*M"), Ok(14));
+    assert!(matches!(eval("N-4"), Err(TemplateError::OutOfRange(_))));
+    assert_eq!(
+        eval("K"),
+        Err(TemplateError::MissingPlaceholder("K".to_string()))
+    );
+    assert!(TemplateExpr::parse("N+").is_err());
+    assert!(TemplateExpr::parse("(N").is_err());
+    assert!(TemplateExpr::parse("3N").is_err());
+    assert_eq!(
+        TemplateExpr::parse("N").unwrap().evaluate(&HashMap::from([(
+            "N".to_string(),
+            TemplatePlaceholder::ArgumentName("x".to_string())
+        )])),
+        Err(TemplateError::WrongPlaceholderKind("N".to_string()))
+    );
+}
+
+#[test]
+fn families_reduce_to_concrete_types() {
+    let family = vector_n();
+    let vec4 = family.instantiate(&[4]).unwrap();
+    let names: Vec<&str> = vec4.inputs.iter().map(|i| i.name.as_str()).collect();
+    assert_eq!(names, ["v0", "v1", "v2", "v3"]);
+    let out = vec4.outputs[0].value_type.as_ref().unwrap();
+    assert_eq!(
+        out.inputs["component"].output,
+        PrimitiveType::U32(U32Boundedness::Bounded(4))
+    );
+    assert_eq!(family.instance_name(&[4]), "Vector4");
+    assert_eq!(family.parse_instance_name("Vector12"), Some(vec![12]));
+    assert_eq!(family.parse_instance_name("Vector0"), None);
+    assert_eq!(family.parse_instance_name("Vec3"), None);
+
+    assert_eq!(family.instantiate(&[1]).unwrap().inputs.len(), 1);
+    assert_eq!(sum_over().instantiate(&[5]).unwrap().inputs.len(), 1);
+}
+
+#[test]
+fn instances_round_trip_through_text_and_run() {
+    let src = "C = Constant: 1()\nV = Vector4(C.0, C.0, C.0, C.0)\nOut(Vec3(SumOver4(V.0).0, C.0, C.0).0)";
+    let vgraph = VisualNodeGraph::from_text(src).unwrap();
+    let text = vgraph.to_text().unwrap();
+    assert!(text.contains("Vector4("));
+    assert!(text.contains("SumOver4("));
+
+    let formal = vgraph.to_formal();
+    let prog = Executor::default()
+        .run(&formal.formal_graph, &formal.typecheck)
+        .ok()
+        .unwrap();
+    assert!(
+        prog.text
+            .contains("if component == 2u { return id0(x,y,component); }")
+    );
+    assert!(
+        prog.text
+            .contains("for (var i = 0u; i < 4u; i++) { let component = i;")
+    );
+}
+
+#[test]
+fn narrower_vectors_feed_wider_inputs() {
+    // Out reads three components, so the third one of the Vector2 is out of its domain.
+    let src = "C = Constant: 1()\nV = Vector2(C.0, C.0)\nOut(V.0)";
+    let formal = VisualNodeGraph::from_text(src).unwrap().to_formal();
+    let prog = Executor::default()
+        .run(&formal.formal_graph, &formal.typecheck)
+        .ok()
+        .unwrap();
+    assert!(prog.text.contains("min(component, u32(1))"));
+}
@@ -0,0 +1,95 @@
+use shadex_backend::{
+    execution::ExecutionInformation,
+    typechecking::typetypes::{TypeError, TypeErrorKind},
+};
+use visual_shadex_lib::{
+    formal_graph_annotations::FormalGraph,
+    visual_graph::{ConstantInfo, VNodeId, VisualNodeGraph},
+};
+
+mod common;
+use common::{SRC, input, val};
+
+fn find(vgraph: &VisualNodeGraph, formal: &FormalGraph, name: &str) -> VNodeId {
+    let mut ids: Vec<VNodeId> = formal
+        .vnode_to_fnode
+        .keys()
+        .copied()
+        .filter(|id| vgraph.get_node(id).data.get_name() == name)
+        .collect();
+    ids.sort();
+    ids[0]
+}
+
+fn assert_matches_rebuild(vgraph: &VisualNodeGraph, formal: &FormalGraph) {
+    let fresh = vgraph.to_formal();
+    assert_eq!(formal.vnode_to_fnode, fresh.vnode_to_fnode);
+    assert_eq!(formal.node_names, fresh.node_names);
+    assert_eq!(
+        formal.typecheck.output_type_notes,
+        fresh.typecheck.output_type_notes
+    );
+    assert_eq!(
+        formal.typecheck.input_type_notes,
+        fresh.typecheck.input_type_notes
+    );
+}
+
+#[test]
+fn edits_in_the_editor_are_synced() {
+    let mut vgraph = VisualNodeGraph::from_text(SRC).unwrap();
+    let mut formal = vgraph.to_formal();
+    let add = find(&vgraph, &formal, "Add");
+
+    vgraph.get_node_mut(&add).input_ports[0].input_source = None;
+    vgraph.sync_formal(&mut formal);
+    assert_matches_rebuild(&vgraph, &formal);
+    let fadd = formal.vnode_to_fnode[&add];
+    assert_eq!(formal.formal_graph.get_node(fadd).unwrap().inputs[0], None);
+}
+
+#[test]
+fn new_constant_values_need_no_retyping() {
+    let mut vgraph = VisualNodeGraph::from_text(SRC).unwrap();
+    let mut formal = vgraph.to_formal();
+    let constant = find(&vgraph, &formal, "Constant");
+    let fconst = formal.vnode_to_fnode[&constant];
+
+    // Plant a note downstream of the constant. Nothing should work it out again.
+    let add = formal.vnode_to_fnode[&find(&vgraph, &formal, "Add")];
+    let planted = Err(TypeError::new(TypeErrorKind::Other, "planted"));
+    let add_val = val(add);
+    formal
+        .typecheck
+        .output_type_notes
+        .insert(add_val, planted.clone());
+
+    vgraph.get_node_mut(&constant).data = Box::new(ConstantInfo::new(5.0));
+    vgraph.sync_formal(&mut formal);
+
+    let fnode = formal.formal_graph.get_node(fconst).unwrap();
+    let exec = &fnode.annotation.type_info.as_ref().unwrap().annotation;
+    assert!(matches!(exec, ExecutionInformation::Constant(v) if *v == 5.0));
+    assert_eq!(fnode.extra_data.as_deref(), Some("5"));
+    assert_eq!(formal.typecheck.output_type_notes[&add_val], planted);
+    assert!(formal.typecheck.input_type_notes[&input(add, 0)].is_ok());
+}
+
+#[test]
+fn hovered_ports_explain_their_arguments() {
+    let vgraph = VisualNodeGraph::from_text(SRC).unwrap();
+    let formal = vgraph.to_formal();
+    let out = formal.vnode_to_fnode[&find(&vgraph, &formal, "Out")];
+    let vec3 = find(&vgraph, &formal, "Vec3");
+
+    let lines = formal.explain_input(&input(out, 0));
+    assert_eq!(
+        lines[0],
+        format!(
+            "component comes from the declared output of {}",
+            formal.node_names[&formal.vnode_to_fnode[&vec3]]
+        )
+    );
+    assert!(lines[0].contains("Vec3#"));
+    assert_eq!(lines.len(), 3);
+}
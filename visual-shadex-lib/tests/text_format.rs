@@ -0,0 +1,77 @@
+use visual_shadex_lib::{
+    GraphUIState,
+    visual_graph::{
+        VisualNodeGraph,
+        text_format::{GraphFileFormat, TextImportError},
+    },
+};
+
+mod common;
+use common::SRC;
+
+#[test]
+fn files_are_told_apart_by_content() {
+    assert_eq!(
+        GraphFileFormat::sniff("{\"nodes\": {}}"),
+        GraphFileFormat::Json
+    );
+    assert_eq!(GraphFileFormat::sniff("\n  {}"), GraphFileFormat::Json);
+    assert_eq!(GraphFileFormat::sniff(SRC), GraphFileFormat::Text);
+    assert_eq!(GraphFileFormat::sniff(""), GraphFileFormat::Text);
+}
+
+#[test]
+fn text_survives_a_json_save() {
+    let text = VisualNodeGraph::from_text(SRC).unwrap().to_text().unwrap();
+
+    let loaded = GraphUIState::load(&text).unwrap();
+    let json = serde_json::to_string(&loaded).unwrap();
+    assert_eq!(GraphFileFormat::sniff(&json), GraphFileFormat::Json);
+
+    let reloaded = GraphUIState::load(&json).unwrap();
+    assert_eq!(reloaded.graph_state.visual_graph.to_text().unwrap(), text);
+}
+
+#[test]
+fn family_instances_are_known_by_name() {
+    // The sized instance isn't declared anywhere; the editor's type world makes it up on lookup.
+    let src = "C = Constant: 1()\nV = Vector4(C.0, C.0, C.0, C.0)\nS = SumOver4(V.0)";
+    let text = VisualNodeGraph::from_text(src).unwrap().to_text().unwrap();
+    assert_eq!(
+        text,
+        "n0 = Constant: 1()\nn1 = Vector4(n0.0, n0.0, n0.0, n0.0)\nn2 = SumOver4(n1.val)\n"
+    );
+
+    assert!(matches!(
+        VisualNodeGraph::from_text("V = Vector0()"),
+        Err(TextImportError::Parse(_))
+    ));
+}
+
+#[test]
+fn literals_come_in_as_constants() {
+    let text = VisualNodeGraph::from_text("S = Add(1, 2.5)\nOut(Vec3(S.0, S.0, 3).0)")
+        .unwrap()
+        .to_text()
+        .unwrap();
+    assert_eq!(
+        text,
+        "n0 = Constant: 1()
+n1 = Constant: 2.5()
+n2 = Add(n0.0, n1.0)
+n3 = Constant: 3()
+n4 = Vec3(n2.0, n2.0, n3.0)
+Out(n4.0)
+"
+    );
+}
+
+#[test]
+fn identical_nodes_survive_a_round_trip() {
+    let src = "A = Constant: 1()\nB = Constant: 1()\nX = Add(NULL, NULL)\nY = Add(NULL, NULL)";
+    let text = VisualNodeGraph::from_text(src).unwrap().to_text().unwrap();
+    assert_eq!(
+        text,
+        "n0 = Constant: 1()\nn1 = Constant: 1()\nn2 = Add(NULL, NULL)\nn3 = Add(NULL, NULL)\n"
+    );
+}